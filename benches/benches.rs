@@ -5,6 +5,7 @@ extern crate octo;
 use criterion::black_box;
 use criterion::Criterion;
 
+use octo::math::morton_encode;
 use octo::Octree;
 
 fn bench(c: &mut Criterion) {
@@ -13,7 +14,15 @@ fn bench(c: &mut Criterion) {
     let mut octree = Octree::<u8>::new(16).unwrap();
     c.bench_function("insert", move |b| {
         b.iter(|| {
-            black_box(octree.insert([12, 6, 8], 255).unwrap());
+            black_box(octree.insert([12, 6, 8], 255)).unwrap();
+        })
+    });
+
+    c.bench_function("insert_deep", |b| {
+        b.iter(|| {
+            let mut octree = Octree::<u8>::new(32768).unwrap();
+            octree.insert([12345, 6789, 30000], 255).unwrap();
+            black_box(octree);
         })
     });
 
@@ -24,6 +33,90 @@ fn bench(c: &mut Criterion) {
             black_box(octree.at([12, 6, 8]).unwrap());
         })
     });
+
+    let octree =
+        Octree::<u8>::from_fn(256, |loc| Some(((loc[0] ^ loc[1] ^ loc[2]) % 3) as u8)).unwrap();
+    let mut cached = octree.clone();
+    cached.enable_path_cache(8);
+    let neighbourhood = |visit: &mut dyn FnMut([u16; 3])| {
+        for x in 100..104u16 {
+            for y in 60..64u16 {
+                for z in 200..204u16 {
+                    visit([x, y, z]);
+                }
+            }
+        }
+    };
+    let mut uncached_writes = octree.clone();
+    let mut cached_writes = cached.clone();
+    let dense = octree.clone();
+    let flat = octree.to_flat();
+    let scattered = |at: &dyn Fn([u16; 3]) -> Option<u8>| {
+        for i in 0..64u16 {
+            black_box(at([i * 3, 255 - i * 2, i * 4]));
+        }
+    };
+    c.bench_function("at_pointer", move |b| {
+        b.iter(|| scattered(&|loc| dense.at(loc)))
+    });
+    c.bench_function("at_flat", move |b| {
+        b.iter(|| scattered(&|loc| flat.at(loc)))
+    });
+    c.bench_function("at_coherent", move |b| {
+        b.iter(|| {
+            neighbourhood(&mut |loc| {
+                black_box(octree.at(loc));
+            })
+        })
+    });
+    c.bench_function("at_coherent_cached", move |b| {
+        b.iter(|| {
+            neighbourhood(&mut |loc| {
+                black_box(cached.at_cached(loc));
+            })
+        })
+    });
+    let mut value = 0u8;
+    c.bench_function("insert_coherent", move |b| {
+        b.iter(|| {
+            value = value.wrapping_add(1);
+            neighbourhood(&mut |loc| uncached_writes.insert(loc, value ^ loc[0] as u8).unwrap())
+        })
+    });
+    c.bench_function("insert_coherent_cached", move |b| {
+        b.iter(|| {
+            value = value.wrapping_add(1);
+            neighbourhood(&mut |loc| cached_writes.insert(loc, value ^ loc[0] as u8).unwrap())
+        })
+    });
+
+    let mut voxels = vec![];
+    for x in 0..32u16 {
+        for y in 0..32u16 {
+            for z in 0..32u16 {
+                if (x + y + z) % 2 == 0 || x < 16 {
+                    voxels.push(([x, y, z], (x % 4) as u8));
+                }
+            }
+        }
+    }
+    let mut sorted: Vec<(u64, u8)> = voxels
+        .iter()
+        .map(|&(loc, data)| (morton_encode(loc), data))
+        .collect();
+    sorted.sort_by_key(|&(key, _)| key);
+
+    c.bench_function("insert_many", move |b| {
+        b.iter(|| {
+            let mut octree = Octree::<u8>::new(32).unwrap();
+            octree.insert_many(voxels.clone()).unwrap();
+            black_box(octree);
+        })
+    });
+
+    c.bench_function("from_morton_sorted", move |b| {
+        b.iter(|| black_box(Octree::<u8>::from_morton_sorted(32, sorted.clone()).unwrap()))
+    });
 }
 
 criterion_group!(benches, bench);
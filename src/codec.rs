@@ -0,0 +1,76 @@
+use std::io::{self, Read, Write};
+
+/// Fixed binary encoding of a voxel value, used by the binary formats of an `Octree<T>`
+///
+/// Implemented for the primitive numeric types and `bool`, each written in little-endian order.
+pub trait VoxelCodec: Sized {
+    /// Write the value to `writer`
+    fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()>;
+
+    /// Read a value written by `write_to` from `reader`
+    fn read_from<R: Read>(reader: &mut R) -> io::Result<Self>;
+}
+
+macro_rules! impl_voxel_codec {
+    ($($t:ty),*) => {
+        $(
+            impl VoxelCodec for $t {
+                fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+                    writer.write_all(&self.to_le_bytes())
+                }
+
+                fn read_from<R: Read>(reader: &mut R) -> io::Result<$t> {
+                    let mut bytes = [0; std::mem::size_of::<$t>()];
+                    reader.read_exact(&mut bytes)?;
+                    Ok(<$t>::from_le_bytes(bytes))
+                }
+            }
+        )*
+    };
+}
+
+impl_voxel_codec!(u8, u16, u32, u64, i8, i16, i32, i64, f32, f64);
+
+impl VoxelCodec for bool {
+    fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        u8::from(*self).write_to(writer)
+    }
+
+    fn read_from<R: Read>(reader: &mut R) -> io::Result<bool> {
+        match u8::read_from(reader)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidData, "invalid bool")),
+        }
+    }
+}
+
+/// Write a location as three little-endian `u16` coordinates
+pub(crate) fn write_loc<W: Write>(writer: &mut W, loc: [u16; 3]) -> io::Result<()> {
+    loc.iter().try_for_each(|coord| coord.write_to(writer))
+}
+
+/// Read a location written by `write_loc`
+pub(crate) fn read_loc<R: Read>(reader: &mut R) -> io::Result<[u16; 3]> {
+    Ok([
+        u16::read_from(reader)?,
+        u16::read_from(reader)?,
+        u16::read_from(reader)?,
+    ])
+}
+
+/// Get the CRC-32 (IEEE) checksum of `bytes`
+pub(crate) fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
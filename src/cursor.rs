@@ -0,0 +1,147 @@
+use node::{child_index, child_origin, OctreeNode};
+use octree::Axis;
+
+/// Navigable handle into an `Octree<T>`, for repeated access to nearby locations
+///
+/// The cursor remembers the path from the root to its current location, so moving a short
+/// distance only re-descends from the nearest common ancestor rather than from the root.
+pub struct Cursor<'a, T: 'a> {
+    extents: [u16; 3],
+    default: Option<T>,
+    loc: [u16; 3],
+    path: Vec<(&'a OctreeNode<T>, [u16; 3])>,
+}
+
+impl<'a, T> Cursor<'a, T>
+where
+    T: Copy + PartialEq,
+{
+    /// Create a new `Cursor<T>` positioned at a location within the root node
+    pub(crate) fn new(
+        root: &'a OctreeNode<T>,
+        extents: [u16; 3],
+        default: Option<T>,
+        loc: [u16; 3],
+    ) -> Cursor<'a, T> {
+        let mut cursor = Cursor {
+            extents,
+            default,
+            loc,
+            path: vec![(root, [0, 0, 0])],
+        };
+        cursor.descend();
+        cursor
+    }
+
+    /// Returns the current location of the `Cursor<T>`
+    pub fn loc(&self) -> [u16; 3] {
+        self.loc
+    }
+
+    /// Get the value stored at the current location of the `Cursor<T>`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([0, 0, 0], 255).unwrap();
+    /// let cursor = octree.cursor([0, 0, 0]).unwrap();
+    ///
+    /// assert_eq!(cursor.value(), Some(255));
+    /// ```
+    ///
+    pub fn value(&self) -> Option<T> {
+        let (node, _) = self.path[self.path.len() - 1];
+        if node.leaf() {
+            node.get().or(self.default)
+        } else {
+            self.default
+        }
+    }
+
+    /// Move the `Cursor<T>` to a new location
+    ///
+    /// Returns `false`, leaving the `Cursor<T>` where it was, if the location is out of bounds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([12, 10, 6], 128).unwrap();
+    /// let mut cursor = octree.cursor([0, 0, 0]).unwrap();
+    ///
+    /// assert!(cursor.move_to([12, 10, 6]));
+    /// assert_eq!(cursor.value(), Some(128));
+    /// assert!(!cursor.move_to([16, 0, 0]));
+    /// ```
+    ///
+    pub fn move_to(&mut self, loc: [u16; 3]) -> bool {
+        if (0..3).any(|axis| loc[axis] >= self.extents[axis]) {
+            return false;
+        }
+
+        self.loc = loc;
+        while self.path.len() > 1 && !self.current_contains(loc) {
+            self.path.pop();
+        }
+        self.descend();
+        true
+    }
+
+    /// Move the `Cursor<T>` along an axis by a signed number of voxels
+    ///
+    /// Returns `false`, leaving the `Cursor<T>` where it was, if the move would leave the bounds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::{Axis, Octree};
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([1, 0, 0], 255).unwrap();
+    /// let mut cursor = octree.cursor([0, 0, 0]).unwrap();
+    ///
+    /// assert!(cursor.step(Axis::X, 1));
+    /// assert_eq!(cursor.value(), Some(255));
+    /// assert!(!cursor.step(Axis::Y, -1));
+    /// ```
+    ///
+    pub fn step(&mut self, axis: Axis, delta: i32) -> bool {
+        let index = axis as usize;
+        let coord = i32::from(self.loc[index]) + delta;
+        if coord < 0 || coord >= i32::from(self.extents[index]) {
+            return false;
+        }
+
+        let mut loc = self.loc;
+        loc[index] = coord as u16;
+        self.move_to(loc)
+    }
+
+    /// Test if the deepest node on the current path contains the given location
+    fn current_contains(&self, loc: [u16; 3]) -> bool {
+        let (node, origin) = self.path[self.path.len() - 1];
+        (0..3).all(|i| loc[i] >= origin[i] && loc[i] - origin[i] < node.dimension())
+    }
+
+    /// Extend the current path down towards the current location, until a leaf or empty child
+    fn descend(&mut self) {
+        loop {
+            let (node, origin) = self.path[self.path.len() - 1];
+            if node.leaf() {
+                return;
+            }
+
+            let half = node.dimension() / 2;
+            let index = child_index(origin, half, self.loc);
+            match node.child(index) {
+                Some(child) => self.path.push((child, child_origin(origin, index, half))),
+                None => return,
+            }
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use error::OctreeError;
+use query::VolumeQuery;
+
+/// Read-only view over an externally owned dense voxel buffer, exposing the same queries as an
+/// `Octree<T>` without building any nodes
+///
+/// Every voxel of the buffer is occupied. The voxel at `[x, y, z]` is stored at index
+/// `x + y * dimension + z * dimension^2`.
+#[derive(Debug, Clone, Copy)]
+pub struct DenseView<'a, T: 'a> {
+    dimension: u16,
+    data: &'a [T],
+}
+
+/// Constructs a `DenseView<T>` over a flat buffer of `dimension^3` voxels
+///
+/// Returns a `DimensionError` if `dimension` is not a power of two, or if the buffer does not
+/// hold exactly `dimension^3` voxels.
+///
+/// # Examples
+///
+/// ```
+/// # use octo::view_dense;
+/// #
+/// let data: Vec<u8> = (0..8).collect();
+/// let view = view_dense(2, &data).unwrap();
+///
+/// assert_eq!(view.at([1, 0, 1]), Some(5));
+/// ```
+///
+pub fn view_dense<T: Copy>(dimension: u16, data: &[T]) -> Result<DenseView<'_, T>, OctreeError> {
+    if !dimension.is_power_of_two() || data.len() != usize::from(dimension).pow(3) {
+        return Err(OctreeError::DimensionError);
+    }
+    Ok(DenseView { dimension, data })
+}
+
+impl<'a, T> DenseView<'a, T>
+where
+    T: Copy,
+{
+    /// Returns the x/y/z dimension of the `DenseView<T>`
+    pub fn dimension(&self) -> u16 {
+        self.dimension
+    }
+
+    /// Get the value at a given location, or `None` if it is out of bounds
+    pub fn at(&self, loc: [u16; 3]) -> Option<T> {
+        if loc.iter().any(|&c| c >= self.dimension) {
+            None
+        } else {
+            Some(self.data[self.index(loc)])
+        }
+    }
+
+    /// Get every voxel within the inclusive box `min`..=`max`, with its value
+    ///
+    /// Parts of the box outside the `DenseView<T>` are ignored.
+    pub fn query_region(&self, min: [u16; 3], max: [u16; 3]) -> Vec<([u16; 3], T)> {
+        let last = self.dimension - 1;
+        let mut voxels = vec![];
+        for z in min[2]..=max[2].min(last) {
+            for y in min[1]..=max[1].min(last) {
+                for x in min[0]..=max[0].min(last) {
+                    voxels.push(([x, y, z], self.data[self.index([x, y, z])]));
+                }
+            }
+        }
+        voxels
+    }
+
+    /// Iterate over every voxel of the `DenseView<T>` with its location, in buffer order
+    pub fn iter_positions(&self) -> impl Iterator<Item = ([u16; 3], T)> + 'a {
+        let dimension = usize::from(self.dimension);
+        self.data.iter().enumerate().map(move |(i, &data)| {
+            let loc = [
+                i % dimension,
+                (i / dimension) % dimension,
+                i / (dimension * dimension),
+            ];
+            ([loc[0] as u16, loc[1] as u16, loc[2] as u16], data)
+        })
+    }
+
+    /// Get the buffer index of an in-bounds location
+    fn index(&self, loc: [u16; 3]) -> usize {
+        let dimension = usize::from(self.dimension);
+        usize::from(loc[0]) + (usize::from(loc[1]) + usize::from(loc[2]) * dimension) * dimension
+    }
+}
+
+impl<'a, T> VolumeQuery<T> for DenseView<'a, T>
+where
+    T: Copy,
+{
+    fn dimension(&self) -> u16 {
+        self.dimension
+    }
+
+    fn at(&self, loc: [u16; 3]) -> Option<T> {
+        DenseView::at(self, loc)
+    }
+
+    fn query_region(&self, min: [u16; 3], max: [u16; 3]) -> Vec<([u16; 3], T)> {
+        DenseView::query_region(self, min, max)
+    }
+}
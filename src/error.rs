@@ -1,26 +1,40 @@
 use std::error::Error;
-use std::fmt;
+use std::{fmt, io};
 
 /// Errors raised by the library
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum OctreeError {
     DimensionError,
-    OutOfBoundsError,
+    OutOfBoundsError([u16; 3]),
+    IoError(io::ErrorKind),
+    CorruptData,
 }
 
-impl Error for OctreeError {
-    fn description(&self) -> &str {
+impl Error for OctreeError {}
+
+impl fmt::Display for OctreeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             OctreeError::DimensionError => {
-                "Invalid dimension for octree. Must be an exponent of 2."
+                f.write_str("Invalid dimension for octree. Must be an exponent of 2.")
+            }
+            OctreeError::OutOfBoundsError(loc) => write!(
+                f,
+                "Node location provided ({}, {}, {}) is out of octree bounds.",
+                loc[0], loc[1], loc[2]
+            ),
+            OctreeError::IoError(kind) => {
+                write!(f, "Failed to read or write octree data: {:?}.", kind)
+            }
+            OctreeError::CorruptData => {
+                f.write_str("Octree data is truncated or does not match its checksum.")
             }
-            OctreeError::OutOfBoundsError => "Node location provided is out of octree bounds.",
         }
     }
 }
 
-impl fmt::Display for OctreeError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        f.write_str(self.description())
+impl From<io::Error> for OctreeError {
+    fn from(error: io::Error) -> OctreeError {
+        OctreeError::IoError(error.kind())
     }
 }
@@ -0,0 +1,153 @@
+use node::{child_index, child_origin, OctreeNode};
+use std::collections::VecDeque;
+
+/// Node of a `FlatOctree<T>`, referring to its children by index rather than by pointer
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FlatNode<T> {
+    data: Option<T>,
+    first_child: u32,
+}
+
+impl<T> FlatNode<T>
+where
+    T: Copy,
+{
+    /// Get the value of a leaf, or `None` for empty leaves and internal nodes
+    pub fn data(&self) -> Option<T> {
+        self.data
+    }
+
+    /// Get the index of the first of the eight consecutive children of an internal node, or
+    /// `None` for a leaf
+    pub fn first_child(&self) -> Option<usize> {
+        match self.first_child {
+            0 => None,
+            index => Some(index as usize),
+        }
+    }
+}
+
+/// Read-only copy of an `Octree<T>` with every node stored in one contiguous `Vec`, built by
+/// `Octree::to_flat`
+///
+/// Nodes are laid out breadth first from the root at index 0, and the eight children of an
+/// internal node are stored consecutively in octant order. Empty octants are stored as empty
+/// leaves. Lookups walk the `Vec` by index, which is friendlier to the cache than following
+/// pointers on traversal-heavy workloads, but the tree cannot be edited.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlatOctree<T> {
+    dimension: u16,
+    extents: [u16; 3],
+    default: Option<T>,
+    nodes: Vec<FlatNode<T>>,
+}
+
+impl<T> FlatOctree<T>
+where
+    T: Copy + PartialEq,
+{
+    /// Lay out the nodes below `root` breadth first
+    pub(crate) fn new(
+        root: &OctreeNode<T>,
+        extents: [u16; 3],
+        default: Option<T>,
+    ) -> FlatOctree<T> {
+        let mut nodes = vec![];
+        let mut queue = VecDeque::new();
+        queue.push_back(Some(root));
+
+        while let Some(node) = queue.pop_front() {
+            let mut flat = FlatNode {
+                data: None,
+                first_child: 0,
+            };
+            if let Some(node) = node {
+                if node.leaf() {
+                    flat.data = node.get();
+                } else {
+                    flat.first_child = (nodes.len() + queue.len() + 1) as u32;
+                    queue.extend(node.children_ref());
+                }
+            }
+            nodes.push(flat);
+        }
+
+        FlatOctree {
+            dimension: root.dimension(),
+            extents,
+            default,
+            nodes,
+        }
+    }
+
+    /// Returns the x/y/z dimension of the `FlatOctree<T>`
+    pub fn dimension(&self) -> u16 {
+        self.dimension
+    }
+
+    /// Get every node of the `FlatOctree<T>`, root first
+    pub fn nodes(&self) -> &[FlatNode<T>] {
+        &self.nodes
+    }
+
+    /// Get the value stored at a given location, as `Octree::at`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([12, 10, 6], 255).unwrap();
+    /// let flat = octree.to_flat();
+    ///
+    /// assert_eq!(flat.at([12, 10, 6]), Some(255));
+    /// assert_eq!(flat.at([12, 10, 7]), None);
+    /// ```
+    ///
+    pub fn at(&self, loc: [u16; 3]) -> Option<T> {
+        if (0..3).any(|axis| loc[axis] >= self.extents[axis]) {
+            return None;
+        }
+
+        let mut node = &self.nodes[0];
+        let mut origin = [0, 0, 0];
+        let mut half = self.dimension / 2;
+        while let Some(first_child) = node.first_child() {
+            let index = child_index(origin, half, loc);
+            node = &self.nodes[first_child + index];
+            origin = child_origin(origin, index, half);
+            half /= 2;
+        }
+        node.data.or(self.default)
+    }
+
+    /// Iterate over every occupied voxel of the `FlatOctree<T>` with its location, visiting
+    /// octants depth first
+    ///
+    /// Simplified nodes are expanded into their individual voxels. Empty voxels are skipped,
+    /// whatever the default value.
+    pub fn iter(&self) -> impl Iterator<Item = ([u16; 3], T)> {
+        let mut voxels = vec![];
+        let mut stack = vec![(0, [0, 0, 0], self.dimension)];
+        while let Some((index, origin, size)) = stack.pop() {
+            let node = &self.nodes[index];
+            if let Some(first_child) = node.first_child() {
+                let half = size / 2;
+                for child in (0..8).rev() {
+                    stack.push((first_child + child, child_origin(origin, child, half), half));
+                }
+            } else if let Some(data) = node.data {
+                let last = |axis: usize| (origin[axis] + (size - 1)).min(self.extents[axis] - 1);
+                for z in origin[2]..=last(2) {
+                    for y in origin[1]..=last(1) {
+                        for x in origin[0]..=last(0) {
+                            voxels.push(([x, y, z], data));
+                        }
+                    }
+                }
+            }
+        }
+        voxels.into_iter()
+    }
+}
@@ -1,17 +1,33 @@
+mod codec;
+mod cursor;
+mod dense;
 mod error;
+mod flat;
+pub mod math;
 mod node;
 pub mod octree;
+mod query;
+mod shared;
 
+pub use codec::VoxelCodec;
+pub use cursor::Cursor;
+pub use dense::{view_dense, DenseView};
 pub use error::OctreeError;
-pub use octree::Octree;
+pub use flat::{FlatNode, FlatOctree};
+pub use node::OctreeNode;
+pub use octree::{
+    Axis, Block, BoundsPolicy, CellInfo, Octree, OctreeBuilder, OctreeMeta, SimplificationMode,
+    Snapshot,
+};
+pub use query::{raycast, VolumeQuery};
+pub use shared::SharedOctree;
 
 extern crate serde;
+#[cfg(any(feature = "json", test))]
+extern crate serde_json;
 
 #[cfg(test)]
 mod tests {
-    extern crate core;
-
-    use self::core::u8;
     use error::OctreeError;
     use octree::Octree;
 
@@ -19,12 +35,21 @@ mod tests {
     fn test_dimension() {
         assert!(
             Octree::<u8>::new(16).is_ok(),
-            "Octree with square number dimension returned None"
+            "Octree with power of two dimension 16 returned None"
         );
         assert!(
             Octree::<u8>::new(3).is_err(),
-            "Octree with non-square number dimension returned Some()"
+            "Octree with non power of two dimension 3 returned Some()"
+        );
+        assert!(
+            Octree::<u8>::new(8).is_ok(),
+            "Octree with power of two dimension 8 returned None"
+        );
+        assert!(
+            Octree::<u8>::new(9).is_err(),
+            "Octree with non power of two dimension 9 returned Some()"
         );
+        assert_eq!(Octree::<u8>::new(256).unwrap().max_depth(), 8);
     }
 
     #[test]
@@ -64,7 +89,7 @@ mod tests {
         if let Some(node) = octree.node_as_ref([0, 0, 0]) {
             assert_eq!(node.dimension(), 1, "Node erroneously simplified");
         } else {
-            assert!(false, "Point not found in Octree after inserting");
+            panic!("Point not found in Octree after inserting");
         }
 
         octree.insert([1, 1, 1], 255).unwrap();
@@ -73,7 +98,7 @@ mod tests {
         if let Some(node) = octree.node_as_ref([0, 0, 0]) {
             assert_eq!(node.dimension(), 2, "Node not simplified");
         } else {
-            assert!(false, "Point not found in Octree after inserting");
+            panic!("Point not found in Octree after inserting");
         }
 
         octree.insert([0, 0, 0], 128).unwrap();
@@ -88,8 +113,8 @@ mod tests {
         octree.insert([12, 10, 6], 128).unwrap();
 
         let mut iter = octree.into_iter();
-        assert_eq!(iter.nth(0), Some(255), "Value not found in iterator");
-        assert_eq!(iter.nth(0), Some(128), "Value not found in iterator");
+        assert_eq!(iter.next(), Some(255), "Value not found in iterator");
+        assert_eq!(iter.next(), Some(128), "Value not found in iterator");
     }
 
     #[test]
@@ -107,9 +132,18 @@ mod tests {
     fn test_insert_none() {
         let mut octree = Octree::<u8>::new(16).unwrap();
         octree.insert([0, 0, 0], 255).unwrap();
+        octree.insert([12, 10, 6], 128).unwrap();
         octree.insert_none([0, 0, 0]);
         let val = octree.at([0, 0, 0]);
         assert_eq!(val, None);
+        assert_eq!(octree.at([12, 10, 6]), Some(128), "Unrelated value cleared");
+
+        octree.insert([0, 0, 0], 64).unwrap();
+        assert_eq!(
+            octree.at([0, 0, 0]),
+            Some(64),
+            "Error inserting into cleared node"
+        );
     }
 
     #[test]
@@ -124,6 +158,3453 @@ mod tests {
         //println!("{:?}", octree);
     }
 
+    #[test]
+    fn test_cell_info() {
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        for x in 2..4 {
+            for y in 2..4 {
+                for z in 2..4 {
+                    octree.insert([x, y, z], 255).unwrap();
+                }
+            }
+        }
+        octree.insert([8, 8, 8], 128).unwrap();
+
+        let info = octree.cell_info([3, 2, 3]).unwrap();
+        assert!(info.size > 1, "Simplified cell reported as a single voxel");
+        assert!(
+            info.simplified,
+            "Simplified cell not reported as simplified"
+        );
+        assert!(info.occupied);
+        assert_eq!(info.origin, [2, 2, 2]);
+
+        let info = octree.cell_info([8, 8, 8]).unwrap();
+        assert_eq!(info.size, 1);
+        assert!(!info.simplified);
+
+        let info = octree.cell_info([15, 0, 0]).unwrap();
+        assert!(!info.occupied);
+        assert!(octree.cell_info([16, 0, 0]).is_none());
+    }
+
+    #[test]
+    fn test_at_many() {
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        octree.insert([0, 0, 0], 255).unwrap();
+        octree.insert([0, 0, 1], 64).unwrap();
+        octree.insert([12, 10, 6], 128).unwrap();
+        octree.insert([15, 15, 15], 1).unwrap();
+
+        let locs = [
+            [15, 15, 15],
+            [0, 0, 1],
+            [3, 7, 2],
+            [12, 10, 6],
+            [0, 0, 0],
+            [12, 10, 7],
+            [0, 0, 1],
+        ];
+        let expected: Vec<Option<u8>> = locs.iter().map(|loc| octree.at(*loc)).collect();
+        assert_eq!(octree.at_many(&locs), expected);
+        assert_eq!(octree.at_many(&[[16, 0, 0]]), vec![None]);
+    }
+
+    #[test]
+    fn test_dilate() {
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        octree.insert([4, 4, 4], 255).unwrap();
+        octree.insert([0, 0, 0], 64).unwrap();
+
+        let dilated = octree.dilate(128);
+        assert_eq!(
+            dilated.at([4, 4, 4]),
+            Some(255),
+            "Occupied voxel changed by dilation"
+        );
+        for neighbor in &[
+            [3, 4, 4],
+            [5, 4, 4],
+            [4, 3, 4],
+            [4, 5, 4],
+            [4, 4, 3],
+            [4, 4, 5],
+        ] {
+            assert_eq!(
+                dilated.at(*neighbor),
+                Some(128),
+                "Face neighbor not dilated"
+            );
+        }
+        assert_eq!(dilated.at([5, 5, 4]), None, "Edge neighbor dilated");
+        assert_eq!(dilated.at([1, 0, 0]), Some(128));
+        assert_eq!(
+            octree.at([5, 4, 4]),
+            None,
+            "Source octree modified by dilation"
+        );
+    }
+
+    #[test]
+    fn test_erode() {
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        for x in 3..6 {
+            for y in 3..6 {
+                for z in 3..6 {
+                    octree.insert([x, y, z], 255).unwrap();
+                }
+            }
+        }
+
+        let eroded = octree.erode();
+        let mut remaining = vec![];
+        for x in 0..16 {
+            for y in 0..16 {
+                for z in 0..16 {
+                    if let Some(val) = eroded.at([x, y, z]) {
+                        remaining.push(([x, y, z], val));
+                    }
+                }
+            }
+        }
+        assert_eq!(
+            remaining,
+            vec![([4, 4, 4], 255)],
+            "Cube not eroded to its center"
+        );
+    }
+
+    #[test]
+    fn test_connected_components() {
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        octree.insert([0, 0, 0], 255).unwrap();
+        octree.insert([1, 0, 0], 128).unwrap();
+        octree.insert([1, 1, 0], 64).unwrap();
+        octree.insert([10, 10, 10], 255).unwrap();
+        octree.insert([10, 10, 11], 255).unwrap();
+        octree.insert([11, 11, 11], 255).unwrap();
+        octree.insert([11, 11, 12], 255).unwrap();
+        octree.insert([11, 10, 11], 255).unwrap();
+
+        let (labels, count) = octree.connected_components();
+        assert_eq!(count, 2, "Incorrect number of components");
+
+        let first = labels.at([0, 0, 0]);
+        let second = labels.at([10, 10, 10]);
+        assert!(first.is_some() && second.is_some());
+        assert_ne!(first, second, "Separate blobs share a label");
+        assert_eq!(labels.at([1, 0, 0]), first);
+        assert_eq!(labels.at([1, 1, 0]), first);
+        assert_eq!(labels.at([10, 10, 11]), second);
+        assert_eq!(labels.at([11, 11, 12]), second);
+        assert_eq!(labels.at([5, 5, 5]), None);
+    }
+
+    #[test]
+    fn test_sample_trilinear() {
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        octree.insert([4, 4, 4], 0).unwrap();
+        octree.insert([5, 4, 4], 100).unwrap();
+
+        let sample = octree.sample_trilinear([4.5, 4.0, 4.0]).unwrap();
+        assert!((sample - 50.0).abs() < 1e-4, "Incorrect interpolated value");
+
+        let sample = octree.sample_trilinear([5.0, 4.0, 4.0]).unwrap();
+        assert!(
+            (sample - 100.0).abs() < 1e-4,
+            "Incorrect value at voxel position"
+        );
+
+        assert_eq!(octree.sample_trilinear([10.5, 10.5, 10.5]), None);
+        assert!(octree.sample_trilinear([-3.0, 100.0, 4.0]).is_none());
+
+        let mut octree = Octree::<u8>::new_with_default(16, 9).unwrap();
+        assert_eq!(octree.sample_trilinear([10.5, 10.5, 10.5]), None);
+        octree.insert([1, 0, 0], 1).unwrap();
+        let sample = octree.sample_trilinear([0.5, 0.0, 0.0]).unwrap();
+        assert!(
+            (sample - 5.0).abs() < 1e-4,
+            "Default not used for empty voxels"
+        );
+    }
+
+    #[test]
+    fn test_actual_depth() {
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        assert_eq!(octree.actual_depth(), 0, "Empty octree has non-zero depth");
+
+        octree.insert([12, 10, 6], 255).unwrap();
+        assert_eq!(octree.actual_depth(), octree.max_depth());
+
+        let mut octree = Octree::<u8>::new(4).unwrap();
+        for x in 0..4 {
+            for y in 0..4 {
+                for z in 0..4 {
+                    octree.insert([x, y, z], 255).unwrap();
+                }
+            }
+        }
+        assert!(
+            octree.actual_depth() < octree.max_depth(),
+            "Simplified octree reports full depth"
+        );
+        assert_eq!(octree.actual_depth(), 0);
+    }
+
+    #[test]
+    fn test_memory_bytes() {
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        for x in 0..16 {
+            for y in 0..16 {
+                for z in 0..16 {
+                    octree.insert([x, y, z], 255).unwrap();
+                }
+            }
+        }
+
+        let dense_bytes = 16 * 16 * 16 * std::mem::size_of::<u8>();
+        assert!(
+            octree.memory_bytes() * 4 < dense_bytes,
+            "Simplified octree uses comparable memory to a dense array"
+        );
+
+        octree.insert([3, 3, 3], 128).unwrap();
+        octree.insert([12, 10, 6], 64).unwrap();
+        assert!(octree.memory_bytes() > std::mem::size_of::<OctreeNode<u8>>());
+    }
+
+    #[test]
+    fn test_fill_interior() {
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        for x in 2..7 {
+            for y in 2..7 {
+                for z in 2..7 {
+                    let on_shell = [x, y, z].iter().any(|&c| c == 2 || c == 6);
+                    if on_shell {
+                        octree.insert([x, y, z], 255).unwrap();
+                    }
+                }
+            }
+        }
+        octree.fill_interior(128);
+
+        for x in 3..6 {
+            for y in 3..6 {
+                for z in 3..6 {
+                    assert_eq!(
+                        octree.at([x, y, z]),
+                        Some(128),
+                        "Interior cavity not filled"
+                    );
+                }
+            }
+        }
+        assert_eq!(octree.at([2, 4, 4]), Some(255), "Shell voxel overwritten");
+        assert_eq!(octree.at([0, 0, 0]), None, "Outside space filled");
+        assert_eq!(octree.at([7, 4, 4]), None, "Outside space filled");
+        assert_eq!(octree.at([15, 15, 15]), None, "Outside space filled");
+
+        let mut rect = Octree::<u8>::new_rect([16, 16, 4]).unwrap();
+        for x in 0..4 {
+            for y in 0..4 {
+                for z in 0..3 {
+                    if [x, y, z] != [1, 1, 1] {
+                        rect.insert([x, y, z], 255).unwrap();
+                    }
+                }
+            }
+        }
+        rect.fill_interior(128);
+        assert_eq!(rect.at([1, 1, 1]), Some(128), "Interior cavity not filled");
+        assert_eq!(rect.len(), 48);
+
+        let mut empty = Octree::<u8>::new(16).unwrap();
+        empty.fill_interior(128);
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn test_cursor() {
+        use octree::Axis;
+
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        octree.insert([0, 3, 5], 1).unwrap();
+        octree.insert([4, 3, 5], 2).unwrap();
+        octree.insert([7, 3, 5], 3).unwrap();
+        octree.insert([8, 3, 5], 4).unwrap();
+        octree.insert([15, 3, 5], 5).unwrap();
+        for x in 10..12 {
+            for y in 2..4 {
+                for z in 4..6 {
+                    octree.insert([x, y, z], 6).unwrap();
+                }
+            }
+        }
+
+        let mut cursor = octree.cursor([0, 3, 5]).unwrap();
+        assert_eq!(cursor.value(), octree.at([0, 3, 5]));
+        for x in 1..16 {
+            assert!(
+                cursor.step(Axis::X, 1),
+                "Cursor failed to step within bounds"
+            );
+            assert_eq!(cursor.loc(), [x, 3, 5]);
+            assert_eq!(
+                cursor.value(),
+                octree.at([x, 3, 5]),
+                "Cursor value mismatch"
+            );
+        }
+        assert!(!cursor.step(Axis::X, 1), "Cursor stepped out of bounds");
+        assert_eq!(cursor.loc(), [15, 3, 5]);
+
+        assert!(cursor.step(Axis::X, -5));
+        assert_eq!(cursor.value(), Some(6));
+        assert!(cursor.move_to([0, 3, 5]));
+        assert_eq!(cursor.value(), Some(1));
+        assert!(octree.cursor([0, 16, 0]).is_none());
+    }
+
+    #[test]
+    fn test_default_value() {
+        let mut octree = Octree::<u8>::new_with_default(16, 7).unwrap();
+        assert_eq!(
+            octree.at([3, 9, 12]),
+            Some(7),
+            "Untouched location not default"
+        );
+        assert_eq!(
+            octree.at([16, 0, 0]),
+            None,
+            "Out of bounds location returned default"
+        );
+
+        octree.insert([3, 9, 12], 255).unwrap();
+        assert_eq!(octree.at([3, 9, 12]), Some(255));
+        octree.insert_none([3, 9, 12]);
+        assert_eq!(
+            octree.at([3, 9, 12]),
+            Some(7),
+            "Cleared location not default"
+        );
+
+        octree.insert([0, 0, 0], 255).unwrap();
+        assert_eq!(octree.take([0, 0, 0]), Some(255));
+        assert_eq!(octree.at([0, 0, 0]), Some(7), "Taken location not default");
+        assert_eq!(
+            octree.at_many(&[[0, 0, 0], [16, 16, 16]]),
+            vec![Some(7), None],
+        );
+        assert_eq!(octree.cursor([1, 1, 1]).unwrap().value(), Some(7));
+    }
+
+    #[test]
+    fn test_to_hashmap() {
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        assert!(octree.is_empty());
+        for x in 0..2 {
+            for y in 0..2 {
+                for z in 0..2 {
+                    octree.insert([x, y, z], 255).unwrap();
+                }
+            }
+        }
+        octree.insert([12, 10, 6], 128).unwrap();
+        assert!(!octree.is_empty());
+        assert_eq!(octree.volume(), 9);
+        assert!(!octree.is_empty());
+
+        let map = octree.to_hashmap();
+        assert_eq!(
+            map.len(),
+            octree.len(),
+            "Map length does not match octree length"
+        );
+        assert_eq!(map.len(), 9);
+        assert_eq!(
+            map.get(&[1, 0, 1]),
+            Some(&255),
+            "Simplified voxel missing from map"
+        );
+        assert_eq!(map.get(&[12, 10, 6]), Some(&128));
+        assert_eq!(map.get(&[2, 0, 0]), None);
+    }
+
+    #[test]
+    fn test_from_hashmap() {
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        for x in 4..8 {
+            for y in 4..8 {
+                for z in 4..8 {
+                    octree.insert([x, y, z], 255).unwrap();
+                }
+            }
+        }
+        octree.insert([0, 0, 0], 128).unwrap();
+        octree.insert([12, 10, 6], 64).unwrap();
+
+        let map = octree.to_hashmap();
+        let rebuilt = Octree::<u8>::from_hashmap(16, &map).unwrap();
+        assert!(
+            rebuilt == octree,
+            "Round-tripped octree differs from original"
+        );
+
+        let mut map = map;
+        map.insert([3, 16, 2], 1);
+        match Octree::<u8>::from_hashmap(16, &map) {
+            Err(OctreeError::OutOfBoundsError(loc)) => assert_eq!(loc, [3, 16, 2]),
+            _ => panic!("Out of bounds location not reported"),
+        }
+    }
+
+    #[test]
+    fn test_try_at() {
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        octree.insert([12, 10, 6], 128).unwrap();
+
+        assert_eq!(octree.try_at([12, 10, 6]), Ok(Some(128)));
+        assert_eq!(
+            octree.try_at([3, 3, 3]),
+            Ok(None),
+            "Empty location returned an error"
+        );
+        assert_eq!(
+            octree.try_at([16, 10, 6]),
+            Err(OctreeError::OutOfBoundsError([16, 10, 6])),
+            "Out of bounds location not reported"
+        );
+        assert_eq!(octree.at([16, 10, 6]), None);
+    }
+
+    #[test]
+    fn test_shell() {
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        for x in 2..8 {
+            for y in 2..8 {
+                for z in 2..8 {
+                    octree.insert([x, y, z], 255).unwrap();
+                }
+            }
+        }
+
+        let on_layer = |loc: [u16; 3], layer: u16| {
+            loc.iter().any(|&c| c == 2 + layer || c == 7 - layer)
+                && loc.iter().all(|&c| c >= 2 + layer && c <= 7 - layer)
+        };
+
+        let surface = octree.shell(1);
+        assert_eq!(surface, octree.surface_voxels());
+        assert_eq!(surface.len(), 6 * 6 * 6 - 4 * 4 * 4);
+        assert!(
+            surface.iter().all(|(loc, _)| on_layer(*loc, 0)),
+            "Interior voxel in shell"
+        );
+
+        let thick = octree.shell(2);
+        assert_eq!(thick.len(), 6 * 6 * 6 - 2 * 2 * 2);
+        assert!(
+            thick.iter().any(|(loc, _)| on_layer(*loc, 1)),
+            "Second layer not in shell"
+        );
+        assert!(
+            thick.iter().all(|(loc, _)| !on_layer(*loc, 2)),
+            "Core voxel in shell"
+        );
+    }
+
+    #[test]
+    fn test_builder() {
+        use octree::{OctreeBuilder, SimplificationMode};
+
+        let fill = |octree: &mut Octree<f32>, value: f32| {
+            for x in 0..4 {
+                for y in 0..4 {
+                    for z in 0..4 {
+                        octree.insert([x, y, z], value).unwrap();
+                    }
+                }
+            }
+        };
+
+        let mut eager = OctreeBuilder::new()
+            .dimension(16)
+            .simplification(SimplificationMode::Eager)
+            .build()
+            .unwrap();
+        fill(&mut eager, 1.0);
+        assert_eq!(eager.node_count(), 3, "Eager mode did not simplify");
+
+        let mut off = OctreeBuilder::new()
+            .dimension(16)
+            .simplification(SimplificationMode::Off)
+            .build()
+            .unwrap();
+        fill(&mut off, 1.0);
+        assert_eq!(off.node_count(), 1 + 1 + 1 + 8 + 64, "Off mode simplified");
+        assert_eq!(off.at([3, 3, 3]), Some(1.0));
+
+        let mut tolerant = OctreeBuilder::new()
+            .dimension(16)
+            .simplification(SimplificationMode::Tolerance(0.5))
+            .metric(|a: f32, b: f32| f64::from((a - b).abs()))
+            .build()
+            .unwrap();
+        tolerant.insert([0, 0, 0], 1.2).unwrap();
+        fill(&mut tolerant, 1.0);
+        assert_eq!(tolerant.node_count(), 3, "Tolerance mode did not simplify");
+
+        let with_default = OctreeBuilder::new().dimension(16).default(0.0).build();
+        assert_eq!(with_default.unwrap().at([1, 1, 1]), Some(0.0));
+        assert!(OctreeBuilder::<u8>::new().build().is_err());
+    }
+
+    #[test]
+    fn test_simplification_ratio() {
+        let mut octree = Octree::<u8>::new(4).unwrap();
+        assert_eq!(
+            octree.simplification_ratio(),
+            0.0,
+            "Empty octree has non-zero ratio"
+        );
+
+        for x in 0..4 {
+            for y in 0..4 {
+                for z in 0..4 {
+                    octree.insert([x, y, z], 255).unwrap();
+                }
+            }
+        }
+        assert_eq!(
+            octree.simplification_ratio(),
+            64.0,
+            "Uniform octree not a single leaf"
+        );
+
+        octree.insert([0, 0, 0], 128).unwrap();
+        let ratio = octree.simplification_ratio();
+        assert!(ratio > 1.0 && ratio < 64.0);
+    }
+
+    #[test]
+    fn test_insert_line() {
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        octree.insert_line([1, 2, 3], [9, 10, 11], 255).unwrap();
+        assert_eq!(octree.at([1, 2, 3]), Some(255), "Line start not inserted");
+        assert_eq!(octree.at([9, 10, 11]), Some(255), "Line end not inserted");
+        assert_eq!(
+            octree.at([5, 6, 7]),
+            Some(255),
+            "Line midpoint not inserted"
+        );
+        assert_eq!(octree.len(), 9);
+
+        octree.insert_line([15, 0, 0], [0, 3, 7], 128).unwrap();
+        assert_eq!(octree.at([15, 0, 0]), Some(128));
+        assert_eq!(octree.at([0, 3, 7]), Some(128));
+        assert_eq!(octree.len(), 9 + 16);
+
+        assert_eq!(
+            octree.insert_line([0, 0, 0], [0, 16, 0], 1),
+            Err(OctreeError::OutOfBoundsError([0, 16, 0]))
+        );
+        assert_eq!(
+            octree.at([0, 0, 0]),
+            None,
+            "Out of bounds line partially inserted"
+        );
+    }
+
+    #[test]
+    fn test_fill() {
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        octree.insert([3, 3, 3], 128).unwrap();
+        octree.fill([2, 1, 0], [9, 4, 5], 255).unwrap();
+
+        assert_eq!(octree.len(), 8 * 4 * 6);
+        assert_eq!(
+            octree.at([3, 3, 3]),
+            Some(255),
+            "Existing value not overwritten"
+        );
+        assert_eq!(octree.at([9, 4, 5]), Some(255));
+        assert_eq!(octree.at([10, 4, 5]), None, "Value filled outside box");
+        assert_eq!(octree.at([2, 0, 0]), None, "Value filled outside box");
+
+        octree.fill([0, 0, 0], [15, 15, 15], 1).unwrap();
+        assert_eq!(octree.node_count(), 1, "Filled octree not simplified");
+        assert_eq!(
+            octree.fill([0, 0, 0], [16, 0, 0], 1),
+            Err(OctreeError::OutOfBoundsError([16, 0, 0]))
+        );
+
+        let mut inverted = Octree::<u8>::new(16).unwrap();
+        inverted.fill([5, 5, 5], [2, 2, 2], 1).unwrap();
+        inverted.fill([0, 5, 0], [3, 2, 3], 1).unwrap();
+        assert_eq!(inverted.fill_changed([5, 5, 5], [2, 2, 2], 1).unwrap(), 0);
+        inverted
+            .map_region([5, 5, 5], [2, 2, 2], |_, _| Some(1))
+            .unwrap();
+        assert!(inverted.is_empty(), "Inverted box filled");
+        assert_eq!(inverted.len(), 0);
+        assert_eq!(inverted.take_dirty_bounds(), None);
+    }
+
+    #[test]
+    fn test_insert_plane() {
+        use octree::Axis;
+
+        let mut octree = Octree::<u8>::new(8).unwrap();
+        octree.insert_plane(Axis::Z, 0, 255).unwrap();
+
+        assert_eq!(octree.at([0, 0, 0]), Some(255));
+        assert_eq!(octree.at([7, 7, 0]), Some(255));
+        assert_eq!(octree.at([3, 5, 0]), Some(255));
+        assert_eq!(octree.at([3, 5, 1]), None, "Value inserted outside plane");
+        assert_eq!(octree.at([7, 7, 7]), None, "Value inserted outside plane");
+        assert_eq!(octree.len(), 64);
+        assert_eq!(
+            octree.insert_plane(Axis::X, 8, 255),
+            Err(OctreeError::OutOfBoundsError([8, 0, 0]))
+        );
+    }
+
+    #[test]
+    fn test_iter_empty_in_bounds() {
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        assert_eq!(octree.iter_empty_in_bounds().count(), 0);
+
+        for x in 2..10 {
+            for y in 2..10 {
+                for z in 2..10 {
+                    if [x, y, z] != [5, 3, 8] {
+                        octree.insert([x, y, z], 255).unwrap();
+                    }
+                }
+            }
+        }
+        assert_eq!(octree.occupied_bounds(), Some(([2, 2, 2], [9, 9, 9])));
+
+        let empty: Vec<[u16; 3]> = octree.iter_empty_in_bounds().collect();
+        assert_eq!(
+            empty,
+            vec![[5, 3, 8]],
+            "Incorrect empty locations in bounds"
+        );
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        octree.insert([0, 0, 0], 255).unwrap();
+        octree.insert([12, 10, 6], 128).unwrap();
+        let original = octree.clone();
+
+        let snapshot = octree.snapshot();
+        octree.insert([0, 0, 0], 1).unwrap();
+        octree.insert([1, 1, 1], 2).unwrap();
+        assert_eq!(octree.at([0, 0, 0]), Some(1));
+
+        let mut restored = Octree::<u8>::new(16).unwrap();
+        restored.restore(snapshot.clone());
+        assert!(
+            std::ptr::eq(
+                restored.node_as_ref([12, 10, 6]).unwrap(),
+                octree.node_as_ref([12, 10, 6]).unwrap()
+            ),
+            "Unedited subtree copied on write"
+        );
+        assert!(!std::ptr::eq(
+            restored.node_as_ref([0, 0, 0]).unwrap(),
+            octree.node_as_ref([0, 0, 0]).unwrap()
+        ));
+
+        octree.restore(snapshot);
+        assert!(octree == original, "Restored octree differs from original");
+        assert_eq!(octree.at([0, 0, 0]), Some(255));
+        assert_eq!(octree.at([1, 1, 1]), None);
+        assert_eq!(octree.at([12, 10, 6]), Some(128));
+    }
+
+    #[test]
+    fn test_slice_mask() {
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        octree.insert([0, 0, 4], 255).unwrap();
+        octree.insert([5, 2, 4], 128).unwrap();
+        octree.insert([15, 15, 4], 64).unwrap();
+        octree.insert([5, 2, 5], 32).unwrap();
+
+        let mask = octree.slice_mask(4).unwrap();
+        assert_eq!(mask.len(), 4);
+        assert_eq!(mask[0], 1 | (1 << 37), "Incorrect bits set in first word");
+        assert_eq!(mask[1], 0);
+        assert_eq!(mask[2], 0);
+        assert_eq!(mask[3], 1 << 63, "Incorrect bits set in last word");
+
+        assert_eq!(octree.slice_mask(5).unwrap()[0], 1 << 37);
+        assert_eq!(
+            octree.slice_mask(16),
+            Err(OctreeError::OutOfBoundsError([0, 0, 16]))
+        );
+    }
+
+    #[test]
+    fn test_contains_value() {
+        use std::cell::Cell;
+
+        thread_local!(static COMPARISONS: Cell<usize> = const { Cell::new(0) });
+
+        #[derive(Debug, Clone, Copy)]
+        struct Counted(u8);
+
+        impl PartialEq for Counted {
+            fn eq(&self, other: &Counted) -> bool {
+                COMPARISONS.with(|c| c.set(c.get() + 1));
+                self.0 == other.0
+            }
+        }
+
+        let mut octree = Octree::<Counted>::new(16).unwrap();
+        octree.insert([0, 0, 0], Counted(1)).unwrap();
+        for x in 0..16 {
+            octree.insert([x, 15, 15], Counted(2)).unwrap();
+        }
+
+        assert!(octree.contains_value(Counted(2)));
+        assert!(!octree.contains_value(Counted(3)));
+
+        COMPARISONS.with(|c| c.set(0));
+        assert!(octree.contains_value(Counted(1)));
+        assert_eq!(
+            COMPARISONS.with(|c| c.get()),
+            1,
+            "Search continued past first match"
+        );
+    }
+
+    #[test]
+    fn test_replace_value() {
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        for x in 0..2 {
+            for y in 0..2 {
+                for z in 0..2 {
+                    let value = if [x, y, z] == [1, 1, 1] { 2 } else { 1 };
+                    octree.insert([x, y, z], value).unwrap();
+                }
+            }
+        }
+        octree.insert([12, 10, 6], 1).unwrap();
+        octree.insert([15, 15, 15], 3).unwrap();
+        let nodes = octree.node_count();
+
+        assert_eq!(octree.replace_value(1, 2), 8, "Incorrect replacement count");
+        assert!(!octree.contains_value(1), "Value remains after replacement");
+        assert_eq!(octree.at([0, 0, 0]), Some(2));
+        assert_eq!(octree.at([12, 10, 6]), Some(2));
+        assert_eq!(octree.at([15, 15, 15]), Some(3));
+        assert_eq!(octree.len(), 10);
+        assert!(
+            octree.node_count() < nodes,
+            "Uniform region not simplified after replacement"
+        );
+        assert_eq!(octree.replace_value(1, 2), 0);
+
+        octree.take_dirty_bounds();
+        assert_eq!(octree.replace_value(3, 4), 1);
+        assert_eq!(
+            octree.take_dirty_bounds(),
+            Some(([15, 15, 15], [15, 15, 15]))
+        );
+        assert_eq!(
+            octree.replace_value(2, 2),
+            0,
+            "Identity replacement counted"
+        );
+        assert_eq!(octree.take_dirty_bounds(), None);
+
+        let mut octree = Octree::<u8>::new_with_default(16, 6).unwrap();
+        octree.fill([0, 0, 0], [3, 3, 3], 5).unwrap();
+        assert_eq!(octree.replace_value(5, 6), 64);
+        assert_eq!(octree.len(), 0, "Default value stored by replacement");
+        assert_eq!(octree.at([2, 2, 2]), Some(6));
+        assert_eq!(octree.take_dirty_bounds(), Some(([0, 0, 0], [3, 3, 3])));
+    }
+
+    #[test]
+    fn test_iter_order() {
+        let mut octree = Octree::<u8>::new(4).unwrap();
+        octree.insert([3, 3, 3], 1).unwrap();
+        octree.insert([0, 0, 2], 6).unwrap();
+        octree.insert([0, 2, 0], 4).unwrap();
+        octree.insert([1, 0, 0], 3).unwrap();
+        octree.insert([2, 2, 0], 5).unwrap();
+        octree.insert([0, 0, 0], 2).unwrap();
+
+        let values: Vec<u8> = octree.iter().collect();
+        assert_eq!(
+            values,
+            vec![2, 3, 5, 4, 6, 1],
+            "Values not yielded in octant order"
+        );
+    }
+
+    #[test]
+    fn test_iter_size_hint() {
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        octree.insert([0, 0, 0], 255).unwrap();
+        octree.insert([12, 10, 6], 128).unwrap();
+        octree.insert([15, 15, 15], 64).unwrap();
+
+        let mut iter = octree.iter();
+        assert_eq!(iter.size_hint(), (3, Some(3)));
+        assert_eq!(ExactSizeIterator::len(&iter), 3);
+
+        iter.next();
+        assert_eq!(iter.size_hint(), (2, Some(2)), "Size hint not updated");
+        assert_eq!(ExactSizeIterator::len(&iter), 2);
+        assert_eq!(iter.count(), 2);
+    }
+
+    #[test]
+    fn test_iter_lazy() {
+        let mut octree = Octree::<u8>::new(64).unwrap();
+        for x in 0..64 {
+            for y in (0..64).step_by(2) {
+                octree.insert([x, y, 0], 255).unwrap();
+            }
+        }
+        octree.insert([63, 63, 63], 128).unwrap();
+
+        let mut iter = octree.iter();
+        assert_eq!(iter.next(), Some(255));
+        assert!(
+            iter.pending() <= 7 * usize::from(octree.max_depth()),
+            "Iterator traversed more than the path to the first value"
+        );
+        assert_eq!(iter.len(), 2048);
+        assert_eq!(iter.last(), Some(128));
+    }
+
+    #[test]
+    fn test_volume() {
+        let mut octree = Octree::<u8>::new(1024).unwrap();
+        assert_eq!(octree.volume(), 0);
+
+        octree.fill([0, 0, 0], [511, 511, 511], 255).unwrap();
+        assert_eq!(octree.node_count(), 2, "Filled block not simplified");
+        assert_eq!(octree.volume(), 512u64.pow(3));
+        assert_eq!(octree.volume(), octree.len() as u64);
+
+        octree.insert([1023, 1023, 1023], 128).unwrap();
+        assert_eq!(octree.volume(), 512u64.pow(3) + 1);
+    }
+
+    #[test]
+    fn test_ray_box_intersection() {
+        use math::ray_box_intersection;
+
+        let hit = ray_box_intersection([-2.0, 1.0, 1.0], [1.0, 0.0, 0.0], [0.0; 3], [4.0; 3]);
+        assert_eq!(hit, Some((2.0, 6.0)), "Incorrect entry and exit for hit");
+
+        let diagonal = ray_box_intersection([-1.0; 3], [1.0; 3], [0.0; 3], [1.0; 3]);
+        assert_eq!(diagonal, Some((1.0, 2.0)));
+
+        assert_eq!(
+            ray_box_intersection([-2.0, 5.0, 1.0], [1.0, 0.0, 0.0], [0.0; 3], [4.0; 3]),
+            None,
+            "Parallel ray outside slab reported a hit"
+        );
+        assert_eq!(
+            ray_box_intersection([-2.0, 1.0, 1.0], [-1.0, 0.0, 0.0], [0.0; 3], [4.0; 3]),
+            None,
+            "Box behind ray reported a hit"
+        );
+        assert_eq!(
+            ray_box_intersection([-2.0, 1.0, 1.0], [1.0, 1.0, 0.0], [0.0; 3], [1.0; 3]),
+            None,
+            "Ray passing beside box reported a hit"
+        );
+
+        let inside = ray_box_intersection([1.0, 1.0, 1.0], [0.0, 0.0, 1.0], [0.0; 3], [4.0; 3]);
+        assert_eq!(
+            inside,
+            Some((-1.0, 3.0)),
+            "Incorrect interval from inside box"
+        );
+    }
+
+    #[test]
+    fn test_raycast() {
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        octree.insert([8, 2, 3], 255).unwrap();
+        octree.insert([12, 2, 3], 128).unwrap();
+        octree.insert([5, 5, 5], 64).unwrap();
+
+        assert_eq!(
+            octree.raycast([-4.0, 2.5, 3.5], [1.0, 0.0, 0.0]),
+            Some(([8, 2, 3], 255))
+        );
+        assert_eq!(
+            octree.raycast([20.0, 2.5, 3.5], [-1.0, 0.0, 0.0]),
+            Some(([12, 2, 3], 128)),
+            "Incorrect first hit travelling in negative direction"
+        );
+        assert_eq!(
+            octree.raycast([0.5, 0.5, 0.5], [1.0, 1.0, 1.0]),
+            Some(([5, 5, 5], 64)),
+            "Incorrect hit along diagonal"
+        );
+        assert_eq!(octree.raycast([-4.0, 9.5, 3.5], [1.0, 0.0, 0.0]), None);
+        assert_eq!(octree.raycast([-4.0, 2.5, 3.5], [-1.0, 0.0, 0.0]), None);
+    }
+
+    #[test]
+    fn test_morton() {
+        use math::{morton_decode, morton_encode};
+
+        assert_eq!(morton_encode([0, 0, 0]), 0);
+        assert_eq!(morton_encode([1, 1, 1]), 7);
+        assert_eq!(morton_encode([2, 0, 0]), 8);
+        assert_eq!(morton_encode([u16::MAX; 3]), (1 << 48) - 1);
+        for loc in &[[0, 0, 0], [12, 10, 6], [1023, 0, 512], [u16::MAX, 1, 40000]] {
+            assert_eq!(
+                morton_decode(morton_encode(*loc)),
+                *loc,
+                "Morton key did not round-trip"
+            );
+        }
+
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        octree.insert([15, 15, 15], 1).unwrap();
+        octree.insert([0, 3, 0], 2).unwrap();
+        octree.insert([12, 10, 6], 3).unwrap();
+        octree.fill([4, 4, 4], [5, 5, 5], 4).unwrap();
+
+        let voxels: Vec<(u64, u8)> = octree.iter_morton().collect();
+        assert_eq!(voxels.len(), 11);
+        assert!(
+            voxels.windows(2).all(|pair| pair[0].0 < pair[1].0),
+            "Voxels not sorted by Morton key"
+        );
+        for (key, data) in voxels {
+            assert_eq!(octree.at(morton_decode(key)), Some(data));
+        }
+    }
+
+    #[test]
+    fn test_from_morton_sorted() {
+        use math::morton_encode;
+
+        let mut voxels = vec![];
+        for x in 0..16u16 {
+            for y in 0..16u16 {
+                for z in 0..16u16 {
+                    if x < 8 && y < 8 && z < 8 {
+                        voxels.push(([x, y, z], 255));
+                    } else if (x + y + z) % 3 == 0 {
+                        voxels.push(([x, y, z], (x % 4) as u8));
+                    }
+                }
+            }
+        }
+
+        let mut expected = Octree::<u8>::new(16).unwrap();
+        expected.insert_many(voxels.clone()).unwrap();
+
+        let mut sorted: Vec<(u64, u8)> = voxels
+            .iter()
+            .map(|&(loc, data)| (morton_encode(loc), data))
+            .collect();
+        sorted.sort_by_key(|&(key, _)| key);
+        let octree = Octree::<u8>::from_morton_sorted(16, sorted.clone()).unwrap();
+        assert_eq!(octree.to_hashmap(), expected.to_hashmap());
+        assert_eq!(
+            octree.node_count(),
+            expected.node_count(),
+            "Sorted build not simplified"
+        );
+
+        sorted.swap(0, 100);
+        sorted.push(sorted[3]);
+        let unsorted = Octree::<u8>::from_morton_sorted(16, sorted).unwrap();
+        assert_eq!(
+            unsorted.to_hashmap(),
+            expected.to_hashmap(),
+            "Unsorted input built incorrectly"
+        );
+
+        assert_eq!(
+            Octree::<u8>::from_morton_sorted(16, vec![(morton_encode([16, 0, 0]), 1)]),
+            Err(OctreeError::OutOfBoundsError([16, 0, 0]))
+        );
+    }
+
+    #[test]
+    fn test_lod() {
+        let average = |values: &[Option<u8>]| {
+            let present: Vec<u32> = values.iter().flatten().map(|&v| u32::from(v)).collect();
+            if present.is_empty() {
+                None
+            } else {
+                Some((present.iter().sum::<u32>() / present.len() as u32) as u8)
+            }
+        };
+
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        let mut value = 10;
+        for z in 0..2 {
+            for y in 0..2 {
+                for x in 0..2 {
+                    octree.insert([x, y, z], value).unwrap();
+                    value += 10;
+                }
+            }
+        }
+        octree.insert([2, 2, 2], 200).unwrap();
+        octree.insert([12, 10, 6], 100).unwrap();
+        assert_eq!(
+            octree.node_lod([0, 0, 0], 3),
+            None,
+            "LOD set before compute"
+        );
+
+        octree.compute_lod(average);
+        assert_eq!(
+            octree.node_lod([1, 1, 1], 3),
+            Some(45),
+            "LOD not the average of leaves"
+        );
+        assert_eq!(octree.node_lod([3, 3, 3], 3), Some(200));
+        assert_eq!(octree.node_lod([0, 0, 0], 2), Some(122));
+        assert_eq!(octree.node_lod([12, 10, 6], 0), Some(111));
+        assert_eq!(
+            octree.node_lod([1, 1, 1], 4),
+            Some(80),
+            "Leaf data not returned"
+        );
+        assert_eq!(octree.node_lod([15, 15, 15], 2), None);
+        assert_eq!(octree.node_lod([16, 0, 0], 0), None);
+    }
+
+    #[test]
+    fn test_compact() {
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        for i in 0..16 {
+            octree.insert([i, 15 - i, i / 2], 255).unwrap();
+        }
+        let full_bytes = octree.memory_bytes();
+        let full_nodes = octree.node_count();
+
+        for i in 0..12 {
+            octree.take([i, 15 - i, i / 2]);
+        }
+        assert_eq!(octree.memory_bytes(), full_bytes, "Nodes removed by take");
+
+        octree.compact();
+        let node_bytes = std::mem::size_of::<OctreeNode<u8>>() + 8 * std::mem::size_of::<usize>();
+        assert_eq!(node_bytes, 104);
+        assert_eq!(
+            octree.memory_bytes(),
+            full_bytes - (full_nodes - octree.node_count()) * node_bytes,
+            "Incorrect bytes saved by compact"
+        );
+        assert!(octree.memory_bytes() < full_bytes / 2);
+        assert_eq!(octree.len(), 4);
+        for i in 12..16 {
+            assert_eq!(octree.at([i, 15 - i, i / 2]), Some(255));
+        }
+
+        octree.insert([0, 15, 0], 128).unwrap();
+        assert_eq!(
+            octree.at([0, 15, 0]),
+            Some(128),
+            "Error inserting after compact"
+        );
+
+        let _snapshot = octree.snapshot();
+        let root: *const OctreeNode<u8> = octree.root();
+        octree.compact();
+        assert!(
+            std::ptr::eq(octree.root(), root),
+            "Shared nodes copied with nothing to remove"
+        );
+    }
+
+    #[test]
+    fn test_from_fn() {
+        let checkerboard = |loc: [u16; 3]| {
+            if (loc[0] + loc[1] + loc[2]).is_multiple_of(2) {
+                Some(255)
+            } else {
+                None
+            }
+        };
+        let octree = Octree::<u8>::from_fn(16, checkerboard).unwrap();
+        for loc in &[[0, 0, 0], [1, 1, 0], [15, 15, 0], [12, 10, 6]] {
+            assert_eq!(octree.at(*loc), Some(255), "Even voxel not occupied");
+        }
+        for loc in &[[1, 0, 0], [0, 1, 0], [15, 15, 15], [12, 10, 7]] {
+            assert_eq!(octree.at(*loc), None, "Odd voxel occupied");
+        }
+        assert_eq!(octree.len(), 16 * 16 * 16 / 2);
+
+        let solid = Octree::<u8>::from_fn(16, |_| Some(1)).unwrap();
+        assert_eq!(solid.node_count(), 1, "Uniform output not simplified");
+
+        let mut calls = 0;
+        let layered = Octree::<u8>::from_fn_with_uniform(
+            16,
+            |loc| {
+                calls += 1;
+                Some(loc[2] as u8 / 4)
+            },
+            |origin, size| size <= 4 && origin[2].is_multiple_of(4),
+        )
+        .unwrap();
+        assert_eq!(calls, 64, "Uniform regions sampled per voxel");
+        assert_eq!(layered.at([12, 10, 6]), Some(1));
+        assert_eq!(layered.len(), 16 * 16 * 16);
+    }
+
+    #[test]
+    fn test_view_dense() {
+        use dense::view_dense;
+
+        let data: Vec<u16> = (0..16 * 16 * 16).collect();
+        let view = view_dense(16, &data).unwrap();
+        for loc in &[[0, 0, 0], [15, 0, 0], [0, 15, 0], [12, 10, 6], [15, 15, 15]] {
+            let index = usize::from(loc[0]) + 16 * usize::from(loc[1]) + 256 * usize::from(loc[2]);
+            assert_eq!(
+                view.at(*loc),
+                Some(data[index]),
+                "View does not match buffer"
+            );
+        }
+        assert_eq!(view.at([16, 0, 0]), None);
+
+        let region = view.query_region([14, 14, 14], [17, 15, 15]);
+        assert_eq!(region.len(), 8);
+        assert!(region.iter().all(|&(loc, data)| view.at(loc) == Some(data)));
+        assert!(view
+            .iter_positions()
+            .all(|(loc, data)| view.at(loc) == Some(data)));
+        assert_eq!(view.iter_positions().count(), data.len());
+
+        assert_eq!(
+            view_dense(16, &data[1..]).unwrap_err(),
+            OctreeError::DimensionError
+        );
+        assert!(view_dense(12, &data[..1728]).is_err());
+    }
+
+    #[test]
+    fn test_query_region() {
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        octree.fill([0, 0, 0], [7, 7, 7], 255).unwrap();
+        octree.insert([12, 10, 6], 128).unwrap();
+        octree.insert([15, 15, 15], 64).unwrap();
+
+        let region = octree.query_region([6, 6, 6], [12, 10, 6]);
+        assert_eq!(region.len(), 5, "Incorrect number of voxels in region");
+        assert!(region.contains(&([7, 7, 6], 255)));
+        assert!(region.contains(&([12, 10, 6], 128)));
+        assert_eq!(
+            octree.query_region([15, 15, 15], [20, 20, 20]),
+            vec![([15, 15, 15], 64)]
+        );
+        assert_eq!(octree.iter_positions().count(), 514);
+    }
+
+    #[test]
+    fn test_volume_query() {
+        use dense::view_dense;
+        use query::{raycast, VolumeQuery};
+
+        fn count_region<V: VolumeQuery<u8>>(volume: &V) -> usize {
+            volume.query_region([6, 6, 6], [9, 9, 9]).len()
+        }
+
+        let index = |loc: [u16; 3]| {
+            usize::from(loc[0]) + 8 * usize::from(loc[1]) + 64 * usize::from(loc[2])
+        };
+        let data: Vec<u8> = (0..8 * 8 * 8).map(|i| (i % 7) as u8).collect();
+        let dense = view_dense(8, &data).unwrap();
+        let octree = Octree::<u8>::from_fn(8, |loc| Some(data[index(loc)])).unwrap();
+
+        let rays = [
+            ([-1.0, 2.5, 3.5], [1.0, 0.0, 0.0]),
+            ([9.0, 2.5, 3.5], [-1.0, 0.0, 0.0]),
+            ([-3.0, -2.0, -1.0], [1.0, 1.0, 1.0]),
+            ([0.5, 9.0, 1.5], [0.0, -1.0, 0.0]),
+            ([4.5, 4.5, 12.0], [0.1, -0.2, -1.0]),
+            ([4.5, 4.5, 12.0], [0.0, 0.0, 1.0]),
+        ];
+        for &(origin, dir) in &rays {
+            assert_eq!(
+                raycast(&octree, origin, dir),
+                raycast(&dense, origin, dir),
+                "Sparse and dense raycasts differ"
+            );
+            assert_eq!(raycast(&octree, origin, dir), octree.raycast(origin, dir));
+        }
+        assert_eq!(
+            raycast(&dense, [9.0, 2.5, 3.5], [-1.0, 0.0, 0.0]),
+            Some(([7, 2, 3], data[index([7, 2, 3])]))
+        );
+        assert_eq!(raycast(&dense, [4.5, 4.5, 12.0], [0.0, 0.0, 1.0]), None);
+        assert_eq!(count_region(&octree), count_region(&dense));
+        assert_eq!(count_region(&dense), 8);
+    }
+
+    #[test]
+    fn test_insert_if_empty() {
+        let mut octree = Octree::<u8>::new_with_default(16, 0).unwrap();
+        assert_eq!(octree.insert_if_empty([12, 10, 6], 255), Ok(true));
+        assert_eq!(
+            octree.insert_if_empty([12, 10, 6], 128),
+            Ok(false),
+            "Occupied location reported as empty"
+        );
+        assert_eq!(
+            octree.at([12, 10, 6]),
+            Some(255),
+            "Existing value overwritten"
+        );
+        assert_eq!(
+            octree.insert_if_empty([16, 0, 0], 1),
+            Err(OctreeError::OutOfBoundsError([16, 0, 0]))
+        );
+    }
+
+    #[test]
+    fn test_content_hash() {
+        use octree::{OctreeBuilder, SimplificationMode};
+
+        let mut simplified = Octree::<u8>::new(16).unwrap();
+        let mut expanded = OctreeBuilder::<u8>::new()
+            .dimension(16)
+            .simplification(SimplificationMode::Off)
+            .build()
+            .unwrap();
+        for octree in [&mut simplified, &mut expanded].iter_mut() {
+            octree.fill([0, 0, 0], [7, 7, 7], 255).unwrap();
+            octree.insert([12, 10, 6], 128).unwrap();
+        }
+        assert!(simplified.node_count() < expanded.node_count());
+        assert!(simplified != expanded);
+        assert_eq!(
+            simplified.content_hash(),
+            expanded.content_hash(),
+            "Logically equal octrees hash differently"
+        );
+
+        let std_hash = |octree: &Octree<u8>| {
+            use std::hash::{Hash, Hasher};
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            octree.hash(&mut hasher);
+            hasher.finish()
+        };
+        assert_eq!(std_hash(&simplified), std_hash(&expanded));
+
+        expanded.insert([12, 10, 6], 127).unwrap();
+        assert_ne!(simplified.content_hash(), expanded.content_hash());
+        expanded.insert([12, 10, 6], 128).unwrap();
+        expanded.insert_none([15, 15, 15]);
+        assert_eq!(simplified.content_hash(), expanded.content_hash());
+        assert_ne!(
+            simplified.content_hash(),
+            Octree::<u8>::new(32).unwrap().content_hash()
+        );
+    }
+
+    #[test]
+    fn test_new_rect() {
+        use octree::Axis;
+
+        let mut octree = Octree::<u8>::new_rect([256, 256, 64]).unwrap();
+        assert_eq!(octree.extents(), [256, 256, 64]);
+        assert_eq!(octree.dimension(), 256);
+
+        octree.insert([255, 255, 63], 255).unwrap();
+        octree.insert([0, 0, 0], 128).unwrap();
+        assert_eq!(
+            octree.at([255, 255, 63]),
+            Some(255),
+            "Value not found at extreme corner"
+        );
+        assert_eq!(octree.at([0, 0, 0]), Some(128));
+
+        assert_eq!(
+            octree.insert([0, 0, 64], 1),
+            Err(OctreeError::OutOfBoundsError([0, 0, 64])),
+            "Location beyond Z extent accepted"
+        );
+        assert_eq!(
+            octree.try_at([255, 0, 64]),
+            Err(OctreeError::OutOfBoundsError([255, 0, 64]))
+        );
+        assert!(octree.try_at([255, 255, 0]).is_ok());
+        assert!(octree.cursor([0, 0, 64]).is_none());
+        assert!(octree.insert_plane(Axis::Z, 64, 1).is_err());
+        assert_eq!(octree.slice_mask(63).unwrap().len(), 256 * 256 / 64);
+
+        assert_eq!(
+            Octree::<u8>::new_rect([256, 256, 60]).unwrap_err(),
+            OctreeError::DimensionError
+        );
+        assert!(Octree::<u8>::new_rect([1, 2, 4]).is_ok());
+    }
+
+    #[test]
+    fn test_neighbors6_coords() {
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        octree.insert([14, 15, 0], 255).unwrap();
+        octree.insert([15, 15, 1], 128).unwrap();
+
+        let neighbors = octree.neighbors6_coords([15, 15, 0]);
+        assert_eq!(neighbors[0], (Some([14, 15, 0]), Some(255)));
+        assert_eq!(
+            neighbors[1],
+            (None, None),
+            "Out of bounds neighbor has a location"
+        );
+        assert_eq!(neighbors[2], (Some([15, 14, 0]), None));
+        assert_eq!(neighbors[3], (None, None));
+        assert_eq!(neighbors[4], (None, None));
+        assert_eq!(neighbors[5], (Some([15, 15, 1]), Some(128)));
+
+        let inside = octree.neighbors6_coords([8, 8, 8]);
+        assert!(inside.iter().all(|(loc, _)| loc.is_some()));
+    }
+
+    #[test]
+    fn test_find_path() {
+        let mut octree = Octree::<u8>::new(8).unwrap();
+        for y in 0..7 {
+            for z in 0..8 {
+                octree.insert([4, y, z], 255).unwrap();
+            }
+        }
+
+        let path = octree
+            .find_path([2, 0, 0], [6, 0, 0], |data| data.is_none())
+            .unwrap();
+        assert_eq!(path.first(), Some(&[2, 0, 0]));
+        assert_eq!(path.last(), Some(&[6, 0, 0]));
+        assert_eq!(path.len(), 19, "Path is not the shortest detour");
+        assert!(
+            path.iter().all(|loc| octree.at(*loc).is_none()),
+            "Path passes through the wall"
+        );
+        assert!(
+            path.contains(&[4, 7, 0]),
+            "Path does not go around the wall"
+        );
+        for pair in path.windows(2) {
+            let distance: i32 = (0..3)
+                .map(|axis| (i32::from(pair[0][axis]) - i32::from(pair[1][axis])).abs())
+                .sum();
+            assert_eq!(distance, 1, "Path steps are not face-adjacent");
+        }
+
+        octree.fill([4, 7, 0], [4, 7, 7], 255).unwrap();
+        assert_eq!(
+            octree.find_path([2, 0, 0], [6, 0, 0], |data| data.is_none()),
+            None,
+            "Path found through a sealed wall"
+        );
+        assert_eq!(
+            octree
+                .find_path([4, 0, 0], [4, 6, 7], |data| data.is_some())
+                .map(|p| p.len()),
+            Some(14)
+        );
+        assert_eq!(octree.find_path([8, 0, 0], [0, 0, 0], |_| true), None);
+    }
+
+    #[test]
+    fn test_iter_region() {
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        octree.fill([8, 8, 8], [15, 15, 15], 255).unwrap();
+        octree.insert([14, 2, 3], 128).unwrap();
+        octree.insert([2, 2, 2], 64).unwrap();
+
+        let mut region: Vec<([u16; 3], u8)> =
+            octree.iter_region([14, 0, 0], [100, 100, 100]).collect();
+        assert_eq!(
+            region.len(),
+            2 * 8 * 8 + 1,
+            "Incorrect voxels yielded in region"
+        );
+        assert!(region.contains(&([14, 2, 3], 128)));
+        assert!(region.iter().all(|&(loc, data)| {
+            loc.iter().all(|&c| c < 16) && loc[0] >= 14 && octree.at(loc) == Some(data)
+        }));
+
+        let mut expected = octree.query_region([14, 0, 0], [15, 15, 15]);
+        expected.sort();
+        region.sort();
+        assert_eq!(region, expected);
+
+        assert_eq!(octree.iter_region([16, 0, 0], [20, 20, 20]).count(), 0);
+        assert_eq!(octree.iter_region([5, 5, 5], [4, 4, 4]).count(), 0);
+        assert_eq!(
+            octree.iter_region([0, 0, 0], [u16::MAX; 3]).count(),
+            octree.len()
+        );
+    }
+
+    #[test]
+    fn test_try_insert_all() {
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        octree.insert([12, 10, 6], 128).unwrap();
+        let original = octree.clone();
+
+        let items = vec![
+            ([0, 0, 0], 255),
+            ([1, 0, 0], 255),
+            ([3, 16, 2], 64),
+            ([2, 0, 0], 1),
+        ];
+        assert_eq!(
+            octree.try_insert_all(items.clone()),
+            Err(OctreeError::OutOfBoundsError([3, 16, 2]))
+        );
+        assert!(octree == original, "Octree modified by failed transaction");
+
+        assert!(octree.insert_many(items).is_err());
+        assert_eq!(
+            octree.at([1, 0, 0]),
+            Some(255),
+            "insert_many did not apply the items before the error"
+        );
+
+        let mut octree = original.clone();
+        octree
+            .try_insert_all(vec![([0, 0, 0], 255), ([15, 15, 15], 64)])
+            .unwrap();
+        assert_eq!(octree.len(), 3);
+    }
+
+    #[test]
+    fn test_meta() {
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        assert_eq!(octree.meta().origin, [0.0; 3]);
+        assert_eq!(octree.meta().voxel_size, 1.0);
+
+        octree.insert([12, 10, 6], 128).unwrap();
+        octree.meta_mut().origin = [1.5, -2.0, 0.25];
+        octree.meta_mut().voxel_size = 0.5;
+        octree.meta_mut().name = String::from("terrain");
+
+        let json = serde_json::to_string(&octree).unwrap();
+        let restored: Octree<u8> = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.meta(), octree.meta());
+        assert_eq!(restored.at([12, 10, 6]), Some(128));
+
+        let mut csv = vec![];
+        restored.export_csv(&mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "x,y,z,value\n7.75,3.25,3.5,128\n",
+            "Exported coordinates ignore the metadata"
+        );
+    }
+
+    #[test]
+    fn test_bool_ops() {
+        let mut a = Octree::<bool>::new(16).unwrap();
+        let mut b = Octree::<bool>::new(16).unwrap();
+        a.fill([0, 0, 0], [7, 7, 7], true).unwrap();
+        a.insert([12, 10, 6], true).unwrap();
+        b.fill([4, 4, 4], [11, 11, 11], true).unwrap();
+        b.insert([12, 10, 6], false).unwrap();
+
+        let and = a.and(&b).unwrap();
+        let or = a.or(&b).unwrap();
+        let xor = a.xor(&b).unwrap();
+        for loc in &[
+            [0, 0, 0],
+            [5, 5, 5],
+            [10, 10, 10],
+            [12, 10, 6],
+            [15, 15, 15],
+        ] {
+            let lhs = a.at(*loc) == Some(true);
+            let rhs = b.at(*loc) == Some(true);
+            let occupied = a.at(*loc).is_some() || b.at(*loc).is_some();
+            let expected = |value: bool| if occupied { Some(value) } else { None };
+            assert_eq!(and.at(*loc), expected(lhs && rhs), "Wrong AND at {:?}", loc);
+            assert_eq!(or.at(*loc), expected(lhs || rhs), "Wrong OR at {:?}", loc);
+            assert_eq!(xor.at(*loc), expected(lhs != rhs), "Wrong XOR at {:?}", loc);
+        }
+        assert_eq!(or.len(), a.len() + b.len() - 4 * 4 * 4 - 1);
+        assert_eq!(
+            xor.iter_positions().filter(|&(_, set)| set).count(),
+            513 + 512 - 128
+        );
+
+        let full = Octree::<bool>::from_fn(16, |_| Some(true)).unwrap();
+        assert_eq!(
+            full.or(&a).unwrap().node_count(),
+            1,
+            "Uniform result not simplified"
+        );
+        assert_eq!(full.and(&a).unwrap().at([12, 10, 6]), Some(true));
+
+        let c = Octree::<bool>::new_with_default(16, true).unwrap();
+        let not_a = a.xor(&c).unwrap();
+        assert_eq!(not_a.at([0, 0, 0]), Some(false));
+        assert_eq!(
+            not_a.at([15, 15, 15]),
+            Some(true),
+            "Default values not combined"
+        );
+
+        let small = Octree::<bool>::new(8).unwrap();
+        assert_eq!(a.and(&small), Err(OctreeError::DimensionError));
+    }
+
+    #[test]
+    fn test_take_if() {
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        octree.insert([12, 10, 6], 128).unwrap();
+        octree.insert([0, 0, 0], 255).unwrap();
+
+        assert_eq!(octree.take_if([12, 10, 6], |value| value == 255), None);
+        assert_eq!(octree.at([12, 10, 6]), Some(128), "Removed without a match");
+        assert_eq!(octree.take_if([1, 0, 0], |_| true), None);
+        assert_eq!(octree.take_if([16, 0, 0], |_| true), None);
+
+        assert_eq!(octree.take_if([12, 10, 6], |value| value == 128), Some(128));
+        assert_eq!(octree.at([12, 10, 6]), None, "Not removed on a match");
+        assert_eq!(octree.at([0, 0, 0]), Some(255));
+        assert_eq!(octree.len(), 1);
+    }
+
+    #[test]
+    fn test_insert_growing() {
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        octree.insert([12, 10, 6], 128).unwrap();
+        octree.fill([0, 0, 0], [7, 7, 7], 64).unwrap();
+
+        octree.insert_growing([9, 2, 1], 1).unwrap();
+        assert_eq!(octree.dimension(), 16, "Grown for an in-bounds location");
+
+        octree.insert_growing([100, 0, 20], 255).unwrap();
+        assert_eq!(octree.dimension(), 128);
+        assert_eq!(octree.extents(), [128; 3]);
+        assert_eq!(octree.max_depth(), 7);
+        assert_eq!(octree.at([100, 0, 20]), Some(255));
+        assert_eq!(octree.at([12, 10, 6]), Some(128), "Existing content moved");
+        assert_eq!(octree.at([7, 7, 7]), Some(64));
+        assert_eq!(octree.at([9, 2, 1]), Some(1));
+        assert_eq!(octree.len(), 8 * 8 * 8 + 3);
+
+        let mut rect = Octree::<u8>::new_rect([16, 16, 4]).unwrap();
+        rect.insert_growing([0, 0, 9], 255).unwrap();
+        assert_eq!(rect.extents(), [16, 16, 16]);
+        assert_eq!(rect.dimension(), 16);
+
+        let before = octree.clone();
+        assert_eq!(
+            octree.insert_growing([40000, 0, 0], 1),
+            Err(OctreeError::OutOfBoundsError([40000, 0, 0]))
+        );
+        assert!(octree == before, "Octree modified by failed growth");
+    }
+
+    #[test]
+    fn test_set_raw() {
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        for z in 0..4 {
+            for y in 0..4 {
+                for x in 0..4 {
+                    octree.set_raw([x, y, z], 255).unwrap();
+                }
+            }
+        }
+        octree.set_raw([12, 10, 6], 128).unwrap();
+        assert_eq!(
+            octree.set_raw([16, 0, 0], 1),
+            Err(OctreeError::OutOfBoundsError([16, 0, 0]))
+        );
+
+        let unsimplified = octree.node_count();
+        assert_eq!(octree.at([3, 3, 3]), Some(255));
+        assert_eq!(octree.len(), 4 * 4 * 4 + 1);
+
+        octree.resimplify();
+        assert!(
+            octree.node_count() < unsimplified,
+            "Uniform region not collapsed"
+        );
+        assert_eq!(octree.node_as_ref([0, 0, 0]).unwrap().dimension(), 4);
+        assert_eq!(octree.at([3, 3, 3]), Some(255));
+        assert_eq!(octree.at([12, 10, 6]), Some(128));
+        assert_eq!(octree.len(), 4 * 4 * 4 + 1);
+    }
+
+    #[test]
+    fn test_delta() {
+        let mut original = Octree::<u8>::new(16).unwrap();
+        original.insert([12, 10, 6], 128).unwrap();
+        original.insert([1, 2, 3], 64).unwrap();
+        original.insert([15, 15, 15], 32).unwrap();
+
+        let mut edited = original.clone();
+        edited.insert([0, 0, 0], 255).unwrap();
+        edited.insert([12, 10, 6], 1).unwrap();
+        edited.take([1, 2, 3]);
+
+        let mut delta = vec![];
+        edited.encode_delta(&original, &mut delta).unwrap();
+        assert_eq!(delta.len(), 6 + 8 + 2 * 8 + 7, "Unchanged voxels encoded");
+
+        let mut synced = original.clone();
+        synced.apply_delta(&delta[..]).unwrap();
+        assert!(synced == edited, "Delta did not reproduce the edits");
+
+        assert_eq!(
+            synced.apply_delta(&delta[..delta.len() - 1]),
+            Err(OctreeError::IoError(std::io::ErrorKind::UnexpectedEof))
+        );
+        assert!(synced == edited, "Octree modified by a truncated delta");
+
+        let mut small = Octree::<u8>::new(8).unwrap();
+        assert_eq!(
+            edited.encode_delta(&small, &mut vec![]),
+            Err(OctreeError::DimensionError)
+        );
+        assert_eq!(
+            small.apply_delta(&delta[..]),
+            Err(OctreeError::DimensionError)
+        );
+    }
+
+    #[test]
+    fn test_on_change() {
+        use std::sync::{Arc, Mutex};
+
+        let changes = Arc::new(Mutex::new(vec![]));
+        let log = changes.clone();
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        octree.set_on_change(Box::new(move |loc, old, new| {
+            log.lock().unwrap().push((loc, old, new))
+        }));
+
+        octree.insert([0, 0, 0], 255).unwrap();
+        octree.insert([0, 0, 0], 255).unwrap();
+        octree.insert([0, 0, 0], 128).unwrap();
+        for loc in (1..8).map(|i| [i & 1, (i >> 1) & 1, i >> 2]) {
+            octree.insert(loc, 128).unwrap();
+        }
+        octree.take([1, 1, 1]);
+        octree.take([1, 1, 1]);
+        octree.insert_none([0, 0, 0]);
+        assert!(octree.insert([16, 0, 0], 1).is_err());
+
+        let mut expected = vec![
+            ([0, 0, 0], None, Some(255)),
+            ([0, 0, 0], Some(255), Some(128)),
+        ];
+        for loc in (1..8).map(|i| [i & 1, (i >> 1) & 1, i >> 2]) {
+            expected.push((loc, None, Some(128)));
+        }
+        expected.push(([1, 1, 1], Some(128), None));
+        expected.push(([0, 0, 0], Some(128), None));
+        assert_eq!(*changes.lock().unwrap(), expected);
+
+        let mut copy = octree.clone();
+        copy.insert([5, 5, 5], 1).unwrap();
+        assert_eq!(
+            changes.lock().unwrap().len(),
+            expected.len(),
+            "Callback cloned"
+        );
+    }
+
+    #[test]
+    fn test_dirty_bounds() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        assert_eq!(octree.take_dirty_bounds(), None);
+
+        octree.insert([1, 14, 2], 255).unwrap();
+        octree.insert([13, 0, 15], 128).unwrap();
+        assert!(octree.insert([16, 0, 0], 1).is_err());
+        assert_eq!(octree.take_dirty_bounds(), Some(([1, 0, 2], [13, 14, 15])));
+        assert_eq!(octree.take_dirty_bounds(), None, "Dirty bounds not reset");
+
+        octree.take([13, 0, 15]);
+        octree.insert_none([4, 5, 6]);
+        octree.insert_none([0, 16, 0]);
+        assert_eq!(octree.take_dirty_bounds(), Some(([4, 0, 6], [13, 5, 15])));
+
+        octree.fill([0, 0, 0], [3, 3, 3], 5).unwrap();
+        assert_eq!(octree.take_dirty_bounds(), Some(([0, 0, 0], [3, 3, 3])));
+
+        let changed = Arc::new(AtomicUsize::new(0));
+        let counter = changed.clone();
+        octree.set_on_change(Box::new(move |_, old, new| {
+            assert_eq!((old, new), (None, Some(5)));
+            counter.fetch_add(1, Ordering::Relaxed);
+        }));
+        octree.fill([0, 0, 0], [7, 3, 3], 5).unwrap();
+        assert_eq!(changed.load(Ordering::Relaxed), 64);
+        assert_eq!(octree.take_dirty_bounds(), Some(([0, 0, 0], [7, 3, 3])));
+    }
+
+    #[test]
+    fn test_map_parallel() {
+        let octree = Octree::<u8>::from_fn(32, |loc| {
+            if loc[0] < 20 && (loc[1] + loc[2]).is_multiple_of(3) {
+                Some((loc[0] / 4) as u8)
+            } else {
+                None
+            }
+        })
+        .unwrap();
+
+        let double = |value: u8| u16::from(value) * 2;
+        let serial = octree.map(double);
+        assert_eq!(serial.at([13, 3, 0]), Some(6));
+        assert_eq!(serial.at([13, 3, 1]), None);
+        assert_eq!(serial.len(), octree.len());
+        for threads in &[0, 1, 3, 8, 16] {
+            assert!(
+                octree.map_parallel(*threads, double) == serial,
+                "Parallel map differs with {} threads",
+                threads
+            );
+        }
+
+        let gradient = Octree::<u8>::from_fn(16, |loc| Some(loc[0] as u8)).unwrap();
+        let constant = gradient.map(|_| 1u8);
+        assert_eq!(constant.node_count(), 1, "Mapped values not merged");
+        assert!(gradient.map_parallel(4, |_| 1u8) == constant);
+    }
+
+    #[test]
+    fn test_marks() {
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        octree.insert([12, 10, 6], 128).unwrap();
+        octree.fill([0, 0, 0], [3, 3, 3], 255).unwrap();
+        let original = octree.clone();
+        let nodes = octree.node_count();
+
+        assert!(octree.mark([12, 10, 6]));
+        assert!(octree.mark([1, 2, 3]));
+        assert!(!octree.mark([8, 0, 0]), "Marked an unallocated region");
+        assert!(!octree.mark([16, 0, 0]));
+        assert!(octree.is_marked([12, 10, 6]));
+        assert!(
+            octree.is_marked([3, 3, 3]),
+            "Mark not shared by the simplified block"
+        );
+        assert!(!octree.is_marked([12, 10, 7]));
+
+        assert!(octree == original, "Marks affect equality");
+        assert_eq!(octree.node_count(), nodes);
+        assert_eq!(octree.at([12, 10, 6]), Some(128));
+        assert!(!original.is_marked([12, 10, 6]), "Mark leaked into a clone");
+
+        assert!(octree.unmark([1, 2, 3]));
+        assert!(!octree.is_marked([1, 2, 3]));
+        octree.clear_marks();
+        assert!(!octree.is_marked([12, 10, 6]), "Marks not cleared");
+        assert!(octree == original);
+        assert_eq!(octree.len(), original.len());
+    }
+
+    #[test]
+    fn test_any_in_region() {
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        assert!(!octree.any_in_region([0, 0, 0], [15, 15, 15]));
+
+        octree.insert([8, 4, 4], 255).unwrap();
+        assert!(
+            octree.any_in_region([4, 4, 4], [8, 8, 8]),
+            "Voxel just inside missed"
+        );
+        assert!(
+            !octree.any_in_region([4, 4, 4], [7, 8, 8]),
+            "Voxel just outside found"
+        );
+        assert!(!octree.any_in_region([9, 0, 0], [15, 15, 15]));
+        assert!(
+            !octree.any_in_region([8, 5, 4], [8, 4, 4]),
+            "Inverted box found a voxel"
+        );
+
+        octree.fill([0, 0, 8], [7, 7, 15], 128).unwrap();
+        assert!(octree.any_in_region([7, 7, 15], [20, 20, 20]));
+        assert!(!octree.any_in_region([0, 8, 8], [7, 15, 15]));
+        assert!(!octree.any_in_region([16, 0, 0], [20, 20, 20]));
+
+        let mut rect = Octree::<u8>::new_rect([16, 16, 4]).unwrap();
+        rect.insert([0, 0, 3], 1).unwrap();
+        assert!(rect.any_in_region([0, 0, 3], [0, 0, 100]));
+        assert!(!rect.any_in_region([0, 0, 4], [0, 0, 15]));
+    }
+
+    #[test]
+    fn test_symmetric_difference() {
+        let mut a = Octree::<u8>::new(16).unwrap();
+        let mut b = Octree::<u8>::new(16).unwrap();
+        a.insert([4, 4, 4], 255).unwrap();
+        b.insert([4, 4, 4], 1).unwrap();
+        a.insert([12, 10, 6], 128).unwrap();
+        b.insert([0, 15, 3], 64).unwrap();
+
+        let mut difference: Vec<_> = a.symmetric_difference(&b).unwrap().collect();
+        difference.sort();
+        assert_eq!(difference, vec![[0, 15, 3], [12, 10, 6]]);
+        assert_eq!(a.symmetric_difference(&a).unwrap().count(), 0);
+
+        let mut block = Octree::<u8>::new(16).unwrap();
+        block.fill([0, 0, 0], [7, 7, 7], 1).unwrap();
+        let mut holed = block.clone();
+        holed.take([3, 3, 3]);
+        holed.insert([8, 0, 0], 1).unwrap();
+        let mut difference: Vec<_> = block.symmetric_difference(&holed).unwrap().collect();
+        difference.sort();
+        assert_eq!(difference, vec![[3, 3, 3], [8, 0, 0]]);
+
+        let small = Octree::<u8>::new(8).unwrap();
+        assert!(a.symmetric_difference(&small).is_err());
+    }
+
+    #[test]
+    fn test_iter_strided() {
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        octree.fill([1, 0, 0], [8, 7, 7], 255).unwrap();
+        octree.insert([13, 10, 6], 128).unwrap();
+        octree.insert([12, 10, 6], 128).unwrap();
+
+        let voxels: Vec<_> = octree.iter_strided(2).collect();
+        assert!(voxels
+            .iter()
+            .all(|(loc, _)| loc.iter().all(|c| c.is_multiple_of(2))));
+        assert_eq!(voxels.len(), 4 * 4 * 4 + 1);
+        assert!(voxels.contains(&([12, 10, 6], 128)));
+        assert!(voxels.contains(&([8, 6, 0], 255)));
+
+        assert_eq!(octree.iter_strided(1).count(), octree.len());
+        assert_eq!(octree.iter_strided(16).collect::<Vec<_>>(), vec![]);
+        assert_eq!(octree.iter_strided(3).count(), 2 * 3 * 3);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_iter_strided_zero() {
+        let octree = Octree::<u8>::new(16).unwrap();
+        octree.iter_strided(0).count();
+    }
+
+    #[test]
+    fn test_map_region() {
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        octree.fill([0, 0, 0], [7, 7, 7], 1).unwrap();
+        octree.insert([12, 10, 6], 128).unwrap();
+        octree
+            .map_region([4, 4, 4], [4, 4, 4], |_, _| Some(9))
+            .unwrap();
+        assert_eq!(octree.at([4, 4, 4]), Some(9));
+        assert_eq!(octree.len(), 8 * 8 * 8 + 1, "Simplified block not split");
+
+        octree
+            .map_region([2, 2, 2], [9, 9, 9], |_, value| match value {
+                Some(value) if value + 1 > 5 => None,
+                Some(value) => Some(value + 1),
+                None => Some(0),
+            })
+            .unwrap();
+        assert_eq!(octree.at([2, 2, 2]), Some(2), "Value not incremented");
+        assert_eq!(octree.at([7, 7, 7]), Some(2));
+        assert_eq!(
+            octree.at([4, 4, 4]),
+            None,
+            "Value over threshold not cleared"
+        );
+        assert_eq!(
+            octree.at([1, 1, 1]),
+            Some(1),
+            "Value outside the box changed"
+        );
+        assert_eq!(octree.at([8, 8, 8]), Some(0));
+        assert_eq!(octree.at([12, 10, 6]), Some(128));
+        assert_eq!(octree.len(), 8 * 8 * 8 + 8 * 8 * 8 - 6 * 6 * 6);
+
+        let mut calls = 0;
+        octree
+            .map_region([0, 0, 0], [15, 15, 15], |_, value| {
+                calls += 1;
+                value.map(|_| 3)
+            })
+            .unwrap();
+        assert_eq!(calls, 16 * 16 * 16);
+        assert_eq!(octree.at([8, 8, 8]), Some(3));
+        assert_eq!(octree.at([4, 4, 4]), None);
+
+        octree
+            .map_region([0, 0, 0], [15, 15, 15], |_, _| None)
+            .unwrap();
+        assert!(octree.is_empty());
+        assert_eq!(octree.node_count(), 1, "Cleared region not simplified");
+
+        octree
+            .map_region([0, 0, 0], [7, 7, 7], |_, _| Some(4))
+            .unwrap();
+        assert_eq!(octree.node_as_ref([0, 0, 0]).unwrap().dimension(), 8);
+        assert_eq!(
+            octree.map_region([0, 0, 0], [16, 0, 0], |_, value| value),
+            Err(OctreeError::OutOfBoundsError([16, 0, 0]))
+        );
+    }
+
+    #[test]
+    fn test_occupied_count() {
+        let traversal_count = |octree: &Octree<u8>| octree.iter_positions().count();
+
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        let mut seed = 12345u32;
+        let mut next = |range: u32| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            ((seed >> 16) % range) as u16
+        };
+        for _ in 0..2000 {
+            let loc = [next(4), next(4), next(4)];
+            match next(4) {
+                0 => {
+                    octree.take(loc);
+                }
+                1 => octree.insert_none(loc),
+                _ => octree.insert(loc, next(2) as u8).unwrap(),
+            }
+            assert_eq!(
+                octree.len(),
+                traversal_count(&octree),
+                "Count wrong at {:?}",
+                loc
+            );
+        }
+
+        octree.fill([2, 2, 2], [11, 11, 11], 7).unwrap();
+        assert_eq!(octree.len(), traversal_count(&octree));
+        octree.take([8, 8, 8]);
+        octree.insert_none([4, 4, 4]);
+        octree.insert([15, 15, 15], 1).unwrap();
+        assert_eq!(octree.len(), traversal_count(&octree));
+        octree
+            .map_region([0, 0, 0], [5, 5, 5], |loc, value| {
+                if loc[0] == 0 {
+                    None
+                } else {
+                    value.or(Some(1))
+                }
+            })
+            .unwrap();
+        assert_eq!(octree.len(), traversal_count(&octree));
+        assert_eq!(octree.volume(), octree.len() as u64);
+
+        let json = serde_json::to_string(&octree).unwrap();
+        let restored: Octree<u8> = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            restored.len(),
+            octree.len(),
+            "Count not recomputed after loading"
+        );
+
+        let snapshot = octree.snapshot();
+        octree.fill([0, 0, 0], [15, 15, 15], 1).unwrap();
+        assert_eq!(octree.len(), 16 * 16 * 16);
+        octree.restore(snapshot);
+        assert_eq!(octree.len(), restored.len(), "Count not reset by restore");
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_to_json_voxels() {
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        octree.fill([0, 0, 0], [1, 1, 1], 255).unwrap();
+        octree.insert([12, 10, 6], 128).unwrap();
+
+        let json: serde_json::Value = serde_json::from_str(&octree.to_json_voxels()).unwrap();
+        assert_eq!(json["dimension"], 16);
+        let voxels = json["voxels"].as_array().unwrap();
+        assert_eq!(voxels.len(), 9);
+        assert!(voxels.contains(&serde_json::json!({"x": 12, "y": 10, "z": 6, "value": 128})));
+        assert!(voxels.contains(&serde_json::json!({"x": 1, "y": 0, "z": 1, "value": 255})));
+    }
+
+    #[test]
+    fn test_path_cache() {
+        use octree::{OctreeBuilder, SimplificationMode};
+
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        octree.enable_path_cache(2);
+        octree.insert([12, 10, 6], 1).unwrap();
+        octree.insert([13, 10, 6], 2).unwrap();
+
+        assert_eq!(octree.at_cached([12, 10, 6]), Some(1));
+        assert_eq!(octree.at_cached([13, 10, 6]), Some(2));
+        assert_eq!(octree.at_cached([0, 0, 0]), None);
+
+        octree.insert([12, 10, 6], 3).unwrap();
+        octree.take([13, 10, 6]);
+        assert_eq!(octree.at_cached([12, 10, 6]), Some(3));
+        assert_eq!(octree.at_cached([13, 10, 6]), None);
+
+        octree.fill([8, 8, 4], [15, 15, 7], 4).unwrap();
+        assert_eq!(octree.at_cached([12, 10, 6]), Some(4));
+        assert_eq!(octree.at_cached([13, 10, 6]), Some(4));
+
+        let snapshot = octree.snapshot();
+        octree.insert([0, 0, 0], 5).unwrap();
+        assert_eq!(octree.at_cached([0, 0, 0]), Some(5));
+        octree.restore(snapshot);
+        assert_eq!(octree.at_cached([0, 0, 0]), None);
+
+        let mut uncached = octree.clone();
+        uncached.enable_path_cache(0);
+        for x in 0..16 {
+            for y in 0..16 {
+                for z in 0..16 {
+                    assert_eq!(octree.at_cached([x, y, z]), uncached.at([x, y, z]));
+                }
+            }
+        }
+
+        let modes = [
+            SimplificationMode::Eager,
+            SimplificationMode::Off,
+            SimplificationMode::Tolerance(1.0),
+        ];
+        for &mode in &modes {
+            let build = || {
+                OctreeBuilder::new()
+                    .dimension(16)
+                    .simplification(mode)
+                    .metric(|a: u8, b: u8| (f64::from(a) - f64::from(b)).abs())
+                    .build()
+                    .unwrap()
+            };
+            let mut cached = build();
+            let mut uncached = build();
+            cached.enable_path_cache(3);
+
+            let mut seed = 7u32;
+            let mut snapshot = None;
+            for step in 0..2000 {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                let loc = [
+                    (seed >> 8) as u16 % 8,
+                    (seed >> 12) as u16 % 8,
+                    (seed >> 16) as u16 % 4,
+                ];
+                let value = 1 + (seed >> 20) as u8 % 2;
+                cached.insert(loc, value).unwrap();
+                uncached.insert(loc, value).unwrap();
+                assert_eq!(cached.at_cached(loc), uncached.at(loc));
+                if step == 1000 {
+                    snapshot = Some((cached.snapshot(), uncached.clone()));
+                }
+            }
+
+            for x in 8..16 {
+                for y in 0..8 {
+                    for z in 0..8 {
+                        cached.insert([x, y, z], 3).unwrap();
+                        uncached.insert([x, y, z], 3).unwrap();
+                    }
+                }
+            }
+            assert_eq!(cached.at_cached([12, 4, 4]), Some(3));
+
+            assert_eq!(cached, uncached, "Cached inserts diverged under {:?}", mode);
+            assert_eq!(cached.node_count(), uncached.node_count());
+            cached.assert_consistent();
+
+            let (snapshot, expected) = snapshot.unwrap();
+            cached.restore(snapshot);
+            assert_eq!(cached, expected, "Cached inserts leaked into a snapshot");
+        }
+    }
+
+    #[test]
+    fn test_from_columns() {
+        let heights = |x: u16, y: u16| (x + y) / 4;
+        let mut columns = vec![];
+        for x in 0..8 {
+            for y in 0..8 {
+                let column = (0..heights(x, y)).map(|z| (z, 1)).collect();
+                columns.push(([x, y], column));
+            }
+        }
+        columns.push(([3, 3], vec![(7, 2)]));
+
+        let octree = Octree::<u8>::from_columns(8, columns).unwrap();
+        assert_eq!(octree.at([0, 0, 0]), None);
+        assert_eq!(octree.at([7, 7, 2]), Some(1));
+        assert_eq!(octree.at([7, 7, 3]), None);
+        assert_eq!(octree.at([4, 0, 0]), Some(1));
+        assert_eq!(octree.at([4, 0, 1]), None);
+        assert_eq!(octree.at([3, 3, 7]), Some(2));
+        assert_eq!(octree.volume(), 89);
+        assert_eq!(
+            octree,
+            Octree::from_fn(8, |[x, y, z]| {
+                if [x, y, z] == [3, 3, 7] {
+                    Some(2)
+                } else if z < heights(x, y) {
+                    Some(1)
+                } else {
+                    None
+                }
+            })
+            .unwrap()
+        );
+
+        assert_eq!(
+            Octree::<u8>::from_columns(8, vec![([0, 8], vec![(0, 1)])]),
+            Err(OctreeError::OutOfBoundsError([0, 8, 0]))
+        );
+        assert_eq!(
+            Octree::<u8>::from_columns(8, vec![([0, 0], vec![(8, 1)])]),
+            Err(OctreeError::OutOfBoundsError([0, 0, 8]))
+        );
+    }
+
+    #[test]
+    fn test_fill_changed() {
+        use octree::{OctreeBuilder, SimplificationMode};
+        use std::sync::{Arc, Mutex};
+
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        octree.insert([5, 5, 5], 2).unwrap();
+        assert_eq!(octree.fill_changed([0, 0, 0], [7, 7, 7], 1).unwrap(), 512);
+        assert_eq!(octree.volume(), 512);
+
+        octree.take_dirty_bounds();
+        let changes = Arc::new(Mutex::new(vec![]));
+        let log = Arc::clone(&changes);
+        octree.set_on_change(Box::new(move |loc, _, _| log.lock().unwrap().push(loc)));
+
+        assert_eq!(octree.fill_changed([0, 0, 0], [7, 7, 7], 1).unwrap(), 0);
+        assert_eq!(octree.fill_changed([2, 2, 2], [5, 5, 5], 1).unwrap(), 0);
+        assert_eq!(octree.take_dirty_bounds(), None);
+        assert!(changes.lock().unwrap().is_empty());
+
+        assert_eq!(octree.fill_changed([6, 6, 6], [9, 9, 9], 1).unwrap(), 56);
+        assert_eq!(octree.take_dirty_bounds(), Some(([6, 6, 6], [9, 9, 9])));
+        assert_eq!(changes.lock().unwrap().len(), 56);
+        assert_eq!(octree.volume(), 568);
+        assert_eq!(octree.at([9, 9, 9]), Some(1));
+        assert_eq!(octree.at([5, 5, 5]), Some(1));
+
+        assert_eq!(
+            octree.fill_changed([0, 0, 0], [16, 0, 0], 1),
+            Err(OctreeError::OutOfBoundsError([16, 0, 0]))
+        );
+
+        let build = || {
+            OctreeBuilder::new()
+                .dimension(16)
+                .simplification(SimplificationMode::Tolerance(0.5))
+                .metric(|a: f32, b: f32| f64::from((a - b).abs()))
+                .build()
+                .unwrap()
+        };
+        let mut tolerant = build();
+        tolerant.fill([0, 0, 0], [7, 7, 7], 1.0).unwrap();
+        let mut filled = tolerant.clone();
+        let changes = Arc::new(Mutex::new(0));
+        let log = Arc::clone(&changes);
+        filled.set_on_change(Box::new(move |_, _, _| *log.lock().unwrap() += 1));
+
+        filled.fill([4, 4, 4], [11, 11, 11], 1.2).unwrap();
+        assert_eq!(tolerant.fill_changed([4, 4, 4], [11, 11, 11], 1.2), Ok(448));
+        assert_eq!(*changes.lock().unwrap(), 448);
+        assert_eq!(tolerant, filled);
+    }
+
+    #[test]
+    fn test_desimplify_at() {
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        octree.fill([0, 0, 0], [7, 7, 7], 1).unwrap();
+        octree.insert([12, 10, 6], 2).unwrap();
+        let before = octree.clone();
+        let nodes = octree.node_count();
+
+        octree.desimplify_at([5, 2, 7]).unwrap();
+        assert_eq!(octree.node_count(), nodes + 24);
+        for x in 0..16 {
+            for y in 0..16 {
+                for z in 0..16 {
+                    assert_eq!(octree.at([x, y, z]), before.at([x, y, z]));
+                }
+            }
+        }
+        assert_eq!(octree.volume(), before.volume());
+
+        let nodes = octree.node_count();
+        octree.desimplify_at([5, 2, 7]).unwrap();
+        octree.desimplify_at([12, 10, 6]).unwrap();
+        octree.desimplify_at([15, 15, 15]).unwrap();
+        assert_eq!(octree.node_count(), nodes);
+
+        octree.resimplify();
+        assert_eq!(octree.node_count(), before.node_count());
+        assert_eq!(
+            octree.desimplify_at([16, 0, 0]),
+            Err(OctreeError::OutOfBoundsError([16, 0, 0]))
+        );
+    }
+
+    #[test]
+    fn test_closest_surface() {
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        assert_eq!(octree.closest_surface([3.0, 5.5, 5.5]), None);
+
+        octree.fill([8, 0, 0], [11, 15, 15], 1).unwrap();
+        assert_eq!(
+            octree.closest_surface([3.0, 5.5, 5.5]),
+            Some(([8, 5, 5], [-1.0, 0.0, 0.0]))
+        );
+        assert_eq!(
+            octree.closest_surface([14.2, 9.5, 2.5]),
+            Some(([11, 9, 2], [1.0, 0.0, 0.0]))
+        );
+
+        // Inside the wall, the nearest face is the one pushed out through
+        assert_eq!(
+            octree.closest_surface([10.7, 4.5, 4.5]),
+            Some(([11, 4, 4], [1.0, 0.0, 0.0]))
+        );
+
+        // A thin wall has empty faces on both sides, so the normal follows the point
+        let mut thin = Octree::<u8>::new(16).unwrap();
+        thin.fill([4, 0, 0], [4, 15, 15], 1).unwrap();
+        assert_eq!(
+            thin.closest_surface([9.0, 7.5, 7.5]),
+            Some(([4, 7, 7], [1.0, 0.0, 0.0]))
+        );
+        assert_eq!(
+            thin.closest_surface([0.0, 7.5, 7.5]),
+            Some(([4, 7, 7], [-1.0, 0.0, 0.0]))
+        );
+    }
+
+    #[test]
+    fn test_equalize() {
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        octree.fill([0, 0, 0], [7, 7, 7], 120).unwrap();
+        octree.fill([8, 0, 0], [15, 7, 7], 121).unwrap();
+        octree.insert([0, 8, 0], 118).unwrap();
+        octree.insert([0, 9, 0], 124).unwrap();
+        let nodes = octree.node_count();
+
+        octree.equalize();
+        assert_eq!(octree.at([0, 8, 0]), Some(0));
+        assert_eq!(octree.at([0, 9, 0]), Some(255));
+        assert_eq!(octree.at([0, 0, 0]), Some(127));
+        assert_eq!(octree.at([8, 0, 0]), Some(255));
+        assert_eq!(octree.at([0, 0, 8]), None);
+        assert_eq!(octree.volume(), 1026);
+        assert_eq!(octree.node_count(), nodes);
+
+        let mut uniform = Octree::<u8>::new(16).unwrap();
+        uniform.fill([0, 0, 0], [3, 3, 3], 7).unwrap();
+        uniform.equalize();
+        assert_eq!(uniform.at([3, 3, 3]), Some(7));
+    }
+
+    #[test]
+    fn test_blur() {
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        octree.insert([8, 8, 8], 100).unwrap();
+        let blurred = octree.blur(2);
+
+        let at = |loc| blurred.at(loc).unwrap();
+        let center = at([8, 8, 8]);
+        let close = |a: f32, b: f32| (a - b).abs() < 1e-4;
+        for offset in 1..=2 {
+            let neighbor = at([8 + offset, 8, 8]);
+            assert!(close(at([8 - offset, 8, 8]), neighbor));
+            assert!(close(at([8, 8 + offset, 8]), neighbor));
+            assert!(close(at([8, 8 - offset, 8]), neighbor));
+            assert!(close(at([8, 8, 8 + offset]), neighbor));
+            assert!(close(at([8, 8, 8 - offset]), neighbor));
+        }
+        assert!(center > at([9, 8, 8]));
+        assert!(at([9, 8, 8]) > at([10, 8, 8]));
+        assert!(at([9, 8, 8]) > at([9, 9, 8]));
+        assert_eq!(blurred.at([11, 8, 8]), None);
+        assert_eq!(blurred.volume(), 125);
+
+        let total: f32 = blurred.iter_morton().map(|(_, value)| value).sum();
+        assert!(close(total, 100.0));
+
+        assert_eq!(octree.blur(0).at([8, 8, 8]), Some(100.0));
+        assert!(Octree::<u8>::new(16).unwrap().blur(2).is_empty());
+    }
+
+    #[test]
+    fn test_shared_octree() {
+        use shared::SharedOctree;
+        use std::thread;
+
+        let shared = SharedOctree::new(Octree::<u8>::new(16).unwrap());
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let shared = shared.clone();
+                thread::spawn(move || {
+                    let mut seen = 0;
+                    while seen < 16 {
+                        let before = seen;
+                        while seen < 16 && shared.read_at([seen, 0, 0]).is_some() {
+                            seen += 1;
+                        }
+                        for x in before..seen {
+                            assert_eq!(shared.read_at([x, 0, 0]), Some(x as u8));
+                        }
+                        let pair =
+                            shared.read(|octree| (octree.at([15, 0, 0]), octree.at([15, 1, 0])));
+                        assert_eq!(pair.0, pair.1);
+                        assert!(shared.read_region([0, 0, 0], [15, 0, 0]).len() >= seen as usize);
+                    }
+                })
+            })
+            .collect();
+
+        let writer = shared.clone();
+        thread::spawn(move || {
+            for x in 0..16 {
+                writer.write(|octree| {
+                    octree.insert([x, 1, 0], x as u8).unwrap();
+                    octree.insert([x, 0, 0], x as u8).unwrap();
+                });
+            }
+        })
+        .join()
+        .unwrap();
+
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        assert_eq!(shared.read(|octree| octree.volume()), 32);
+    }
+
+    #[test]
+    fn test_to_flat() {
+        use math::{morton_decode, morton_encode};
+
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        octree.fill([0, 0, 0], [7, 7, 7], 1).unwrap();
+        octree.fill([8, 8, 8], [11, 11, 11], 2).unwrap();
+        for i in 0..16 {
+            octree.insert([i, 15 - i, (i * 5) % 16], 3).unwrap();
+        }
+
+        let flat = octree.to_flat();
+        for x in 0..17 {
+            for y in 0..16 {
+                for z in 0..16 {
+                    assert_eq!(flat.at([x, y, z]), octree.at([x, y, z]));
+                }
+            }
+        }
+
+        let mut voxels: Vec<_> = flat.iter().collect();
+        voxels.sort_by_key(|&(loc, _)| morton_encode(loc));
+        let expected: Vec<_> = octree
+            .iter_morton()
+            .map(|(key, data)| (morton_decode(key), data))
+            .collect();
+        assert_eq!(voxels, expected);
+
+        assert_eq!(Octree::<u8>::new(16).unwrap().to_flat().nodes().len(), 1);
+    }
+
+    #[test]
+    fn test_compact_serde() {
+        use serde::Serialize;
+
+        // Mirror of the field-by-field layout `OctreeNode<T>` used to serialize with
+        #[derive(Serialize)]
+        struct Verbose {
+            dimension: u16,
+            leaf: bool,
+            simplified: bool,
+            children: Vec<Option<Verbose>>,
+            data: Option<u8>,
+            lod: Option<u8>,
+        }
+
+        fn verbose(node: &OctreeNode<u8>) -> Verbose {
+            let children: Vec<_> = node
+                .children()
+                .iter()
+                .map(|c| c.as_ref().map(verbose))
+                .collect();
+            Verbose {
+                dimension: node.dimension(),
+                leaf: node.leaf(),
+                simplified: node.leaf() && node.get().is_some() && node.dimension() > 1,
+                children,
+                data: node.get(),
+                lod: None,
+            }
+        }
+
+        let mut octree = Octree::<u8>::new(64).unwrap();
+        octree.fill([0, 0, 0], [7, 7, 7], 1).unwrap();
+        for i in 0..16 {
+            octree.insert([i * 4, 63 - i, i * 3], i as u8).unwrap();
+        }
+
+        let compact = serde_json::to_string(octree.root()).unwrap();
+        let derived = serde_json::to_string(&verbose(octree.root())).unwrap();
+        assert!(compact.len() * 8 < derived.len());
+
+        let restored: OctreeNode<u8> = serde_json::from_str(&compact).unwrap();
+        assert_eq!(&restored, octree.root());
+
+        let json = serde_json::to_string(&octree).unwrap();
+        let restored: Octree<u8> = serde_json::from_str(&json).unwrap();
+        assert!(restored == octree);
+        assert_eq!(restored.at([8, 61, 6]), Some(2));
+        assert_eq!(restored.node_count(), octree.node_count());
+
+        let empty = Octree::<u8>::new(64).unwrap();
+        assert_eq!(serde_json::to_string(empty.root()).unwrap(), "[64,1]");
+        assert!(serde_json::from_str::<OctreeNode<u8>>("[64,0]").is_err());
+        assert!(serde_json::from_str::<OctreeNode<u8>>("[1,4]").is_err());
+        assert!(serde_json::from_str::<OctreeNode<u8>>("[2,4,1]").is_err());
+    }
+
+    #[test]
+    fn test_query_sphere() {
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        octree.insert([8, 8, 8], 1).unwrap();
+        octree.insert([11, 8, 8], 2).unwrap();
+        octree.insert([12, 8, 8], 3).unwrap();
+        octree.insert([10, 10, 10], 4).unwrap();
+
+        let mut found = octree.query_sphere([8.5, 8.5, 8.5], 3.0);
+        found.sort();
+        assert_eq!(found, vec![([8, 8, 8], 1), ([11, 8, 8], 2)]);
+
+        let mut found = octree.query_sphere([8.5, 8.5, 8.5], 3.5);
+        found.sort();
+        assert_eq!(
+            found,
+            vec![([8, 8, 8], 1), ([10, 10, 10], 4), ([11, 8, 8], 2)]
+        );
+
+        assert!(octree.query_sphere([8.5, 8.5, 8.5], -1.0).is_empty());
+        assert_eq!(
+            octree.query_sphere([8.5, 8.5, 8.5], 0.0),
+            vec![([8, 8, 8], 1)]
+        );
+
+        // A far-away block of 2^30 voxels is skipped without being expanded, and only the part
+        // of a nearby one within the sphere's bounding box is visited
+        let mut large = Octree::<u8>::new(2048).unwrap();
+        large
+            .fill([1024, 1024, 1024], [2047, 2047, 2047], 5)
+            .unwrap();
+        large.fill([0, 0, 0], [1023, 1023, 1023], 6).unwrap();
+        let found = large.query_sphere([0.5, 0.5, 0.5], 1.0);
+        assert_eq!(found.len(), 4);
+        assert!(found.iter().all(|&(_, data)| data == 6));
+        assert!(large.query_sphere([0.0, 0.0, 2000.0], 10.0).is_empty());
+    }
+
+    #[test]
+    fn test_intersects_aabb() {
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        octree.meta_mut().origin = [1.0, 0.0, 0.0];
+        octree.meta_mut().voxel_size = 0.5;
+        octree.insert([4, 2, 2], 255).unwrap();
+
+        // The voxel covers [3.0, 3.5) x [1.0, 1.5) x [1.0, 1.5)
+        assert!(octree.intersects_aabb([3.2, 1.1, 1.1], [3.3, 1.2, 1.2]));
+        assert!(octree.intersects_aabb([0.0, 0.0, 0.0], [3.0, 1.0, 1.0]));
+        assert!(octree.intersects_aabb([3.4, 1.4, 1.4], [20.0, 20.0, 20.0]));
+        assert!(!octree.intersects_aabb([2.0, 1.1, 1.1], [2.9, 1.2, 1.2]));
+        assert!(!octree.intersects_aabb([3.5, 1.1, 1.1], [4.0, 1.2, 1.2]));
+        assert!(!octree.intersects_aabb([3.2, 1.5, 1.1], [3.3, 1.6, 1.2]));
+        assert!(!octree.intersects_aabb([3.3, 1.2, 1.2], [3.2, 1.1, 1.1]));
+        assert!(!octree.intersects_aabb([-5.0, -5.0, -5.0], [0.9, -0.1, -0.1]));
+        assert!(!octree.intersects_aabb([9.0, 1.1, 1.1], [10.0, 1.2, 1.2]));
+    }
+
+    #[test]
+    fn test_random_edits() {
+        use std::collections::HashMap;
+
+        let mut seed = 2024u32;
+        let mut next = |range: u32| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (seed >> 16) % range
+        };
+
+        for &(dimension, values) in &[(4, 2), (8, 2), (8, 5), (16, 2)] {
+            let mut octree = Octree::<u8>::new(dimension).unwrap();
+            let mut shadow = HashMap::new();
+            let side = u32::from(dimension);
+
+            for step in 0..3000 {
+                let loc = [next(side) as u16, next(side) as u16, next(side) as u16];
+                match next(5) {
+                    0 => assert_eq!(octree.take(loc), shadow.remove(&loc), "take {:?}", loc),
+                    1 => {
+                        octree.insert_none(loc);
+                        shadow.remove(&loc);
+                    }
+                    _ => {
+                        let data = next(values) as u8;
+                        octree.insert(loc, data).unwrap();
+                        shadow.insert(loc, data);
+                    }
+                }
+
+                octree.assert_consistent();
+                assert_eq!(octree.len(), shadow.len(), "Step {} at {:?}", step, loc);
+                for (&loc, &data) in &shadow {
+                    assert_eq!(octree.at(loc), Some(data), "Step {} lost {:?}", step, loc);
+                }
+                if dimension > 8 && step % 50 != 0 {
+                    continue;
+                }
+                for x in 0..dimension {
+                    for y in 0..dimension {
+                        for z in 0..dimension {
+                            let loc = [x, y, z];
+                            let expected = shadow.get(&loc).cloned();
+                            assert_eq!(octree.at(loc), expected, "Step {} at {:?}", step, loc);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_insert_into_block() {
+        use octree::{OctreeBuilder, SimplificationMode};
+
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        octree.fill([0, 0, 0], [15, 15, 15], 1).unwrap();
+        assert_eq!(octree.node_count(), 1);
+
+        octree.insert([0, 0, 0], 2).unwrap();
+        octree.insert([9, 6, 13], 3).unwrap();
+        octree.assert_consistent();
+        for x in 0..16 {
+            for y in 0..16 {
+                for z in 0..16 {
+                    let expected = match [x, y, z] {
+                        [0, 0, 0] => 2,
+                        [9, 6, 13] => 3,
+                        _ => 1,
+                    };
+                    assert_eq!(octree.at([x, y, z]), Some(expected), "at {:?}", [x, y, z]);
+                }
+            }
+        }
+        assert_eq!(octree.len(), 4096);
+
+        octree.insert([0, 0, 0], 1).unwrap();
+        octree.insert([9, 6, 13], 1).unwrap();
+        assert_eq!(octree.node_count(), 1);
+
+        // Values within tolerance of a block leave it unchanged
+        let mut octree = OctreeBuilder::new()
+            .dimension(16)
+            .simplification(SimplificationMode::Tolerance(1.0))
+            .metric(|a: u8, b: u8| f64::from(a.abs_diff(b)))
+            .build()
+            .unwrap();
+        octree.fill([0, 0, 0], [7, 7, 7], 10).unwrap();
+        octree.insert([5, 6, 7], 11).unwrap();
+        assert_eq!(octree.at([5, 6, 7]), Some(10));
+        octree.insert([5, 6, 7], 20).unwrap();
+        assert_eq!(octree.at([5, 6, 7]), Some(20));
+        assert_eq!(octree.at([5, 6, 6]), Some(10));
+        octree.assert_consistent();
+    }
+
+    #[test]
+    fn test_cast() {
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        octree.fill([0, 0, 0], [7, 7, 7], 1).unwrap();
+        for x in 8..10 {
+            for y in 8..10 {
+                for z in 8..10 {
+                    octree.set_raw([x, y, z], 2).unwrap();
+                }
+            }
+        }
+        octree.insert([12, 10, 6], 3).unwrap();
+
+        let cast = octree.cast(|data| data.map(u32::from));
+        assert_eq!(cast.node_count(), octree.node_count());
+        assert_eq!(cast.at([5, 5, 5]), Some(1));
+        assert_eq!(cast.at([9, 8, 9]), Some(2));
+        assert_eq!(cast.at([12, 10, 6]), Some(3));
+        assert_eq!(cast.len(), octree.len());
+
+        // The unsimplified block is kept split, where `map` merges it
+        assert!(octree.map(u32::from).node_count() < cast.node_count());
+    }
+
+    #[test]
+    fn test_slice() {
+        let mut octree = Octree::<u8>::new(8).unwrap();
+        let points = [([0, 0], 1), ([7, 2], 2), ([3, 6], 3)];
+        for &([x, y], data) in &points {
+            octree.insert([x, y, 4], data).unwrap();
+        }
+        octree.insert([5, 5, 3], 4).unwrap();
+        octree.fill([0, 0, 5], [7, 7, 7], 5).unwrap();
+
+        let grid = octree.slice(4).unwrap();
+        assert_eq!(grid.len(), 8);
+        for (y, row) in grid.iter().enumerate() {
+            assert_eq!(row.len(), 8);
+            for (x, cell) in row.iter().enumerate() {
+                let expected = points
+                    .iter()
+                    .find(|&&([px, py], _)| usize::from(px) == x && usize::from(py) == y)
+                    .map(|&(_, data)| data);
+                assert_eq!(*cell, expected, "at {:?}", [x, y]);
+            }
+        }
+
+        assert!(octree
+            .slice(6)
+            .unwrap()
+            .iter()
+            .flatten()
+            .all(|&cell| cell == Some(5)));
+        assert_eq!(
+            octree.slice(8),
+            Err(OctreeError::OutOfBoundsError([0, 0, 8]))
+        );
+    }
+
+    #[test]
+    fn test_count_value_in_region() {
+        let mut octree = Octree::<u8>::new(32).unwrap();
+        octree.fill([0, 0, 0], [15, 15, 15], 1).unwrap();
+        octree.fill([16, 0, 0], [31, 15, 15], 2).unwrap();
+        octree.insert([20, 4, 4], 1).unwrap();
+        assert_eq!(octree.node_count(), 1 + 2 + 8 + 8 + 8 + 8);
+
+        // The box spans x = 10..=21 across both blocks, 12 * 8 * 8 voxels in all
+        let (min, max) = ([10, 2, 3], [21, 9, 10]);
+        assert_eq!(octree.count_value_in_region(min, max, 1), 6 * 64 + 1);
+        assert_eq!(octree.count_value_in_region(min, max, 2), 6 * 64 - 1);
+        assert_eq!(octree.count_value_in_region(min, max, 3), 0);
+
+        assert_eq!(
+            octree.count_value_in_region([0, 0, 0], [40, 40, 40], 2),
+            4095
+        );
+        assert_eq!(octree.count_value_in_region([0, 16, 0], [31, 31, 31], 1), 0);
+        assert_eq!(octree.count_value_in_region([32, 0, 0], [40, 0, 0], 1), 0);
+    }
+
+    #[test]
+    fn test_warp() {
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        octree.fill([0, 0, 0], [1, 1, 1], 1).unwrap();
+        octree.insert([12, 3, 6], 2).unwrap();
+        octree.insert([3, 12, 6], 3).unwrap();
+
+        let swapped = octree.warp(|[x, y, z]| Some([y, x, z]));
+        assert_eq!(swapped.at([3, 12, 6]), Some(2));
+        assert_eq!(swapped.at([12, 3, 6]), Some(3));
+        assert_eq!(swapped.at([1, 0, 1]), Some(1));
+        assert_eq!(swapped.len(), 10);
+        assert_eq!(swapped.node_count(), octree.node_count());
+
+        let shifted = octree.warp(|[x, y, z]| if x < 8 { Some([x + 10, y, z]) } else { None });
+        assert_eq!(shifted.at([13, 12, 6]), Some(3));
+        assert_eq!(shifted.at([12, 3, 6]), None);
+        assert_eq!(shifted.at([11, 1, 1]), Some(1));
+        assert_eq!(shifted.len(), 9);
+
+        let collapsed = octree.warp(|_| Some([0, 0, 0]));
+        assert_eq!(collapsed.len(), 1);
+        assert!(collapsed.at([0, 0, 0]).is_some());
+    }
+
+    #[test]
+    fn test_iter_blocks() {
+        use octree::Block;
+
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        octree.fill([8, 8, 8], [15, 15, 15], 1).unwrap();
+        octree.fill([0, 0, 0], [1, 1, 1], 2).unwrap();
+        octree.insert([5, 0, 3], 3).unwrap();
+        octree.insert([0, 9, 2], 3).unwrap();
+
+        let blocks: Vec<_> = octree.iter_blocks().collect();
+        assert_eq!(blocks.len(), 4);
+        let merged: Vec<_> = blocks.iter().filter(|block| block.simplified).collect();
+        assert_eq!(
+            merged,
+            vec![
+                &Block {
+                    origin: [0, 0, 0],
+                    size: 2,
+                    value: 2,
+                    simplified: true
+                },
+                &Block {
+                    origin: [8, 8, 8],
+                    size: 8,
+                    value: 1,
+                    simplified: true
+                },
+            ]
+        );
+        let mut voxels: Vec<_> = blocks.iter().filter(|block| !block.simplified).collect();
+        voxels.sort_by_key(|block| block.origin);
+        assert_eq!(voxels.len(), 2);
+        assert!(voxels
+            .iter()
+            .all(|block| block.size == 1 && block.value == 3));
+        assert_eq!(voxels[0].origin, [0, 9, 2]);
+        assert_eq!(voxels[1].origin, [5, 0, 3]);
+
+        let total: u64 = blocks
+            .iter()
+            .map(|block| u64::from(block.size).pow(3))
+            .sum();
+        assert_eq!(total, octree.volume());
+    }
+
+    #[test]
+    fn test_settle() {
+        use octree::Axis;
+
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        octree.insert([3, 9, 4], 1).unwrap();
+        octree.insert([3, 2, 4], 2).unwrap();
+        octree.insert([3, 0, 4], 3).unwrap();
+        octree.insert([7, 12, 7], 4).unwrap();
+        octree.fill([8, 0, 8], [15, 3, 15], 5).unwrap();
+        octree.insert([10, 6, 9], 6).unwrap();
+        let volume = octree.volume();
+
+        assert_eq!(octree.settle(Axis::Y), 4);
+        assert_eq!(octree.at([3, 0, 4]), Some(3));
+        assert_eq!(octree.at([3, 1, 4]), Some(2));
+        assert_eq!(octree.at([3, 2, 4]), Some(1));
+        assert_eq!(octree.at([3, 9, 4]), None);
+        assert_eq!(octree.at([7, 0, 7]), Some(4));
+        assert_eq!(octree.at([7, 12, 7]), None);
+        assert_eq!(octree.at([10, 4, 9]), Some(6));
+        assert_eq!(octree.at([10, 6, 9]), None);
+        assert_eq!(octree.volume(), volume);
+
+        assert_eq!(octree.settle(Axis::Y), 0);
+        assert_eq!(octree.settle(Axis::X), 8 * 4 * 8 + 5);
+        assert_eq!(octree.at([0, 0, 7]), Some(4));
+        assert_eq!(octree.at([7, 3, 15]), Some(5));
+        assert_eq!(octree.at([8, 3, 15]), None);
+        octree.assert_consistent();
+    }
+
+    #[test]
+    fn test_load_from() {
+        use codec::crc32;
+
+        let mut octree = Octree::<u8>::new_rect([16, 8, 16]).unwrap();
+        octree.fill([0, 0, 0], [7, 7, 7], 1).unwrap();
+        octree.insert([12, 5, 6], 2).unwrap();
+        octree.insert([3, 2, 14], 3).unwrap();
+
+        let mut bytes = vec![];
+        octree.save_to(&mut bytes).unwrap();
+        let loaded = Octree::<u8>::load_from(&bytes[..]).unwrap();
+        assert_eq!(loaded.extents(), [16, 8, 16]);
+        assert_eq!(loaded.node_count(), octree.node_count());
+        assert_eq!(loaded.volume(), octree.volume());
+        assert!(loaded.iter_morton().eq(octree.iter_morton()));
+
+        for len in 0..bytes.len() {
+            assert_eq!(
+                Octree::<u8>::load_from(&bytes[..len]).err(),
+                Some(OctreeError::CorruptData)
+            );
+        }
+        for i in 0..bytes.len() {
+            let mut flipped = bytes.clone();
+            flipped[i] ^= 0x10;
+            assert_eq!(
+                Octree::<u8>::load_from(&flipped[..]).err(),
+                Some(OctreeError::CorruptData)
+            );
+        }
+
+        // A re-checksummed payload missing its final node must still be rejected
+        let mut resealed = bytes[..bytes.len() - 5].to_vec();
+        let checksum = crc32(&resealed);
+        resealed.extend_from_slice(&checksum.to_le_bytes());
+        assert_eq!(
+            Octree::<u8>::load_from(&resealed[..]).err(),
+            Some(OctreeError::CorruptData)
+        );
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn test_iter_to_depth() {
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        octree.insert([1, 2, 3], 10).unwrap();
+        octree.insert([6, 5, 4], 30).unwrap();
+        octree.fill([8, 8, 8], [15, 15, 15], 20).unwrap();
+        octree.compute_lod(|values| values.iter().flatten().max().cloned());
+
+        let top: Vec<_> = octree.iter_to_depth(0).collect();
+        assert_eq!(top.len(), 8);
+        assert!(top.iter().all(|((_, size), _)| *size == 8));
+        assert_eq!(top[0], (([0, 0, 0], 8), Some(30)));
+        assert_eq!(top[6], (([8, 8, 8], 8), Some(20)));
+        assert_eq!(top.iter().filter(|(_, data)| data.is_none()).count(), 6);
+
+        // Only the first octant has children, the rest are yielded whole
+        let next: Vec<_> = octree.iter_to_depth(1).collect();
+        assert_eq!(next.len(), 8 + 7);
+        assert!(next.contains(&(([8, 8, 8], 8), Some(20))));
+        assert!(next.contains(&(([0, 0, 0], 4), Some(10))));
+        assert!(next.contains(&(([4, 4, 4], 4), Some(30))));
+
+        let leaves: Vec<_> = octree
+            .iter_to_depth(octree.max_depth())
+            .filter_map(|((origin, size), data)| data.map(|data| (origin, size, data)))
+            .collect();
+        assert_eq!(leaves.len(), 3);
+        assert!(leaves.contains(&([1, 2, 3], 1, 10)));
+
+        let empty = Octree::<u8>::new(16).unwrap();
+        assert_eq!(empty.iter_to_depth(0).count(), 1);
+    }
+
+    #[test]
+    fn test_single_cell() {
+        let mut octree = Octree::<u8>::new(1).unwrap();
+        assert_eq!(octree.max_depth(), 0);
+        assert_eq!(octree.at([0, 0, 0]), None);
+
+        octree.insert([0, 0, 0], 5).unwrap();
+        assert_eq!(octree.at([0, 0, 0]), Some(5));
+        assert_eq!(octree.node_count(), 1);
+        assert_eq!(octree.volume(), 1);
+        octree.insert([0, 0, 0], 6).unwrap();
+        assert_eq!(octree.at([0, 0, 0]), Some(6));
+        assert_eq!(
+            octree.insert([1, 0, 0], 5),
+            Err(OctreeError::OutOfBoundsError([1, 0, 0]))
+        );
+        assert_eq!(octree.at([0, 1, 0]), None);
+
+        assert_eq!(octree.take([0, 0, 0]), Some(6));
+        assert_eq!(octree.at([0, 0, 0]), None);
+        assert_eq!(octree.volume(), 0);
+
+        octree.fill([0, 0, 0], [0, 0, 0], 3).unwrap();
+        octree.assert_consistent();
+        octree.insert_none([0, 0, 0]);
+        assert_eq!(octree.at([0, 0, 0]), None);
+
+        let mut unsimplified = octree.clone();
+        unsimplified.set_raw([0, 0, 0], 7).unwrap();
+        assert_eq!(unsimplified.iter_morton().collect::<Vec<_>>(), vec![(0, 7)]);
+    }
+
+    #[test]
+    fn test_coarsen_to_budget() {
+        let mut octree = Octree::<u8>::new(32).unwrap();
+        // A solid floor with scattered noise above it, and a lone detailed voxel far away
+        octree.fill([0, 0, 0], [31, 3, 31], 1).unwrap();
+        for i in 0..32u16 {
+            octree.insert([i, 4 + i % 3, (i * 7) % 32], 2).unwrap();
+        }
+        octree.insert([30, 28, 2], 3).unwrap();
+        let detailed = octree.node_count();
+
+        let mut coarse = octree.clone();
+        coarse.coarsen_to_budget(detailed, |values| values.iter().flatten().max().cloned());
+        assert_eq!(coarse, octree);
+
+        coarse.coarsen_to_budget(40, |values| values.iter().flatten().max().cloned());
+        assert!(coarse.node_count() <= 40);
+        coarse.assert_consistent();
+        assert!(coarse.at([5, 1, 17]).is_some());
+        assert_eq!(coarse.at([20, 28, 20]), None);
+        assert!((24..32).any(|y| coarse.at([30, y, 2]) == Some(3)));
+
+        coarse.coarsen_to_budget(1, |values| values.iter().flatten().max().cloned());
+        assert_eq!(coarse.node_count(), 1);
+        assert_eq!(coarse.at([0, 0, 0]), Some(3));
+    }
+
+    #[test]
+    fn test_serialize_against() {
+        let mut base = Octree::<u8>::new(64).unwrap();
+        for i in 0..64u16 {
+            base.insert([i, (i * 5) % 64, (i * 11) % 64], (i % 7) as u8)
+                .unwrap();
+        }
+        base.fill([0, 0, 0], [63, 3, 63], 9).unwrap();
+
+        let mut edited = base.clone();
+        edited.insert([40, 50, 60], 1).unwrap();
+        edited.take([0, 0, 0]);
+        edited.fill([32, 32, 32], [39, 39, 39], 2).unwrap();
+
+        let mut relative = vec![];
+        edited.serialize_against(&base, &mut relative).unwrap();
+        let mut full = vec![];
+        edited.save_to(&mut full).unwrap();
+        assert!(relative.len() * 4 < full.len());
+
+        let loaded = Octree::deserialize_against(&base, &relative[..]).unwrap();
+        assert_eq!(loaded, edited);
+        assert!(loaded.iter_morton().eq(edited.iter_morton()));
+        loaded.assert_consistent();
+
+        let mut unchanged = vec![];
+        base.serialize_against(&base, &mut unchanged).unwrap();
+        assert_eq!(unchanged.len(), 6 + 1 + 1);
+        let empty = Octree::<u8>::new(64).unwrap();
+        let mut from_empty = vec![];
+        edited.serialize_against(&empty, &mut from_empty).unwrap();
+        assert_eq!(
+            Octree::deserialize_against(&empty, &from_empty[..]).unwrap(),
+            edited
+        );
+        assert_eq!(
+            Octree::deserialize_against(&Octree::<u8>::new(32).unwrap(), &relative[..]),
+            Err(OctreeError::DimensionError)
+        );
+    }
+
+    #[test]
+    fn test_extract_octant() {
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        octree.insert([3, 2, 1], 1).unwrap();
+        octree.insert([12, 10, 6], 2).unwrap();
+        octree.fill([8, 0, 8], [15, 7, 15], 3).unwrap();
+
+        let octant = octree.extract_octant(2).unwrap();
+        assert_eq!(octant.dimension(), 8);
+        assert_eq!(octant.at([4, 2, 6]), Some(2));
+        assert_eq!(octant.volume(), 1);
+        octant.assert_consistent();
+
+        assert_eq!(octree.extract_octant(0).unwrap().at([3, 2, 1]), Some(1));
+        let block = octree.extract_octant(5).unwrap();
+        assert_eq!(block.volume(), 8 * 8 * 8);
+        assert_eq!(block.node_count(), 1);
+        assert_eq!(octree.extract_octant(7).unwrap().volume(), 0);
+
+        let mut filled = Octree::<u8>::new_with_default(16, 0).unwrap();
+        filled.fill([0, 0, 0], [15, 15, 15], 4).unwrap();
+        let corner = filled.extract_octant(6).unwrap();
+        assert_eq!(corner.at([7, 7, 7]), Some(4));
+        assert_eq!(corner.volume(), 8 * 8 * 8);
+
+        let mut rect = Octree::<u8>::new_rect([16, 4, 16]).unwrap();
+        rect.insert([9, 3, 0], 5).unwrap();
+        let partial = rect.extract_octant(1).unwrap();
+        assert_eq!(partial.extents(), [8, 4, 8]);
+        assert_eq!(partial.at([1, 3, 0]), Some(5));
+        assert!(!partial.in_bounds([1, 4, 0]));
+        assert_eq!(
+            rect.extract_octant(2).map(|octant| octant.extents()),
+            Err(OctreeError::OutOfBoundsError([8, 8, 0]))
+        );
+        assert!(rect.extract_octant(7).is_err());
+    }
+
+    #[test]
+    fn test_place_octant() {
+        use node::child_origin;
+
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        octree.insert([3, 2, 1], 1).unwrap();
+        octree.insert([12, 10, 6], 2).unwrap();
+        octree.insert([13, 10, 6], 2).unwrap();
+        octree.fill([8, 0, 8], [15, 7, 15], 3).unwrap();
+        let original = octree.clone();
+
+        for octant in 0..8 {
+            let sub = octree.extract_octant(octant).unwrap();
+            let min = child_origin([0, 0, 0], usize::from(octant), 8);
+            let max = [min[0] + 7, min[1] + 7, min[2] + 7];
+            for z in min[2]..=max[2] {
+                for y in min[1]..=max[1] {
+                    for x in min[0]..=max[0] {
+                        octree.take([x, y, z]);
+                    }
+                }
+            }
+            octree.compact();
+            assert_eq!(octree.extract_octant(octant).unwrap().volume(), 0);
+
+            octree.place_octant(octant, &sub).unwrap();
+            assert_eq!(octree, original);
+            assert!(octree.iter_morton().eq(original.iter_morton()));
+        }
+
+        let mut solid = Octree::<u8>::new(16).unwrap();
+        let block = {
+            let mut block = Octree::<u8>::new(8).unwrap();
+            block.fill([0, 0, 0], [7, 7, 7], 5).unwrap();
+            block
+        };
+        for octant in 0..8 {
+            solid.place_octant(octant, &block).unwrap();
+        }
+        assert_eq!(solid.node_count(), 1);
+        assert_eq!(solid.volume(), 16 * 16 * 16);
+
+        assert_eq!(
+            solid.place_octant(0, &Octree::new(4).unwrap()),
+            Err(OctreeError::DimensionError)
+        );
+    }
+
+    #[test]
+    fn test_gradient_at() {
+        let close = |a: [f32; 3], b: [f32; 3]| (0..3).all(|axis| (a[axis] - b[axis]).abs() < 1e-4);
+
+        let mut octree = Octree::<f32>::new(16).unwrap();
+        for z in 0..16 {
+            for y in 0..16 {
+                for x in 0..16 {
+                    octree.insert([x, y, z], 1.0 + 2.5 * f32::from(x)).unwrap();
+                }
+            }
+        }
+
+        assert!(close(
+            octree.gradient_at([7, 3, 9]).unwrap(),
+            [2.5, 0.0, 0.0]
+        ));
+        assert!(close(
+            octree.gradient_at([0, 0, 0]).unwrap(),
+            [2.5, 0.0, 0.0]
+        ));
+        assert!(close(
+            octree.gradient_at([15, 15, 15]).unwrap(),
+            [2.5, 0.0, 0.0]
+        ));
+
+        octree.take([8, 3, 9]);
+        assert_eq!(octree.gradient_at([8, 3, 9]), None);
+        assert!(close(
+            octree.gradient_at([7, 3, 9]).unwrap(),
+            [2.5, 0.0, 0.0]
+        ));
+        assert_eq!(octree.gradient_at([16, 0, 0]), None);
+
+        let mut lone = Octree::<u8>::new(16).unwrap();
+        lone.insert([4, 4, 4], 10).unwrap();
+        assert_eq!(lone.gradient_at([4, 4, 4]), Some([0.0, 0.0, 0.0]));
+    }
+
+    #[test]
+    fn test_insert_default() {
+        let mut octree = Octree::<u8>::new_with_default(16, 0).unwrap();
+        octree.insert([1, 2, 3], 0).unwrap();
+        assert_eq!(octree.at([1, 2, 3]), Some(0));
+        assert_eq!(octree.len(), 0);
+        assert_eq!(octree.node_count(), 1);
+        assert_eq!(octree.iter_morton().count(), 0);
+
+        octree.insert([1, 2, 3], 5).unwrap();
+        assert_eq!(octree.len(), 1);
+        octree.insert([1, 2, 3], 0).unwrap();
+        assert_eq!(octree.len(), 0);
+        assert_eq!(octree.at([1, 2, 3]), Some(0));
+        assert_eq!(octree.insert_if_empty([1, 2, 3], 7), Ok(true));
+        octree.assert_consistent();
+
+        octree.fill([0, 0, 0], [7, 7, 7], 4).unwrap();
+        octree.fill([0, 0, 0], [3, 7, 7], 0).unwrap();
+        assert_eq!(octree.len(), 4 * 8 * 8);
+        assert_eq!(octree.at([2, 2, 2]), Some(0));
+        assert_eq!(octree.at([5, 2, 2]), Some(4));
+        assert_eq!(octree.fill_changed([4, 0, 0], [15, 7, 7], 0), Ok(4 * 8 * 8));
+        assert!(octree.is_empty());
+        assert_eq!(octree.fill_changed([4, 0, 0], [15, 7, 7], 0), Ok(0));
+        octree.assert_consistent();
+
+        octree.set_raw([0, 0, 0], 0).unwrap();
+        assert_eq!(octree.len(), 0);
+        assert_eq!(octree.iter().count(), 0);
+        octree.set_raw([0, 0, 0], 3).unwrap();
+        octree.set_raw([0, 0, 0], 0).unwrap();
+        assert_eq!(octree.len(), 0);
+        assert_eq!(octree.iter().count(), 0);
+        assert_eq!(octree.at([0, 0, 0]), Some(0));
+
+        octree
+            .map_region([0, 0, 0], [1, 0, 0], |_, _| Some(0))
+            .unwrap();
+        assert_eq!(octree.len(), 0);
+        assert_eq!(octree.iter().count(), 0);
+        octree.fill([0, 0, 0], [3, 0, 0], 6).unwrap();
+        octree
+            .map_region([0, 0, 0], [3, 0, 0], |loc, value| {
+                if loc[0] < 2 {
+                    Some(0)
+                } else {
+                    value
+                }
+            })
+            .unwrap();
+        assert_eq!(octree.len(), 2);
+        assert_eq!(octree.iter().count(), 2);
+        assert_eq!(octree.at([1, 0, 0]), Some(0));
+        octree.assert_consistent();
+
+        assert_eq!(
+            octree.insert([16, 0, 0], 0),
+            Err(OctreeError::OutOfBoundsError([16, 0, 0]))
+        );
+    }
+
+    #[test]
+    fn test_insert_iterative() {
+        use node::NodeLoc;
+
+        let equal = |a: u8, b: u8| a == b;
+        let close = |a: u8, b: u8| (i16::from(a) - i16::from(b)).abs() <= 1;
+        let modes: [Option<&dyn Fn(u8, u8) -> bool>; 3] = [None, Some(&equal), Some(&close)];
+        for equivalent in modes.iter() {
+            let mut iterative = OctreeNode::<u8>::construct_root(32);
+            let mut recursive = OctreeNode::<u8>::construct_root(32);
+            let mut seed = 0x2545_f491u32;
+            for i in 0..4000 {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                let loc = (
+                    (seed >> 8) as u16 % 32,
+                    (seed >> 14) as u16 % 4,
+                    (seed >> 20) as u16 % 32,
+                );
+                let data = (seed >> 28) as u8 % 3;
+
+                // Keep an old copy alive now and then, so shared nodes are copied on write
+                let shared = if i % 7 == 0 {
+                    Some(iterative.clone())
+                } else {
+                    None
+                };
+                iterative.insert(&mut NodeLoc::new(loc), data, *equivalent);
+                recursive.insert_recursive(&mut NodeLoc::new(loc), data, *equivalent);
+                assert_eq!(iterative, recursive);
+                drop(shared);
+            }
+        }
+
+        let mut single = OctreeNode::<u8>::construct_root(1);
+        single.insert(&mut NodeLoc::new((0, 0, 0)), 3, Some(&equal));
+        assert_eq!(single.get(), Some(3));
+    }
+
+    #[test]
+    fn test_node_at_path() {
+        use std::ptr;
+
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        octree.insert([12, 10, 6], 255).unwrap();
+        octree.fill([0, 8, 8], [7, 15, 15], 1).unwrap();
+
+        assert!(ptr::eq(octree.node_at_path(&[]).unwrap(), octree.root()));
+        let voxel = octree.node_at_path(&[2, 5, 7, 0]).unwrap();
+        assert!(voxel.leaf());
+        assert_eq!(voxel.dimension(), 1);
+        assert_eq!(voxel.get(), Some(255));
+        assert!(ptr::eq(voxel, octree.node_as_ref([12, 10, 6]).unwrap()));
+
+        let block = octree.node_at_path(&[7]).unwrap();
+        assert!(block.leaf());
+        assert_eq!(block.get(), Some(1));
+        assert!(ptr::eq(block, octree.node_as_ref([3, 12, 9]).unwrap()));
+
+        assert!(octree.node_at_path(&[7, 0]).is_none());
+        assert!(octree.node_at_path(&[0]).is_none());
+        assert!(octree.node_at_path(&[8]).is_none());
+        assert!(octree.node_at_path(&[2, 5, 7, 0, 0]).is_none());
+    }
+
+    #[test]
+    fn test_into_values() {
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        octree.fill([8, 8, 8], [15, 15, 15], 1).unwrap();
+        octree.insert([1, 2, 3], 2).unwrap();
+        octree.insert([12, 10, 6], 3).unwrap();
+        let snapshot = octree.snapshot();
+
+        let values: Vec<_> = octree.clone().into_values().collect();
+        assert_eq!(values, vec![2, 3, 1]);
+        assert_eq!(octree.into_values().count(), 3);
+
+        let mut restored = Octree::<u8>::new(16).unwrap();
+        restored.restore(snapshot);
+        assert_eq!(restored.volume(), 8 * 8 * 8 + 2);
+        assert_eq!(Octree::<u8>::new(16).unwrap().into_values().count(), 0);
+    }
+
+    #[test]
+    fn test_clone_values() {
+        let stone = String::from("stone");
+        let mut octree = Octree::<String>::new(16).unwrap();
+        octree.insert([12, 10, 6], stone.clone()).unwrap();
+        octree.insert([1, 2, 3], String::from("grass")).unwrap();
+
+        let at = |octree: &Octree<String>, loc| {
+            octree
+                .node_as_ref(loc)
+                .and_then(OctreeNode::get_ref)
+                .cloned()
+        };
+        assert_eq!(at(&octree, [12, 10, 6]), Some(stone.clone()));
+        assert_eq!(at(&octree, [12, 10, 7]), None);
+        assert_eq!(octree.take([1, 2, 3]), Some(String::from("grass")));
+        assert_eq!(at(&octree, [1, 2, 3]), None);
+
+        for i in 0..8 {
+            octree
+                .insert([i & 1, (i >> 1) & 1, i >> 2], stone.clone())
+                .unwrap();
+        }
+        let block = octree.node_as_ref([1, 1, 1]).unwrap();
+        assert_eq!(block.dimension(), 2);
+        assert_eq!(block.get_ref(), Some(&stone));
+        octree.insert([1, 1, 1], String::from("ore")).unwrap();
+        assert_eq!(at(&octree, [1, 1, 1]), Some(String::from("ore")));
+        assert_eq!(at(&octree, [0, 1, 1]), Some(stone.clone()));
+
+        let mut with_default = Octree::new_with_default(16, String::new()).unwrap();
+        with_default.insert([4, 4, 4], String::new()).unwrap();
+        assert_eq!(with_default.node_count(), 1);
+        assert_eq!(
+            with_default.clone().into_values().collect::<Vec<_>>(),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            octree.into_values().filter(|value| *value == stone).count(),
+            8
+        );
+    }
+
+    #[test]
+    fn test_at_ref() {
+        use std::ptr;
+
+        #[derive(Debug, Clone, PartialEq)]
+        struct Material {
+            name: String,
+            spectrum: Vec<f32>,
+        }
+
+        let glass = Material {
+            name: String::from("glass"),
+            spectrum: vec![0.9; 64],
+        };
+        let mut octree = Octree::new_with_default(16, glass.clone()).unwrap();
+        let lead = Material {
+            name: String::from("lead"),
+            spectrum: vec![0.1; 64],
+        };
+        octree.insert([12, 10, 6], lead.clone()).unwrap();
+
+        assert_eq!(octree.at_ref([12, 10, 6]), Some(&lead));
+        assert!(ptr::eq(
+            octree.at_ref([12, 10, 6]).unwrap(),
+            octree.at_ref([12, 10, 6]).unwrap()
+        ));
+        assert_eq!(octree.at_ref([0, 0, 0]), Some(&glass));
+        assert_eq!(octree.at_ref([16, 0, 0]), None);
+
+        octree.take([12, 10, 6]);
+        assert_eq!(octree.at_ref([12, 10, 6]), Some(&glass));
+
+        let mut numbers = Octree::<u8>::new(16).unwrap();
+        numbers.insert([1, 2, 3], 7).unwrap();
+        assert_eq!(numbers.at_ref([1, 2, 3]).copied(), numbers.at([1, 2, 3]));
+    }
+
+    #[test]
+    fn test_from_points() {
+        let octree = Octree::<u8>::from_points(vec![([17, 3, 0], 255), ([2, 9, 4], 128)]);
+
+        assert_eq!(octree.dimension(), 32);
+        assert_eq!(octree.len(), 2);
+        assert_eq!(octree.at([17, 3, 0]), Some(255));
+        assert_eq!(octree.at([2, 9, 4]), Some(128));
+
+        assert_eq!(
+            Octree::<u8>::from_points(vec![([15, 0, 0], 1)]).dimension(),
+            16
+        );
+        assert_eq!(
+            Octree::<u8>::from_points(vec![([16, 0, 0], 1)]).dimension(),
+            32
+        );
+
+        let empty = Octree::<u8>::from_points(vec![]);
+        assert_eq!(empty.dimension(), 1);
+        assert_eq!(empty.len(), 0);
+    }
+
+    #[test]
+    fn test_join() {
+        let mut a = Octree::<u8>::new(16).unwrap();
+        let mut b = Octree::<u8>::new(16).unwrap();
+        a.insert([1, 2, 3], 255).unwrap();
+        b.insert([1, 2, 3], 128).unwrap();
+        a.insert([4, 4, 4], 7).unwrap();
+        b.insert([4, 4, 4], 7).unwrap();
+        b.insert([12, 10, 6], 64).unwrap();
+        a.fill([8, 0, 0], [11, 3, 3], 9).unwrap();
+
+        let joined: Vec<_> = a.join(&b).unwrap().collect();
+        assert_eq!(joined.len(), 3 + 64);
+        assert!(joined.contains(&([1, 2, 3], Some(255), Some(128))));
+        assert!(joined.contains(&([4, 4, 4], Some(7), Some(7))));
+        assert!(joined.contains(&([12, 10, 6], None, Some(64))));
+        assert!(joined.contains(&([11, 3, 3], Some(9), None)));
+
+        let mut rect = Octree::<u8>::new_rect([16, 16, 4]).unwrap();
+        rect.fill([0, 0, 0], [15, 15, 3], 1).unwrap();
+        let empty = Octree::<u8>::new_rect([16, 16, 4]).unwrap();
+        assert_eq!(rect.join(&empty).unwrap().count(), 16 * 16 * 4);
+
+        assert!(a.join(&Octree::new(32).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_arg_max() {
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        assert_eq!(octree.arg_max(), None);
+        assert_eq!(octree.arg_min(), None);
+
+        octree.fill([0, 0, 0], [7, 7, 7], 10).unwrap();
+        octree.insert([3, 5, 2], 200).unwrap();
+        octree.insert([12, 10, 6], 250).unwrap();
+        octree.insert([13, 1, 9], 3).unwrap();
+
+        assert_eq!(octree.arg_max(), Some(([12, 10, 6], 250)));
+        assert_eq!(octree.arg_min(), Some(([13, 1, 9], 3)));
+
+        octree.insert([15, 15, 15], 250).unwrap();
+        octree.insert([14, 0, 0], 3).unwrap();
+        assert_eq!(octree.arg_max(), Some(([12, 10, 6], 250)));
+        assert_eq!(octree.arg_min(), Some(([14, 0, 0], 3)));
+
+        let mut uniform = Octree::<u8>::new(16).unwrap();
+        uniform.fill([8, 8, 8], [15, 15, 15], 7).unwrap();
+        assert_eq!(uniform.arg_max(), Some(([8, 8, 8], 7)));
+    }
+
+    #[test]
+    fn test_to_image_stack() {
+        let mut octree = Octree::<u8>::new(16).unwrap();
+        octree.insert([12, 10, 6], 255).unwrap();
+        octree.fill([0, 0, 8], [7, 7, 15], 9).unwrap();
+
+        let stack = octree.to_image_stack(0);
+        assert_eq!(stack.len(), 16);
+        assert!(stack.iter().all(|slice| slice.len() == 16 * 16));
+        assert_eq!(stack[6][10 * 16 + 12], 255);
+        assert_eq!(stack[6].iter().filter(|&&cell| cell != 0).count(), 1);
+        assert_eq!(stack[5][10 * 16 + 12], 0);
+        for slice in &stack[8..] {
+            assert_eq!(slice.iter().filter(|&&cell| cell == 9).count(), 64);
+            assert_eq!(slice[7 * 16 + 7], 9);
+            assert_eq!(slice[7 * 16 + 8], 0);
+        }
+
+        let mut rect = Octree::<u8>::new_rect([16, 8, 4]).unwrap();
+        rect.fill([0, 0, 0], [15, 7, 3], 1).unwrap();
+        let stack = rect.to_image_stack(0);
+        assert_eq!(stack.len(), 4);
+        assert!(stack
+            .iter()
+            .all(|slice| slice.iter().all(|&cell| cell == 1)));
+        assert_eq!(stack[3].len(), 16 * 8);
+    }
+
+    #[test]
+    fn test_bounds_policy() {
+        use octree::BoundsPolicy;
+
+        let mut octree = Octree::<u8>::new_rect([16, 16, 8]).unwrap();
+        octree.insert([3, 4, 5], 7).unwrap();
+        assert_eq!(octree.bounds_policy(), BoundsPolicy::Error);
+        assert!(octree.insert([3, 20, 5], 255).is_err());
+        assert_eq!(octree.at([3, 4, 20]), None);
+        assert_eq!(octree.take([3, 4, 20]), None);
+        assert_eq!(octree.len(), 1);
+
+        octree.set_bounds_policy(BoundsPolicy::Clamp);
+        octree.insert([20, 3, 9], 255).unwrap();
+        assert_eq!(octree.at([15, 3, 7]), Some(255));
+        assert_eq!(octree.at([u16::MAX, 3, 100]), Some(255));
+        assert_eq!(octree.at([3, 4, 5]), Some(7));
+        assert_eq!(octree.take([40, 3, 8]), Some(255));
+        assert_eq!(octree.at([15, 3, 7]), None);
+        assert_eq!(octree.len(), 1);
+
+        octree.set_bounds_policy(BoundsPolicy::Ignore);
+        assert!(octree.insert([3, 4, 200], 255).is_ok());
+        assert_eq!(octree.at([3, 4, 5]), Some(7));
+        assert_eq!(octree.at([3, 4, 200]), None);
+        assert_eq!(octree.take([3, 4, 200]), None);
+        assert_eq!(octree.len(), 1);
+        assert_eq!(octree.insert_if_empty([3, 4, 200], 255), Ok(false));
+        assert_eq!(octree.take_if([3, 4, 200], |_| true), None);
+        assert!(octree.try_at([3, 4, 200]).is_err());
+
+        let mut edge = Octree::<u8>::new(16).unwrap();
+        edge.insert([15, 0, 0], 7).unwrap();
+        for &policy in &[BoundsPolicy::Error, BoundsPolicy::Ignore] {
+            edge.set_bounds_policy(policy);
+            edge.insert_none([16, 0, 0]);
+            assert_eq!(
+                edge.at([15, 0, 0]),
+                Some(7),
+                "Neighbour cleared under {:?}",
+                policy
+            );
+            assert_eq!(edge.len(), 1);
+        }
+        edge.set_bounds_policy(BoundsPolicy::Error);
+        assert!(edge.insert_if_empty([16, 0, 0], 1).is_err());
+
+        edge.set_bounds_policy(BoundsPolicy::Clamp);
+        assert_eq!(edge.insert_if_empty([16, 0, 0], 1), Ok(false));
+        assert_eq!(edge.take_if([16, 0, 0], |value| value == 7), Some(7));
+        assert_eq!(edge.insert_if_empty([16, 0, 0], 1), Ok(true));
+        edge.insert_none([16, 0, 0]);
+        assert_eq!(edge.at([15, 0, 0]), None);
+        assert!(edge.is_empty());
+    }
+
     use node::OctreeNode;
 
     #[test]
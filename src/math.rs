@@ -0,0 +1,102 @@
+/// Intersect a ray with an axis-aligned box using the slab method
+///
+/// Returns the parametric distances `(near, far)` along `dir` at which the ray enters and leaves
+/// the box, or `None` if the ray misses it or the box lies entirely behind the origin. If the
+/// origin is inside the box, `near` is negative.
+///
+/// # Examples
+///
+/// ```
+/// # use octo::math::ray_box_intersection;
+/// #
+/// let hit = ray_box_intersection([-1.0, 0.5, 0.5], [1.0, 0.0, 0.0], [0.0; 3], [1.0; 3]);
+///
+/// assert_eq!(hit, Some((1.0, 2.0)));
+/// ```
+///
+pub fn ray_box_intersection(
+    origin: [f32; 3],
+    dir: [f32; 3],
+    min: [f32; 3],
+    max: [f32; 3],
+) -> Option<(f32, f32)> {
+    let mut near = f32::NEG_INFINITY;
+    let mut far = f32::INFINITY;
+
+    for axis in 0..3 {
+        if dir[axis] == 0.0 {
+            if origin[axis] < min[axis] || origin[axis] > max[axis] {
+                return None;
+            }
+            continue;
+        }
+
+        let t1 = (min[axis] - origin[axis]) / dir[axis];
+        let t2 = (max[axis] - origin[axis]) / dir[axis];
+        near = near.max(t1.min(t2));
+        far = far.min(t1.max(t2));
+    }
+
+    if near > far || far < 0.0 {
+        None
+    } else {
+        Some((near, far))
+    }
+}
+
+/// Interleave the bits of a location into its Morton (Z-order) key
+///
+/// Bit `i` of `x`, `y` and `z` is stored in bit `3i`, `3i + 1` and `3i + 2` of the key
+/// respectively.
+///
+/// # Examples
+///
+/// ```
+/// # use octo::math::morton_encode;
+/// #
+/// assert_eq!(morton_encode([1, 0, 0]), 1);
+/// assert_eq!(morton_encode([0, 0, 1]), 4);
+/// assert_eq!(morton_encode([3, 0, 0]), 9);
+/// ```
+///
+pub fn morton_encode(loc: [u16; 3]) -> u64 {
+    spread_bits(loc[0]) | (spread_bits(loc[1]) << 1) | (spread_bits(loc[2]) << 2)
+}
+
+/// Recover the location encoded in a Morton (Z-order) key
+///
+/// # Examples
+///
+/// ```
+/// # use octo::math::{morton_decode, morton_encode};
+/// #
+/// assert_eq!(morton_decode(morton_encode([12, 10, 6])), [12, 10, 6]);
+/// ```
+///
+pub fn morton_decode(key: u64) -> [u16; 3] {
+    [
+        compact_bits(key),
+        compact_bits(key >> 1),
+        compact_bits(key >> 2),
+    ]
+}
+
+/// Spread the 16 bits of a value so that each is followed by two zero bits
+fn spread_bits(value: u16) -> u64 {
+    let mut x = u64::from(value);
+    x = (x | (x << 16)) & 0x001f_0000_ff00_00ff;
+    x = (x | (x << 8)) & 0x100f_00f0_0f00_f00f;
+    x = (x | (x << 4)) & 0x10c3_0c30_c30c_30c3;
+    x = (x | (x << 2)) & 0x1249_2492_4924_9249;
+    x
+}
+
+/// Gather every third bit of a value, the inverse of `spread_bits`
+fn compact_bits(value: u64) -> u16 {
+    let mut x = value & 0x1249_2492_4924_9249;
+    x = (x | (x >> 2)) & 0x10c3_0c30_c30c_30c3;
+    x = (x | (x >> 4)) & 0x100f_00f0_0f00_f00f;
+    x = (x | (x >> 8)) & 0x001f_0000_ff00_00ff;
+    x = (x | (x >> 16)) & 0xffff;
+    x as u16
+}
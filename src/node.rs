@@ -1,4 +1,12 @@
-use serde::{Serialize, Deserialize};
+use codec::VoxelCodec;
+use octree::CellInfo;
+use serde::de::{self, Deserialize, Deserializer, SeqAccess, Visitor};
+use serde::ser::{Serialize, SerializeSeq, Serializer};
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
+use std::ptr;
+use std::sync::Arc;
+use std::{fmt, mem};
 
 /// NodeLoc structure, representing location within octree
 #[derive(Debug)]
@@ -26,15 +34,15 @@ impl NodeLoc {
     }
 
     pub fn sub_x(&mut self, delta: u16) {
-        self.location[0 as usize] -= delta;
+        self.location[0] -= delta;
     }
 
     pub fn sub_y(&mut self, delta: u16) {
-        self.location[1 as usize] -= delta;
+        self.location[1] -= delta;
     }
 
     pub fn sub_z(&mut self, delta: u16) {
-        self.location[2 as usize] -= delta;
+        self.location[2] -= delta;
     }
 }
 
@@ -52,97 +60,541 @@ enum ChildLoc {
     TopFrontLeft,
 }
 
-/// OctreeNode structure (inaccessible outside module)
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// OctreeNode structure, reachable read-only through `Octree::root` and `Octree::node_as_ref`
+///
+/// Serializes compactly as a sequence holding the dimension of the node, then each node below
+/// it in depth-first order as a tag followed by only the data that tag needs.
+#[derive(Debug, Clone)]
 pub struct OctreeNode<T> {
     dimension: u16,
     leaf: bool,
     simplified: bool,
-    children: Vec<Option<OctreeNode<T>>>,
+    children: Vec<Option<Arc<OctreeNode<T>>>>,
     data: Option<T>,
+    lod: Option<T>,
+    marked: bool,
+}
+
+/// Serialized tag of an octant with no child node
+const TAG_ABSENT: u8 = 0;
+
+/// Serialized tag of an empty leaf
+const TAG_EMPTY: u8 = 1;
+
+/// Serialized tag of an unsimplified leaf, followed by its value
+const TAG_VALUE: u8 = 2;
+
+/// Serialized tag of a simplified leaf, followed by its value
+const TAG_BLOCK: u8 = 3;
+
+/// Serialized tag of an internal node, followed by its eight children
+const TAG_INTERNAL: u8 = 4;
+
+/// Serialized tag of an internal node with a level of detail value, followed by the value and
+/// its eight children
+const TAG_INTERNAL_LOD: u8 = 5;
+
+/// Binary tag of a subtree equal to the node at the same position of a base tree, written by
+/// `OctreeNode::write_against`
+const TAG_SAME: u8 = 6;
+
+impl<T> Serialize for OctreeNode<T>
+where
+    T: Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(1 + self.serialized_len()))?;
+        seq.serialize_element(&self.dimension)?;
+        self.serialize_into(&mut seq)?;
+        seq.end()
+    }
 }
 
 impl<T> OctreeNode<T>
 where
-    T: Copy + PartialEq,
+    T: Serialize,
+{
+    // Get the number of serialized elements for this node and every node below it
+    fn serialized_len(&self) -> usize {
+        match (self.leaf, &self.data, &self.lod) {
+            (true, None, _) => 1,
+            (true, Some(_), _) => 2,
+            (false, _, lod) => {
+                let children: usize = self
+                    .children
+                    .iter()
+                    .map(|child| child.as_ref().map_or(1, |node| node.serialized_len()))
+                    .sum();
+                1 + usize::from(lod.is_some()) + children
+            }
+        }
+    }
+
+    // Serialize this node and every node below it, in depth-first order
+    fn serialize_into<S: SerializeSeq>(&self, seq: &mut S) -> Result<(), S::Error> {
+        if self.leaf {
+            match self.data {
+                None => seq.serialize_element(&TAG_EMPTY),
+                Some(ref data) => {
+                    let tag = if self.simplified {
+                        TAG_BLOCK
+                    } else {
+                        TAG_VALUE
+                    };
+                    seq.serialize_element(&tag)?;
+                    seq.serialize_element(data)
+                }
+            }
+        } else {
+            match self.lod {
+                None => seq.serialize_element(&TAG_INTERNAL)?,
+                Some(ref lod) => {
+                    seq.serialize_element(&TAG_INTERNAL_LOD)?;
+                    seq.serialize_element(lod)?;
+                }
+            }
+            for child in &self.children {
+                match child {
+                    None => seq.serialize_element(&TAG_ABSENT)?,
+                    Some(node) => node.serialize_into(seq)?,
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+impl<'de, T> Deserialize<'de> for OctreeNode<T>
+where
+    T: Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<OctreeNode<T>, D::Error> {
+        deserializer.deserialize_seq(NodeVisitor(PhantomData))
+    }
+}
+
+/// Visitor rebuilding an `OctreeNode<T>` from its compact serialized form
+struct NodeVisitor<T>(PhantomData<T>);
+
+impl<'de, T> Visitor<'de> for NodeVisitor<T>
+where
+    T: Deserialize<'de>,
+{
+    type Value = OctreeNode<T>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a dimension followed by tagged octree nodes")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<OctreeNode<T>, A::Error> {
+        let dimension: u16 = next(&mut seq)?;
+        match read_node(&mut seq, dimension)? {
+            Some(node) => Ok(node),
+            None => Err(de::Error::custom("the root node is absent")),
+        }
+    }
+}
+
+// Helper function that reads the next element of a serialized node sequence
+fn next<'de, A: SeqAccess<'de>, E: Deserialize<'de>>(seq: &mut A) -> Result<E, A::Error> {
+    seq.next_element()?
+        .ok_or_else(|| de::Error::custom("octree node data ended early"))
+}
+
+// Helper function that reads a serialized node of a given dimension and every node below it,
+// returning `None` for an absent octant
+fn read_node<'de, A, T>(seq: &mut A, dimension: u16) -> Result<Option<OctreeNode<T>>, A::Error>
+where
+    A: SeqAccess<'de>,
+    T: Deserialize<'de>,
+{
+    let mut node = OctreeNode {
+        dimension,
+        leaf: true,
+        simplified: false,
+        children: no_children(),
+        data: None,
+        lod: None,
+        marked: false,
+    };
+
+    match next::<A, u8>(seq)? {
+        TAG_ABSENT => return Ok(None),
+        TAG_EMPTY => {}
+        TAG_VALUE => node.data = Some(next(seq)?),
+        TAG_BLOCK => {
+            node.data = Some(next(seq)?);
+            node.simplified = true;
+        }
+        tag @ (TAG_INTERNAL | TAG_INTERNAL_LOD) => {
+            if dimension < 2 {
+                return Err(de::Error::custom("a single voxel node has children"));
+            }
+            if tag == TAG_INTERNAL_LOD {
+                node.lod = Some(next(seq)?);
+            }
+            node.leaf = false;
+            for child in node.children.iter_mut() {
+                *child = read_node(seq, dimension / 2)?.map(Arc::new);
+            }
+        }
+        tag => {
+            return Err(de::Error::custom(format!(
+                "unknown octree node tag {}",
+                tag
+            )))
+        }
+    }
+    Ok(Some(node))
+}
+
+impl<T> OctreeNode<T>
+where
+    T: PartialEq + VoxelCodec,
+{
+    // Get the number of tagged nodes written by `write_binary`, counting absent octants
+    pub(crate) fn binary_len(&self) -> u64 {
+        if self.leaf {
+            return 1;
+        }
+
+        let children: u64 = self
+            .children
+            .iter()
+            .map(|child| child.as_ref().map_or(1, |node| node.binary_len()))
+            .sum();
+        1 + children
+    }
+
+    // Write this node and every node below it in depth-first order, using the same tags as the
+    // serialized form
+    pub(crate) fn write_binary<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        if self.leaf {
+            return match self.data {
+                None => TAG_EMPTY.write_to(writer),
+                Some(ref data) => {
+                    let tag = if self.simplified {
+                        TAG_BLOCK
+                    } else {
+                        TAG_VALUE
+                    };
+                    tag.write_to(writer)?;
+                    data.write_to(writer)
+                }
+            };
+        }
+
+        match self.lod {
+            None => TAG_INTERNAL.write_to(writer)?,
+            Some(ref lod) => {
+                TAG_INTERNAL_LOD.write_to(writer)?;
+                lod.write_to(writer)?;
+            }
+        }
+        for child in &self.children {
+            match child {
+                None => TAG_ABSENT.write_to(writer)?,
+                Some(node) => node.write_binary(writer)?,
+            }
+        }
+        Ok(())
+    }
+
+    // Write this node as `write_binary` does, except that any subtree equal to the node at the
+    // same position of a base tree is written as a single tag
+    pub(crate) fn write_against<W: Write>(
+        &self,
+        base: Option<&OctreeNode<T>>,
+        writer: &mut W,
+    ) -> io::Result<()> {
+        let base = match base {
+            Some(base) if ptr::eq(base, self) || base == self => return TAG_SAME.write_to(writer),
+            Some(base) if !self.leaf && !base.leaf => base,
+            _ => return self.write_binary(writer),
+        };
+
+        match self.lod {
+            None => TAG_INTERNAL.write_to(writer)?,
+            Some(ref lod) => {
+                TAG_INTERNAL_LOD.write_to(writer)?;
+                lod.write_to(writer)?;
+            }
+        }
+        for (child, base_child) in self.children.iter().zip(&base.children) {
+            match child {
+                None => TAG_ABSENT.write_to(writer)?,
+                Some(node) => node.write_against(base_child.as_deref(), writer)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+// Helper function that reads a node of a given dimension written by `write_binary` or
+// `write_against` and every node below it, returning `None` for an absent octant and counting
+// each tagged node read. `base` is the node at the same position of the base tree, if any.
+pub fn read_binary<R: Read, T: VoxelCodec>(
+    reader: &mut R,
+    dimension: u16,
+    base: Option<&Arc<OctreeNode<T>>>,
+    count: &mut u64,
+) -> io::Result<Option<Arc<OctreeNode<T>>>> {
+    let mut node = OctreeNode {
+        dimension,
+        leaf: true,
+        simplified: false,
+        children: no_children(),
+        data: None,
+        lod: None,
+        marked: false,
+    };
+
+    *count += 1;
+    match u8::read_from(reader)? {
+        TAG_ABSENT => return Ok(None),
+        TAG_EMPTY => {}
+        TAG_VALUE => node.data = Some(T::read_from(reader)?),
+        TAG_BLOCK => {
+            node.data = Some(T::read_from(reader)?);
+            node.simplified = true;
+        }
+        tag @ (TAG_INTERNAL | TAG_INTERNAL_LOD) if dimension > 1 => {
+            if tag == TAG_INTERNAL_LOD {
+                node.lod = Some(T::read_from(reader)?);
+            }
+            node.leaf = false;
+            for (i, child) in node.children.iter_mut().enumerate() {
+                let base_child = base.and_then(|base| base.children[i].as_ref());
+                *child = read_binary(reader, dimension / 2, base_child, count)?;
+            }
+        }
+        TAG_SAME if base.is_some() => return Ok(base.cloned()),
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "invalid node tag",
+            ))
+        }
+    }
+    Ok(Some(Arc::new(node)))
+}
+
+impl<T> OctreeNode<T>
+where
+    T: Clone,
+{
+    // Move the data of every occupied leaf below this node into `values` in depth-first order,
+    // copying only the nodes still shared with another tree
+    pub(crate) fn into_values(self, values: &mut Vec<T>) {
+        if self.leaf {
+            values.extend(self.data);
+            return;
+        }
+
+        for child in self.children.into_iter().flatten() {
+            unwrap_shared(child).into_values(values);
+        }
+    }
+}
+
+/// Equality ignores the scratch `marked` flag
+impl<T> PartialEq for OctreeNode<T>
+where
+    T: PartialEq,
+{
+    fn eq(&self, other: &OctreeNode<T>) -> bool {
+        self.dimension == other.dimension
+            && self.leaf == other.leaf
+            && self.simplified == other.simplified
+            && self.children == other.children
+            && self.data == other.data
+            && self.lod == other.lod
+    }
+}
+
+impl<T> OctreeNode<T>
+where
+    T: Clone + PartialEq,
 {
     /// Constructs a new `OctreeNode<T>`.
-    pub fn new(curr_dimension: u16, data: T) -> OctreeNode<T> {
+    pub(crate) fn new(curr_dimension: u16, data: T) -> OctreeNode<T> {
         OctreeNode::<T> {
             dimension: curr_dimension / 2,
             leaf: true,
             simplified: false,
             children: no_children::<T>(),
             data: Some(data),
+            lod: None,
+            marked: false,
         }
     }
 
     /// Constructs a root `OctreeNode<T>` to be used in an `Octree<T>` structure
-    pub fn construct_root(dimension: u16) -> OctreeNode<T> {
+    pub(crate) fn construct_root(dimension: u16) -> OctreeNode<T> {
         OctreeNode {
             dimension,
             leaf: true,
             simplified: false,
             children: no_children::<T>(),
             data: None,
+            lod: None,
+            marked: false,
         }
     }
 
-    /// Sets node `data` field
-    pub fn set(&mut self, data: T) -> Result<(), String> {
-        if self.leaf {
-            self.data = Some(data);
-            Ok(())
-        } else {
-            Err("Could not set octree node data: node is not a leaf".to_string())
-        }
+    /// Constructs a leaf `OctreeNode<T>` covering its whole volume with a single value
+    pub(crate) fn new_block(dimension: u16, data: T) -> OctreeNode<T> {
+        let mut node = OctreeNode::construct_root(dimension);
+        node.set_block(Some(data));
+        node
     }
 
-    /// Get node `data` field
-    pub fn get(&self) -> Option<T> {
-        self.data
+    /// Get a reference to node `data` field
+    pub fn get_ref(&self) -> Option<&T> {
+        self.data.as_ref()
+    }
+
+    // Get a reference to the data of an `OctreeNode<T>` at a given `NodeLoc`, without copying
+    // it out
+    pub(crate) fn at_ref(&self, loc: &mut NodeLoc) -> Option<&T> {
+        let mut node = self;
+        while !node.leaf {
+            let child_loc = node.get_child_loc(loc) as usize;
+            node = node.children[child_loc].as_deref()?;
+        }
+        node.data.as_ref()
     }
 
     /// Get node children
     pub fn children(&self) -> Vec<Option<OctreeNode<T>>> {
-        self.children.clone()
+        self.children
+            .iter()
+            .map(|child| child.as_ref().map(|child_node| (**child_node).clone()))
+            .collect()
+    }
+
+    /// Get shared references to the children of the node, one for each octant in ascending
+    /// octant order, with `None` for an empty octant
+    pub fn children_ref(&self) -> impl Iterator<Item = Option<&OctreeNode<T>>> + '_ {
+        self.children.iter().map(Option::as_deref)
+    }
+
+    /// Get shared handles to the existing children of the node, in ascending octant order
+    pub(crate) fn shared_children(
+        &self,
+    ) -> impl DoubleEndedIterator<Item = Arc<OctreeNode<T>>> + '_ {
+        self.children.iter().flatten().cloned()
     }
 
     /// Algorithm to insert a new `OctreeNode<T>` into the tree
-    pub fn insert(&mut self, loc: &mut NodeLoc, data: T) {
-        let child_loc = self.get_child_loc(loc);
-        let mut node = if self.children[child_loc as usize].is_some() && !self.simplified {
-            self.children[child_loc as usize].take().unwrap()
-        } else {
-            OctreeNode::<T>::new(self.dimension, data)
-        };
+    ///
+    /// `equivalent` decides when values are close enough to simplify into a single node, and
+    /// simplification is disabled entirely if it is `None`. Writing into a block splits it into
+    /// eight blocks of the same value, down to the written voxel, so every other location of
+    /// the block keeps its value. Writing a value equivalent to the block's own leaves the block
+    /// unchanged.
+    pub(crate) fn insert(
+        &mut self,
+        loc: &mut NodeLoc,
+        data: T,
+        equivalent: Option<&dyn Fn(T, T) -> bool>,
+    ) {
+        // Detach each node on the path to `loc` from its parent while descending, so they can
+        // be edited in place without recursing, then reattach them on the way back up
+        let mut node = mem::replace(
+            self,
+            OctreeNode {
+                dimension: self.dimension,
+                leaf: true,
+                simplified: false,
+                children: Vec::new(),
+                data: None,
+                lod: None,
+                marked: false,
+            },
+        );
+        let mut parents = Vec::with_capacity(node.dimension.trailing_zeros() as usize);
+
+        loop {
+            if node.dimension == 1 {
+                node.set_block(Some(data.clone()));
+                break;
+            }
+            if node.leaf {
+                match (node.data.as_ref(), equivalent) {
+                    (Some(node_data), Some(equivalent))
+                        if equivalent(node_data.clone(), data.clone()) =>
+                    {
+                        break
+                    }
+                    (Some(_), _) => node.split(),
+                    (None, _) => node.make_leaf(false),
+                }
+            }
 
-        if self.leaf && !self.simplified {
-            self.make_leaf(false);
-            self.data = None;
+            let child_loc = node.get_child_loc(loc) as usize;
+            let child = match node.children[child_loc].take() {
+                Some(child) => unwrap_shared(child),
+                None => OctreeNode::construct_root(node.dimension / 2),
+            };
+            parents.push((node, child_loc));
+            node = child;
         }
 
-        if self.dimension == 2 {
-            node.make_leaf(true);
-        } else {
-            node.insert(loc, data);
+        while let Some((mut parent, child_loc)) = parents.pop() {
+            parent.children[child_loc] = Some(Arc::new(node));
+            if let Some(equivalent) = equivalent {
+                parent.try_simplify(&data, equivalent);
+            }
+            node = parent;
         }
+        *self = node;
+    }
 
-        if self.simplified && self.data != Some(data) {
-            self.try_desimplify(&node, child_loc);
-        } else {
-            self.children[child_loc as usize] = Some(node.clone());
+    // Recursive form of `insert`, kept to check the iterative form against
+    #[cfg(test)]
+    pub(crate) fn insert_recursive(
+        &mut self,
+        loc: &mut NodeLoc,
+        data: T,
+        equivalent: Option<&dyn Fn(T, T) -> bool>,
+    ) {
+        if self.dimension == 1 {
+            self.set_block(Some(data));
+            return;
+        }
+
+        if self.leaf {
+            match (self.data.as_ref(), equivalent) {
+                (Some(node_data), Some(equivalent))
+                    if equivalent(node_data.clone(), data.clone()) =>
+                {
+                    return
+                }
+                (Some(_), _) => self.split(),
+                (None, _) => self.make_leaf(false),
+            }
         }
 
-        self.try_simplify(data);
+        let child_loc = self.get_child_loc(loc) as usize;
+        let mut node = match self.children[child_loc].take() {
+            Some(child) => unwrap_shared(child),
+            None => OctreeNode::construct_root(self.dimension / 2),
+        };
+        node.insert_recursive(loc, data.clone(), equivalent);
+        self.children[child_loc] = Some(Arc::new(node));
+
+        if let Some(equivalent) = equivalent {
+            self.try_simplify(&data, equivalent);
+        }
     }
 
-    // Simplify the current node if all children have the same value
-    fn try_simplify(&mut self, data: T) {
+    // Simplify the current node if all children have equivalent values
+    fn try_simplify(&mut self, data: &T, equivalent: &dyn Fn(T, T) -> bool) {
         for child in &self.children {
             if let Some(child_node) = child {
-                if let Some(node_data) = child_node.get() {
-                    if node_data != data {
+                if let Some(node_data) = child_node.data.as_ref() {
+                    if !equivalent(node_data.clone(), data.clone()) {
                         return;
                     }
                 } else {
@@ -153,100 +605,198 @@ where
             };
         }
 
-        self.data = Some(data);
+        self.data = Some(data.clone());
         self.make_leaf(true);
         self.simplified = true;
     }
 
-    // Attempt to insert node at base level to simplified node
-    fn try_desimplify(&mut self, node: &OctreeNode<T>, child_loc: ChildLoc) {
-        for i in 0..self.children.len() {
-            if i as usize != child_loc as usize {
-                self.children[i as usize] =
-                    Some(OctreeNode::<T>::new(self.dimension, self.data.unwrap()));
-            }
+    // Turn this node into a leaf covering its whole volume with a single value
+    fn set_block(&mut self, data: Option<T>) {
+        self.make_leaf(true);
+        self.simplified = data.is_some() && self.dimension > 1;
+        self.data = data;
+    }
+
+    // Expand a leaf node into eight children holding the same value
+    fn split(&mut self) {
+        let half = self.dimension / 2;
+        let data = self.data.take();
+
+        for child in self.children.iter_mut() {
+            *child = data.as_ref().map(|data| {
+                let mut node = OctreeNode::construct_root(half);
+                node.set_block(Some(data.clone()));
+                Arc::new(node)
+            });
         }
 
-        self.children[child_loc as usize] = Some(node.clone());
         self.leaf = false;
         self.simplified = false;
-        self.data = None;
     }
 
-    // Get data of an `OctreeNode<T>` at a given `NodeLoc`
-    pub fn at(&self, loc: &mut NodeLoc) -> Option<T> {
-        let child_loc = self.get_child_loc(loc);
-        let child = &self.children[child_loc as usize];
-
-        if child.is_none() {
-            None
-        } else if child.as_ref().unwrap().leaf {
-            child.as_ref().unwrap().data
-        } else {
-            child.as_ref().unwrap().at(loc)
+    // Get data of an `OctreeNode<T>` at a given `NodeLoc`, and replace it with `None`
+    pub(crate) fn take(&mut self, loc: &mut NodeLoc) -> Option<T> {
+        if self.leaf {
+            if self.dimension == 1 || self.data.is_none() {
+                return self.data.take();
+            }
+            self.split();
         }
-    }
 
-    // Get data of an `OctreeNode<T>` at a given `NodeLoc`, and replace it with `None`
-    pub fn take(&mut self, loc: &mut NodeLoc) -> Option<T> {
         let child_loc = self.get_child_loc(loc);
-        let child = &mut self.children[child_loc as usize];
-
-        if child.is_none() {
-            None
-        } else if child.as_ref().unwrap().leaf {
-            child.as_mut().unwrap().data.take()
-        } else {
-            child.as_mut().unwrap().take(loc)
+        match self.children[child_loc as usize].as_mut() {
+            Some(child) => Arc::make_mut(child).take(loc),
+            None => None,
         }
     }
 
     // Insert `None` into the data field of an `OctreeNode<T>`
-    pub fn insert_none(&mut self, loc: &mut NodeLoc) {
-        self.take(loc);
+    pub(crate) fn insert_none(&mut self, loc: &mut NodeLoc) {
+        if self.leaf {
+            if self.dimension == 1 || self.data.is_none() {
+                self.data = None;
+                return;
+            }
+            self.split();
+        }
+
+        let child_loc = self.get_child_loc(loc);
+        if let Some(child) = self.children[child_loc as usize].as_mut() {
+            Arc::make_mut(child).insert_none(loc);
+        }
+
         self.try_simplify_none();
     }
 
-    // Remove leaf nodes from branch if all leaves contain None
+    // Remove leaf nodes from branch if all children are empty leaves
     fn try_simplify_none(&mut self) {
-        for child in &self.children {
-            if let Some(child_node) = child {
-                if child_node.data.is_some() {
-                    return;
-                }
+        for child_node in self.children.iter().flatten() {
+            if !child_node.leaf || child_node.data.is_some() {
+                return;
             }
         }
 
         self.data = None;
         self.make_leaf(true);
-        self.simplified = true;
-        self.children = no_children();
+        self.simplified = false;
     }
 
     // Get a shared reference to a given `OctreeNode<T>`
-    pub fn node_as_ref(&self, loc: &mut NodeLoc) -> Option<&OctreeNode<T>> {
+    pub(crate) fn node_as_ref(&self, loc: &mut NodeLoc) -> Option<&OctreeNode<T>> {
         let child_loc = self.get_child_loc(loc);
         let child = &self.children[child_loc as usize];
 
         if child.is_none() {
             None
         } else if child.as_ref().unwrap().leaf {
-            child.as_ref()
+            child.as_deref()
         } else {
             child.as_ref().unwrap().node_as_ref(loc)
         }
     }
 
+    // Find the deepest node on the path to `loc` that is at least `side` wide, along with its
+    // origin, stopping early at a leaf or an empty octant
+    pub(crate) fn descend(
+        node: &Arc<OctreeNode<T>>,
+        loc: [u16; 3],
+        side: u16,
+    ) -> ([u16; 3], Arc<OctreeNode<T>>) {
+        let mut node = node;
+        let mut origin = [0, 0, 0];
+        while !node.leaf && node.dimension / 2 >= side {
+            let half = node.dimension / 2;
+            let index = child_index(origin, half, loc);
+            match &node.children[index] {
+                Some(child) => {
+                    origin = child_origin(origin, index, half);
+                    node = child;
+                }
+                None => break,
+            }
+        }
+        (origin, Arc::clone(node))
+    }
+
+    // Get the node `dimension` wide at `origin` for editing in place, cloning any shared nodes
+    // above it. Returns `None` if the path to it ends early at a leaf or an empty octant
+    pub(crate) fn descend_mut(
+        node: &mut Arc<OctreeNode<T>>,
+        origin: [u16; 3],
+        dimension: u16,
+    ) -> Option<&mut Arc<OctreeNode<T>>> {
+        let mut node = node;
+        let mut node_origin = [0, 0, 0];
+        while node.dimension > dimension {
+            let parent = Arc::make_mut(node);
+            if parent.leaf {
+                return None;
+            }
+            let half = parent.dimension / 2;
+            let index = child_index(node_origin, half, origin);
+            node_origin = child_origin(node_origin, index, half);
+            node = parent.children[index].as_mut()?;
+        }
+        Some(node)
+    }
+
+    // Get a mutable reference to the deepest `OctreeNode<T>` covering a given `NodeLoc`,
+    // cloning any shared nodes along the way
+    pub(crate) fn node_as_mut(&mut self, loc: &mut NodeLoc) -> Option<&mut OctreeNode<T>> {
+        if self.leaf {
+            return Some(self);
+        }
+
+        let child_loc = self.get_child_loc(loc);
+        match self.children[child_loc as usize].as_mut() {
+            Some(child) => Arc::make_mut(child).node_as_mut(loc),
+            None => None,
+        }
+    }
+
+    // Set or clear the scratch flag of this node
+    pub(crate) fn set_marked(&mut self, marked: bool) {
+        self.marked = marked;
+    }
+
+    // Test the scratch flag of this node
+    pub(crate) fn marked(&self) -> bool {
+        self.marked
+    }
+
+    // Get a shared reference to the child at a given index, if it exists
+    pub(crate) fn child(&self, index: usize) -> Option<&OctreeNode<T>> {
+        self.children[index].as_deref()
+    }
+
+    // Get a shared handle to the child at a given index, if it exists
+    pub(crate) fn shared_child(&self, index: usize) -> Option<Arc<OctreeNode<T>>> {
+        self.children[index].clone()
+    }
+
+    // Count this node and all of its descendants
+    pub(crate) fn node_count(&self) -> usize {
+        1 + self
+            .children
+            .iter()
+            .flatten()
+            .map(|child| child.node_count())
+            .sum::<usize>()
+    }
+
+    /// Returns `true` if the node has no children, covering its whole volume with its data
     pub fn leaf(&self) -> bool {
         self.leaf
     }
 
+    /// Returns the edge length of the cube covered by the node
     pub fn dimension(&self) -> u16 {
         self.dimension
     }
 
     // Get correct insertion location of child node on insertion
     fn get_child_loc(&self, loc: &mut NodeLoc) -> ChildLoc {
+        debug_assert!(self.dimension > 1, "Single voxel nodes have no children");
         let comparator = self.dimension / 2;
 
         if loc.z() < comparator {
@@ -297,7 +847,1086 @@ where
     }
 }
 
-// Helper function that returns an empty `OctreeNode<T>` child vector
-fn no_children<T>() -> Vec<Option<OctreeNode<T>>> {
-    vec![None, None, None, None, None, None, None, None]
+impl<T> OctreeNode<T>
+where
+    T: Copy + PartialEq,
+{
+    /// Constructs an internal `OctreeNode<T>` from a complete set of children, merging them
+    /// into a single leaf if they are all equivalent leaves
+    pub(crate) fn from_children(
+        dimension: u16,
+        children: Vec<Option<Arc<OctreeNode<T>>>>,
+        equivalent: Option<&dyn Fn(T, T) -> bool>,
+    ) -> OctreeNode<T> {
+        let mut node = OctreeNode::<T> {
+            dimension,
+            leaf: false,
+            simplified: false,
+            children,
+            data: None,
+            lod: None,
+            marked: false,
+        };
+
+        let first = node.children[0].as_ref().and_then(|child| child.data);
+        if let (Some(data), Some(equivalent)) = (first, equivalent) {
+            node.try_merge(data, equivalent);
+        }
+        node
+    }
+
+    /// Get node `data` field
+    pub fn get(&self) -> Option<T> {
+        self.data
+    }
+
+    // Set every location of this node within the inclusive box `min`..=`max` to `data`
+    pub(crate) fn fill(
+        &mut self,
+        origin: [u16; 3],
+        min: [u16; 3],
+        max: [u16; 3],
+        data: T,
+        equivalent: &dyn Fn(T, T) -> bool,
+    ) {
+        let last = |axis: usize| origin[axis] + (self.dimension - 1);
+        if (0..3).any(|axis| max[axis] < origin[axis] || min[axis] > last(axis)) {
+            return;
+        }
+
+        if (0..3).all(|axis| min[axis] <= origin[axis] && max[axis] >= last(axis)) {
+            self.set_block(Some(data));
+            return;
+        }
+
+        if self.leaf {
+            if let Some(node_data) = self.data {
+                if equivalent(node_data, data) {
+                    return;
+                }
+            }
+            self.split();
+        }
+
+        let half = self.dimension / 2;
+        for (i, child) in self.children.iter_mut().enumerate() {
+            let child_origin = child_origin(origin, i, half);
+            let overlaps = (0..3).all(|axis| {
+                max[axis] >= child_origin[axis] && min[axis] < child_origin[axis] + half
+            });
+
+            if overlaps {
+                let child_node =
+                    child.get_or_insert_with(|| Arc::new(OctreeNode::construct_root(half)));
+                Arc::make_mut(child_node).fill(child_origin, min, max, data, equivalent);
+            }
+        }
+
+        self.try_merge(data, equivalent);
+    }
+
+    // Replace every occupied location holding `from` with `to`, returning the number changed
+    pub(crate) fn replace(
+        &mut self,
+        from: T,
+        to: T,
+        equivalent: Option<&dyn Fn(T, T) -> bool>,
+    ) -> usize {
+        if self.leaf {
+            if self.data != Some(from) {
+                return 0;
+            }
+            self.data = Some(to);
+            return usize::from(self.dimension).pow(3);
+        }
+
+        let mut replaced = 0;
+        for child in self.children.iter_mut().flatten() {
+            if child.any_leaf(&mut |data| data == from) {
+                replaced += Arc::make_mut(child).replace(from, to, equivalent);
+            }
+        }
+
+        if let Some(equivalent) = equivalent {
+            if replaced > 0 {
+                self.try_merge(to, equivalent);
+            }
+        }
+        replaced
+    }
+
+    // Replace the value of every location of this node within the inclusive box `min`..=`max`
+    // with `f` of its current value, then merge the affected children. Blocks overlapping the
+    // box are split, and empty octants overlapping it are allocated and pruned again if they
+    // stay empty.
+    pub(crate) fn map_region<F: FnMut([u16; 3], Option<T>) -> Option<T>>(
+        &mut self,
+        origin: [u16; 3],
+        min: [u16; 3],
+        max: [u16; 3],
+        f: &mut F,
+        equivalent: Option<&dyn Fn(T, T) -> bool>,
+    ) {
+        if self.dimension == 1 {
+            self.data = f(origin, self.data);
+            return;
+        }
+        if self.leaf {
+            self.split();
+        }
+
+        let half = self.dimension / 2;
+        for i in 0..8 {
+            let child_origin = child_origin(origin, i, half);
+            let overlaps = (0..3).all(|axis| {
+                max[axis] >= child_origin[axis] && min[axis] < child_origin[axis] + half
+            });
+            if !overlaps {
+                continue;
+            }
+
+            let child =
+                self.children[i].get_or_insert_with(|| Arc::new(OctreeNode::construct_root(half)));
+            Arc::make_mut(child).map_region(child_origin, min, max, f, equivalent);
+            if child.leaf && child.data.is_none() {
+                self.children[i] = None;
+            }
+        }
+
+        let first = self.children[0]
+            .as_ref()
+            .and_then(|child| if child.leaf { child.data } else { None });
+        match (first, equivalent) {
+            (Some(data), Some(equivalent)) => self.try_merge(data, equivalent),
+            _ => self.try_simplify_none(),
+        }
+    }
+
+    // Merge every mergeable region below this node, bottom-up, as if each value had been
+    // inserted with simplification enabled
+    pub(crate) fn resimplify(&mut self, equivalent: &dyn Fn(T, T) -> bool) {
+        if self.leaf {
+            return;
+        }
+
+        for child in self.children.iter_mut().flatten() {
+            Arc::make_mut(child).resimplify(equivalent);
+        }
+
+        let first = self.children[0]
+            .as_ref()
+            .and_then(|child| if child.leaf { child.data } else { None });
+        match first {
+            Some(data) => self.try_merge(data, equivalent),
+            None => self.try_simplify_none(),
+        }
+    }
+
+    // Merge children into this node if they are all empty, or all leaves equivalent to `data`
+    fn try_merge(&mut self, data: T, equivalent: &dyn Fn(T, T) -> bool) {
+        let all_empty = self
+            .children
+            .iter()
+            .all(|child| child.as_ref().is_none_or(|c| c.leaf && c.data.is_none()));
+        if all_empty {
+            self.set_block(None);
+            return;
+        }
+
+        let all_equivalent = self.children.iter().all(|child| match child {
+            Some(c) => c.leaf && c.data.is_some_and(|d| equivalent(d, data)),
+            None => false,
+        });
+        if all_equivalent {
+            self.set_block(Some(data));
+        }
+    }
+
+    // Get data of an `OctreeNode<T>` at a given `NodeLoc`
+    pub(crate) fn at(&self, loc: &mut NodeLoc) -> Option<T> {
+        if self.leaf {
+            return self.data;
+        }
+
+        let child_loc = self.get_child_loc(loc);
+        let child = &self.children[child_loc as usize];
+
+        if child.is_none() {
+            None
+        } else if child.as_ref().unwrap().leaf {
+            child.as_ref().unwrap().data
+        } else {
+            child.as_ref().unwrap().at(loc)
+        }
+    }
+
+    // Combine two nodes covering the same volume voxel by voxel, descending both in lockstep
+    // and treating leaves as uniform over their whole volume. `f` is only called on pairs of
+    // values, each `None` where that side is empty, and returns `None` to leave a region empty.
+    pub(crate) fn combine<F>(
+        a: Option<&Arc<OctreeNode<T>>>,
+        b: Option<&Arc<OctreeNode<T>>>,
+        dimension: u16,
+        f: &F,
+        equivalent: &dyn Fn(T, T) -> bool,
+    ) -> Option<Arc<OctreeNode<T>>>
+    where
+        F: Fn(Option<T>, Option<T>) -> Option<T>,
+    {
+        let uniform = |side: Option<&Arc<OctreeNode<T>>>| match side {
+            Some(node) if !node.leaf => None,
+            Some(node) => Some(node.data),
+            None => Some(None),
+        };
+        if let (Some(x), Some(y)) = (uniform(a), uniform(b)) {
+            return f(x, y).map(|data| Arc::new(OctreeNode::new_block(dimension, data)));
+        }
+
+        let half = dimension / 2;
+        let children: Vec<Option<Arc<OctreeNode<T>>>> = (0..8)
+            .map(|i| OctreeNode::combine(child_side(a, i), child_side(b, i), half, f, equivalent))
+            .collect();
+        if children.iter().all(Option::is_none) {
+            return None;
+        }
+        Some(Arc::new(OctreeNode::from_children(
+            dimension,
+            children,
+            Some(equivalent),
+        )))
+    }
+
+    // Visit every location whose occupancy differs between two nodes covering the same cube,
+    // descending both in lockstep and skipping subtrees they share
+    pub(crate) fn for_each_occupancy_difference<F: FnMut([u16; 3])>(
+        a: Option<&Arc<OctreeNode<T>>>,
+        b: Option<&Arc<OctreeNode<T>>>,
+        origin: [u16; 3],
+        dimension: u16,
+        f: &mut F,
+    ) {
+        if let (Some(a), Some(b)) = (a, b) {
+            if Arc::ptr_eq(a, b) {
+                return;
+            }
+        }
+
+        let occupied = |side: Option<&Arc<OctreeNode<T>>>| match side {
+            Some(node) if !node.leaf => None,
+            Some(node) => Some(node.data.is_some()),
+            None => Some(false),
+        };
+        if let (Some(x), Some(y)) = (occupied(a), occupied(b)) {
+            if x != y {
+                let max = [
+                    origin[0] + (dimension - 1),
+                    origin[1] + (dimension - 1),
+                    origin[2] + (dimension - 1),
+                ];
+                for_each_in_cube(origin, dimension, origin, max, f);
+            }
+            return;
+        }
+
+        let half = dimension / 2;
+        for i in 0..8 {
+            OctreeNode::for_each_occupancy_difference(
+                child_side(a, i),
+                child_side(b, i),
+                child_origin(origin, i, half),
+                half,
+                f,
+            );
+        }
+    }
+
+    // Visit every location within the inclusive box up to `max` occupied in either of two nodes
+    // covering the same cube, with the value on each side, descending both in lockstep and
+    // treating leaves as uniform over their whole volume
+    pub(crate) fn for_each_joined<F: FnMut([u16; 3], Option<T>, Option<T>)>(
+        a: Option<&Arc<OctreeNode<T>>>,
+        b: Option<&Arc<OctreeNode<T>>>,
+        origin: [u16; 3],
+        dimension: u16,
+        max: [u16; 3],
+        f: &mut F,
+    ) {
+        if (0..3).any(|axis| origin[axis] > max[axis]) {
+            return;
+        }
+
+        let uniform = |side: Option<&Arc<OctreeNode<T>>>| match side {
+            Some(node) if !node.leaf => None,
+            Some(node) => Some(node.data),
+            None => Some(None),
+        };
+        if let (Some(x), Some(y)) = (uniform(a), uniform(b)) {
+            if x.is_some() || y.is_some() {
+                for_each_in_cube(origin, dimension, origin, max, &mut |loc| f(loc, x, y));
+            }
+            return;
+        }
+
+        let half = dimension / 2;
+        for i in 0..8 {
+            OctreeNode::for_each_joined(
+                child_side(a, i),
+                child_side(b, i),
+                child_origin(origin, i, half),
+                half,
+                max,
+                f,
+            );
+        }
+    }
+
+    // Apply `f` to the value of every leaf below this node, merging children mapped to equal
+    // values
+    pub(crate) fn map<U, F>(&self, f: &F) -> OctreeNode<U>
+    where
+        U: Copy + PartialEq,
+        F: Fn(T) -> U,
+    {
+        if self.leaf {
+            let mut node = OctreeNode::construct_root(self.dimension);
+            node.set_block(self.data.map(f));
+            return node;
+        }
+
+        let children = self
+            .children
+            .iter()
+            .map(|child| child.as_ref().map(|child| Arc::new(child.map(f))))
+            .collect();
+        OctreeNode::from_children(self.dimension, children, Some(&|a: U, b: U| a == b))
+    }
+
+    // Apply `f` to the data of this node and every node below it, including internal and empty
+    // nodes, keeping the structure and simplification flags exactly as they are
+    pub(crate) fn cast<U, F>(&self, f: &F) -> OctreeNode<U>
+    where
+        U: Copy + PartialEq,
+        F: Fn(Option<T>) -> Option<U>,
+    {
+        OctreeNode {
+            dimension: self.dimension,
+            leaf: self.leaf,
+            simplified: self.simplified,
+            children: self
+                .children
+                .iter()
+                .map(|child| child.as_ref().map(|child| Arc::new(child.cast(f))))
+                .collect(),
+            data: f(self.data),
+            lod: self.lod.and_then(|lod| f(Some(lod))),
+            marked: self.marked,
+        }
+    }
+
+    // Store the reduction of each internal node's children in its `lod` field, bottom-up,
+    // returning the level of detail value of this node
+    pub(crate) fn compute_lod<F: Fn(&[Option<T>]) -> Option<T>>(
+        &mut self,
+        aggregate: &F,
+    ) -> Option<T> {
+        if self.leaf {
+            return self.data;
+        }
+
+        let values: Vec<Option<T>> = self
+            .children
+            .iter_mut()
+            .map(|child| {
+                child
+                    .as_mut()
+                    .and_then(|child_node| Arc::make_mut(child_node).compute_lod(aggregate))
+            })
+            .collect();
+        self.lod = aggregate(&values);
+        self.lod
+    }
+
+    // Get the level of detail value of the node `level` levels below this one, towards a
+    // `NodeLoc`, stopping early at a leaf
+    pub(crate) fn lod_at(&self, loc: &mut NodeLoc, level: u8) -> Option<T> {
+        if self.leaf {
+            return self.data;
+        }
+        if level == 0 {
+            return self.lod;
+        }
+
+        let child_loc = self.get_child_loc(loc);
+        self.children[child_loc as usize]
+            .as_ref()
+            .and_then(|child| child.lod_at(loc, level - 1))
+    }
+
+    // Visit every node `depth` levels below this one, or each leaf or empty octant reached
+    // before that, passing its origin, edge length and data or level of detail value
+    pub(crate) fn for_each_to_depth<F: FnMut([u16; 3], u16, Option<T>)>(
+        &self,
+        origin: [u16; 3],
+        depth: u8,
+        f: &mut F,
+    ) {
+        if self.leaf {
+            f(origin, self.dimension, self.data);
+            return;
+        }
+        if depth == 0 {
+            f(origin, self.dimension, self.lod);
+            return;
+        }
+
+        let half = self.dimension / 2;
+        for (i, child) in self.children.iter().enumerate() {
+            let child_origin = child_origin(origin, i, half);
+            match child {
+                Some(child_node) => child_node.for_each_to_depth(child_origin, depth - 1, f),
+                None => f(child_origin, half, None),
+            }
+        }
+    }
+
+    // Get data at many `NodeLoc`s at once, walking each shared branch a single time
+    pub(crate) fn at_many(&self, queries: Vec<(usize, NodeLoc)>, results: &mut [Option<T>]) {
+        if self.leaf {
+            if let Some(data) = self.data {
+                for (index, _) in queries {
+                    results[index] = Some(data);
+                }
+            }
+            return;
+        }
+
+        let mut octants: Vec<Vec<(usize, NodeLoc)>> = (0..8).map(|_| vec![]).collect();
+        for (index, mut loc) in queries {
+            let child_loc = self.get_child_loc(&mut loc);
+            octants[child_loc as usize].push((index, loc));
+        }
+
+        for (child, octant) in self.children.iter().zip(octants) {
+            if let Some(child_node) = child {
+                if !octant.is_empty() {
+                    child_node.at_many(octant, results);
+                }
+            }
+        }
+    }
+
+    // Split the block covering `loc` into eight equal-valued children, then the child covering
+    // `loc` in turn, until `loc` is held by a single voxel leaf
+    pub(crate) fn desimplify(&mut self, loc: &mut NodeLoc) {
+        if self.leaf {
+            if self.dimension == 1 || self.data.is_none() {
+                return;
+            }
+            self.split();
+        }
+
+        let child_loc = self.get_child_loc(loc);
+        if let Some(child) = self.children[child_loc as usize].as_mut() {
+            Arc::make_mut(child).desimplify(loc);
+        }
+    }
+
+    // Test whether `compact` would remove anything below this node
+    pub(crate) fn any_empty(&self) -> bool {
+        if self.leaf {
+            return false;
+        }
+
+        self.children.iter().all(Option::is_none)
+            || self
+                .children
+                .iter()
+                .flatten()
+                .any(|child| (child.leaf && child.data.is_none()) || child.any_empty())
+    }
+
+    // Remove empty descendants of this node, returning `true` if the node itself is now empty.
+    // Only shared nodes holding something to remove are cloned.
+    pub(crate) fn compact(&mut self) -> bool {
+        if self.leaf {
+            return self.data.is_none();
+        }
+
+        for child in self.children.iter_mut() {
+            let empty = match child {
+                Some(child_node) if child_node.leaf => child_node.data.is_none(),
+                Some(child_node) if child_node.any_empty() => Arc::make_mut(child_node).compact(),
+                _ => false,
+            };
+            if empty {
+                *child = None;
+            }
+        }
+
+        if self.children.iter().all(Option::is_none) {
+            self.set_block(None);
+            true
+        } else {
+            false
+        }
+    }
+
+    // Check the structural invariants of this node and every node below it in debug builds,
+    // panicking with the path to the first broken node
+    pub(crate) fn assert_consistent(&self, path: &mut Vec<usize>) {
+        debug_assert_eq!(self.children.len(), 8, "Node at {:?} lacks octants", path);
+        if self.leaf {
+            debug_assert!(
+                self.children.iter().all(Option::is_none),
+                "Leaf at {:?} has children",
+                path
+            );
+            debug_assert!(
+                !self.simplified || (self.data.is_some() && self.dimension > 1),
+                "Leaf at {:?} is simplified without covering a block",
+                path
+            );
+            return;
+        }
+
+        debug_assert!(self.dimension > 1, "Voxel at {:?} has children", path);
+        debug_assert!(self.data.is_none(), "Internal node at {:?} has data", path);
+        debug_assert!(
+            !self.simplified,
+            "Internal node at {:?} is simplified",
+            path
+        );
+        for (i, child) in self.children.iter().enumerate() {
+            if let Some(child) = child {
+                path.push(i);
+                debug_assert_eq!(
+                    child.dimension,
+                    self.dimension / 2,
+                    "Node at {:?} has the wrong dimension",
+                    path
+                );
+                child.assert_consistent(path);
+                path.pop();
+            }
+        }
+    }
+
+    // Test whether this node or any node below it is marked
+    fn any_marked(&self) -> bool {
+        self.marked
+            || self
+                .children
+                .iter()
+                .flatten()
+                .any(|child| child.any_marked())
+    }
+
+    // Clear the scratch flag of every node below this node, only cloning shared nodes that
+    // hold a mark
+    pub(crate) fn clear_marks(&mut self) {
+        self.marked = false;
+        for child in self.children.iter_mut().flatten() {
+            if child.any_marked() {
+                Arc::make_mut(child).clear_marks();
+            }
+        }
+    }
+
+    // Get metadata describing the leaf that covers a given `NodeLoc`
+    pub(crate) fn cell_info(&self, loc: &mut NodeLoc, origin: [u16; 3]) -> CellInfo {
+        if self.leaf {
+            return CellInfo {
+                origin,
+                size: self.dimension,
+                simplified: self.simplified,
+                occupied: self.data.is_some(),
+            };
+        }
+
+        let child_loc = self.get_child_loc(loc);
+        let child_origin = child_origin(origin, child_loc as usize, self.dimension / 2);
+
+        match self.children[child_loc as usize] {
+            Some(ref child) => child.cell_info(loc, child_origin),
+            None => CellInfo {
+                origin: child_origin,
+                size: self.dimension / 2,
+                simplified: false,
+                occupied: false,
+            },
+        }
+    }
+
+    // Visit every occupied leaf below this node, passing its origin, edge length and data
+    pub(crate) fn for_each_leaf<F: FnMut([u16; 3], u16, T)>(&self, origin: [u16; 3], f: &mut F) {
+        if self.leaf {
+            if let Some(data) = self.data {
+                f(origin, self.dimension, data);
+            }
+            return;
+        }
+
+        let half = self.dimension / 2;
+        for (i, child) in self.children.iter().enumerate() {
+            if let Some(child_node) = child {
+                child_node.for_each_leaf(child_origin(origin, i, half), f);
+            }
+        }
+    }
+
+    // Visit every occupied leaf below this node, passing its origin, edge length, data and
+    // whether it is the result of simplifying a uniform region
+    pub(crate) fn for_each_block<F: FnMut([u16; 3], u16, T, bool)>(
+        &self,
+        origin: [u16; 3],
+        f: &mut F,
+    ) {
+        if self.leaf {
+            if let Some(data) = self.data {
+                f(origin, self.dimension, data, self.simplified);
+            }
+            return;
+        }
+
+        let half = self.dimension / 2;
+        for (i, child) in self.children.iter().enumerate() {
+            if let Some(child_node) = child {
+                child_node.for_each_block(child_origin(origin, i, half), f);
+            }
+        }
+    }
+
+    // Test whether any occupied leaf below this node satisfies `f`, stopping at the first match
+    pub(crate) fn any_leaf<F: FnMut(T) -> bool>(&self, f: &mut F) -> bool {
+        if self.leaf {
+            return self.data.is_some_and(&mut *f);
+        }
+
+        self.children
+            .iter()
+            .flatten()
+            .any(|child| child.any_leaf(f))
+    }
+
+    // Get the depth of the deepest occupied leaf below this node, relative to this node
+    pub(crate) fn actual_depth(&self) -> Option<u8> {
+        if self.leaf {
+            return self.data.map(|_| 0);
+        }
+
+        self.children
+            .iter()
+            .flatten()
+            .filter_map(|child| child.actual_depth())
+            .max()
+            .map(|depth| depth + 1)
+    }
+
+    // Visit every empty location of this node within the inclusive box `min`..=`max`
+    pub(crate) fn for_each_empty<F: FnMut([u16; 3])>(
+        &self,
+        origin: [u16; 3],
+        min: [u16; 3],
+        max: [u16; 3],
+        f: &mut F,
+    ) {
+        if self.leaf {
+            if self.data.is_none() {
+                for_each_in_cube(origin, self.dimension, min, max, f);
+            }
+            return;
+        }
+
+        let half = self.dimension / 2;
+        for (i, child) in self.children.iter().enumerate() {
+            let child_origin = child_origin(origin, i, half);
+            let overlaps = (0..3).all(|axis| {
+                max[axis] >= child_origin[axis] && min[axis] < child_origin[axis] + half
+            });
+
+            if overlaps {
+                match child {
+                    Some(child_node) => child_node.for_each_empty(child_origin, min, max, f),
+                    None => for_each_in_cube(child_origin, half, min, max, f),
+                }
+            }
+        }
+    }
+
+    // Visit every region of this node within the inclusive box `min`..=`max` that does not
+    // already hold a value equivalent to `data`, passing the inclusive bounds of its overlap
+    // with the box and its current value. Blocks holding such a value are skipped whole.
+    pub(crate) fn for_each_differing<F: FnMut([u16; 3], [u16; 3], Option<T>)>(
+        &self,
+        origin: [u16; 3],
+        min: [u16; 3],
+        max: [u16; 3],
+        data: T,
+        equivalent: &dyn Fn(T, T) -> bool,
+        f: &mut F,
+    ) {
+        if self.leaf {
+            if !self
+                .data
+                .is_some_and(|node_data| equivalent(node_data, data))
+            {
+                f(
+                    clip_min(origin, min),
+                    clip_max(origin, self.dimension, max),
+                    self.data,
+                );
+            }
+            return;
+        }
+
+        let half = self.dimension / 2;
+        for (i, child) in self.children.iter().enumerate() {
+            let child_origin = child_origin(origin, i, half);
+            let overlaps = (0..3).all(|axis| {
+                max[axis] >= child_origin[axis] && min[axis] < child_origin[axis] + half
+            });
+
+            if overlaps {
+                match child {
+                    Some(child_node) => {
+                        child_node.for_each_differing(child_origin, min, max, data, equivalent, f)
+                    }
+                    None => f(
+                        clip_min(child_origin, min),
+                        clip_max(child_origin, half, max),
+                        None,
+                    ),
+                }
+            }
+        }
+    }
+
+    // Visit every occupied location of this node within the inclusive box `min`..=`max`
+    pub(crate) fn for_each_occupied<F: FnMut([u16; 3], T)>(
+        &self,
+        origin: [u16; 3],
+        min: [u16; 3],
+        max: [u16; 3],
+        f: &mut F,
+    ) {
+        if self.leaf {
+            if let Some(data) = self.data {
+                for_each_in_cube(origin, self.dimension, min, max, &mut |loc| f(loc, data));
+            }
+            return;
+        }
+
+        let half = self.dimension / 2;
+        for (i, child) in self.children.iter().enumerate() {
+            let child_origin = child_origin(origin, i, half);
+            let overlaps = (0..3).all(|axis| {
+                max[axis] >= child_origin[axis] && min[axis] < child_origin[axis] + half
+            });
+
+            if let (true, Some(child_node)) = (overlaps, child) {
+                child_node.for_each_occupied(child_origin, min, max, f);
+            }
+        }
+    }
+
+    // Count the occupied locations of this node within the inclusive box `min`..=`max`,
+    // counting each simplified block by its overlap with the box
+    pub(crate) fn count_occupied(&self, origin: [u16; 3], min: [u16; 3], max: [u16; 3]) -> u64 {
+        self.count_where(origin, min, max, &|_| true)
+    }
+
+    // Count the locations of this node within the inclusive box `min`..=`max` holding a value
+    // that satisfies `f`, calling it once per leaf and counting each matching simplified block
+    // by its overlap with the box
+    pub(crate) fn count_where(
+        &self,
+        origin: [u16; 3],
+        min: [u16; 3],
+        max: [u16; 3],
+        f: &dyn Fn(T) -> bool,
+    ) -> u64 {
+        if self.leaf {
+            if !self.data.is_some_and(f) {
+                return 0;
+            }
+            return (0..3)
+                .map(|axis| {
+                    let start = origin[axis].max(min[axis]);
+                    let end = (origin[axis] + (self.dimension - 1)).min(max[axis]);
+                    if start > end {
+                        0
+                    } else {
+                        u64::from(end - start) + 1
+                    }
+                })
+                .product();
+        }
+
+        let half = self.dimension / 2;
+        let mut count = 0;
+        for (i, child) in self.children.iter().enumerate() {
+            let child_origin = child_origin(origin, i, half);
+            let overlaps = (0..3).all(|axis| {
+                max[axis] >= child_origin[axis] && min[axis] < child_origin[axis] + half
+            });
+            if let (true, Some(child_node)) = (overlaps, child) {
+                count += child_node.count_where(child_origin, min, max, f);
+            }
+        }
+        count
+    }
+
+    // Test whether any location of this node within the inclusive box `min`..=`max` is
+    // occupied, stopping at the first one found
+    pub(crate) fn any_occupied(&self, origin: [u16; 3], min: [u16; 3], max: [u16; 3]) -> bool {
+        let overlaps = (0..3)
+            .all(|axis| max[axis] >= origin[axis] && min[axis] < origin[axis] + self.dimension);
+        if !overlaps {
+            return false;
+        }
+        if self.leaf {
+            return self.data.is_some();
+        }
+
+        let half = self.dimension / 2;
+        self.children.iter().enumerate().any(|(i, child)| {
+            child.as_ref().is_some_and(|child_node| {
+                child_node.any_occupied(child_origin(origin, i, half), min, max)
+            })
+        })
+    }
+
+    // Visit every occupied location of this node whose coordinates are all multiples of
+    // `stride`, skipping the rest of each simplified block
+    pub(crate) fn for_each_strided<F: FnMut([u16; 3], T)>(
+        &self,
+        origin: [u16; 3],
+        stride: u16,
+        f: &mut F,
+    ) {
+        if self.leaf {
+            if let Some(data) = self.data {
+                let axis = |axis: usize| {
+                    let first = origin[axis].div_ceil(stride) * stride;
+                    (u32::from(first)..u32::from(origin[axis]) + u32::from(self.dimension))
+                        .step_by(usize::from(stride))
+                };
+                for z in axis(2) {
+                    for y in axis(1) {
+                        for x in axis(0) {
+                            f([x as u16, y as u16, z as u16], data);
+                        }
+                    }
+                }
+            }
+            return;
+        }
+
+        let half = self.dimension / 2;
+        for (i, child) in self.children.iter().enumerate() {
+            if let Some(child_node) = child {
+                child_node.for_each_strided(child_origin(origin, i, half), stride, f);
+            }
+        }
+    }
+
+    // Estimate the heap bytes used by the child storage of this node and its descendants
+    pub(crate) fn heap_bytes(&self) -> usize {
+        let slots = self.children.capacity() * mem::size_of::<Option<Arc<OctreeNode<T>>>>();
+        let nodes = self.children.iter().flatten().count() * mem::size_of::<OctreeNode<T>>();
+        slots
+            + nodes
+            + self
+                .children
+                .iter()
+                .flatten()
+                .map(|child| child.heap_bytes())
+                .sum::<usize>()
+    }
+
+    // Get the number of distinct values among the octants of a node whose children are all
+    // leaves, counting empty octants as one value, or `None` if it has an internal child
+    pub(crate) fn merge_cost(&self) -> Option<usize> {
+        if self.leaf {
+            return None;
+        }
+
+        let mut values: Vec<Option<T>> = Vec::with_capacity(8);
+        for child in &self.children {
+            let value = match child {
+                Some(child_node) if !child_node.leaf => return None,
+                Some(child_node) => child_node.data,
+                None => None,
+            };
+            if !values.contains(&value) {
+                values.push(value);
+            }
+        }
+        Some(values.len())
+    }
+
+    // Visit every node below this one that can be merged into a leaf, passing its path of
+    // child indices, its merge cost and its dimension
+    pub(crate) fn for_each_mergeable<F: FnMut(&[usize], usize, u16)>(
+        &self,
+        path: &mut Vec<usize>,
+        f: &mut F,
+    ) {
+        if let Some(cost) = self.merge_cost() {
+            f(path, cost, self.dimension);
+            return;
+        }
+
+        for (i, child) in self.children.iter().enumerate() {
+            if let Some(child_node) = child {
+                path.push(i);
+                child_node.for_each_mergeable(path, f);
+                path.pop();
+            }
+        }
+    }
+
+    // Follow a path of child indices down from this node
+    pub(crate) fn descendant(&self, path: &[usize]) -> Option<&OctreeNode<T>> {
+        path.iter()
+            .try_fold(self, |node, &index| node.children[index].as_deref())
+    }
+
+    // Replace the node at the end of a path of child indices with a leaf holding `reduce` of
+    // its children's values, returning the number of nodes removed
+    pub(crate) fn merge_at<F: Fn(&[Option<T>]) -> Option<T>>(
+        &mut self,
+        path: &[usize],
+        reduce: &F,
+    ) -> usize {
+        match path.split_first() {
+            Some((&index, rest)) => match self.children[index].as_mut() {
+                Some(child) => Arc::make_mut(child).merge_at(rest, reduce),
+                None => 0,
+            },
+            None => {
+                let values: Vec<Option<T>> = self
+                    .children
+                    .iter()
+                    .map(|child| child.as_ref().and_then(|child_node| child_node.data))
+                    .collect();
+                let removed = self.node_count() - 1;
+                self.set_block(reduce(&values));
+                removed
+            }
+        }
+    }
+
+    // Replace the child at a given index, splitting this node first if it is a leaf, then
+    // merge the children if they have become uniform
+    pub(crate) fn replace_child(
+        &mut self,
+        index: usize,
+        child: Option<Arc<OctreeNode<T>>>,
+        equivalent: Option<&dyn Fn(T, T) -> bool>,
+    ) {
+        if self.leaf {
+            match self.data {
+                Some(_) => self.split(),
+                None => self.make_leaf(false),
+            }
+        }
+        self.children[index] = child;
+
+        let first = self.children[0].as_ref().and_then(|child| child.data);
+        match (first, equivalent) {
+            (Some(data), Some(equivalent)) => self.try_simplify(&data, equivalent),
+            (None, _) => self.try_simplify_none(),
+            _ => {}
+        }
+    }
+}
+
+// Helper function that takes ownership of a shared node, cloning it only if it is still shared
+fn unwrap_shared<T: Clone>(node: Arc<OctreeNode<T>>) -> OctreeNode<T> {
+    Arc::try_unwrap(node).unwrap_or_else(|shared| (*shared).clone())
+}
+
+// Helper function that returns one octant of a node for `OctreeNode::combine`, where a leaf
+// covers all of its octants
+fn child_side<T>(side: Option<&Arc<OctreeNode<T>>>, index: usize) -> Option<&Arc<OctreeNode<T>>> {
+    match side {
+        Some(node) if !node.leaf => node.children[index].as_ref(),
+        _ => side,
+    }
+}
+
+// Helper function that returns an empty `OctreeNode<T>` child vector
+fn no_children<T>() -> Vec<Option<Arc<OctreeNode<T>>>> {
+    vec![None, None, None, None, None, None, None, None]
+}
+
+// Helper function that visits every location of a cube within the inclusive box `min`..=`max`
+fn for_each_in_cube<F: FnMut([u16; 3])>(
+    origin: [u16; 3],
+    size: u16,
+    min: [u16; 3],
+    max: [u16; 3],
+    f: &mut F,
+) {
+    let start = |axis: usize| origin[axis].max(min[axis]);
+    let end = |axis: usize| (origin[axis] + (size - 1)).min(max[axis]);
+
+    for z in start(2)..=end(2) {
+        for y in start(1)..=end(1) {
+            for x in start(0)..=end(0) {
+                f([x, y, z]);
+            }
+        }
+    }
+}
+
+// Helper function that returns the lower corner of a cube's overlap with a box
+fn clip_min(origin: [u16; 3], min: [u16; 3]) -> [u16; 3] {
+    [
+        origin[0].max(min[0]),
+        origin[1].max(min[1]),
+        origin[2].max(min[2]),
+    ]
+}
+
+// Helper function that returns the upper corner of a cube's overlap with a box
+fn clip_max(origin: [u16; 3], size: u16, max: [u16; 3]) -> [u16; 3] {
+    [
+        (origin[0] + (size - 1)).min(max[0]),
+        (origin[1] + (size - 1)).min(max[1]),
+        (origin[2] + (size - 1)).min(max[2]),
+    ]
+}
+
+// Helper function that returns the index of the child octant containing an absolute location
+pub fn child_index(origin: [u16; 3], half: u16, loc: [u16; 3]) -> usize {
+    let right = loc[0] >= origin[0] + half;
+    let front = loc[1] >= origin[1] + half;
+    let top = loc[2] >= origin[2] + half;
+
+    let base = match (right, front) {
+        (false, false) => ChildLoc::BaseRearLeft,
+        (true, false) => ChildLoc::BaseRearRight,
+        (true, true) => ChildLoc::BaseFrontRight,
+        (false, true) => ChildLoc::BaseFrontLeft,
+    } as usize;
+
+    if top {
+        base + 4
+    } else {
+        base
+    }
+}
+
+// Helper function that returns the origin of a child octant, given the origin of its parent
+pub fn child_origin(origin: [u16; 3], child: usize, half: u16) -> [u16; 3] {
+    let x = if child % 4 == 1 || child % 4 == 2 {
+        half
+    } else {
+        0
+    };
+    let y = if child % 4 >= 2 { half } else { 0 };
+    let z = if child >= 4 { half } else { 0 };
+    [origin[0] + x, origin[1] + y, origin[2] + z]
 }
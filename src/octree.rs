@@ -1,22 +1,434 @@
+use codec::{crc32, read_loc, write_loc, VoxelCodec};
+use cursor::Cursor;
 use error::OctreeError;
-use node::{NodeLoc, OctreeNode};
+use flat::FlatOctree;
+use math::{morton_decode, morton_encode};
+use node::{child_index, child_origin, read_binary, NodeLoc, OctreeNode};
+use query::{cast_ray, VolumeQuery};
 use serde::{Serialize, Deserialize};
-use std::{fmt, u8};
+#[cfg(feature = "json")]
+use serde_json;
+use std::cell::{Cell, RefCell};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::io::{self, Read, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::{fmt, mem};
 
 /// Octree structure
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Octree<T> {
     dimension: u16,
+    extents: [u16; 3],
     max_depth: u8,
-    root: OctreeNode<T>,
+    root: Arc<OctreeNode<T>>,
+    default: Option<T>,
+    simplification: SimplificationMode,
+    #[serde(default)]
+    bounds_policy: BoundsPolicy,
+    #[serde(skip)]
+    metric: Option<fn(T, T) -> f64>,
+    #[serde(default)]
+    meta: OctreeMeta,
+    #[serde(skip)]
+    tracker: ChangeTracker<T>,
+    #[serde(skip)]
+    occupied: OccupiedCount,
+    #[serde(skip)]
+    paths: PathCache<T>,
 }
 
-impl<T> Octree<T>
+/// Cached number of occupied voxels in an `Octree<T>`, kept up to date by edits that can count
+/// their own changes, and recounted lazily after any other change
+struct OccupiedCount(AtomicU64);
+
+/// Value of an `OccupiedCount` that must be recounted
+const UNKNOWN_COUNT: u64 = u64::MAX;
+
+impl OccupiedCount {
+    fn new(count: u64) -> OccupiedCount {
+        OccupiedCount(AtomicU64::new(count))
+    }
+
+    /// Get the count, if it is known
+    fn get(&self) -> Option<u64> {
+        match self.0.load(Ordering::Relaxed) {
+            UNKNOWN_COUNT => None,
+            count => Some(count),
+        }
+    }
+
+    fn set(&self, count: u64) {
+        self.0.store(count, Ordering::Relaxed);
+    }
+
+    /// Mark the count as needing a recount
+    fn invalidate(&self) {
+        self.set(UNKNOWN_COUNT);
+    }
+
+    /// Update a known count by the number of voxels an edit occupied and cleared
+    fn adjust(&self, added: u64, removed: u64) {
+        if let Some(count) = self.get() {
+            self.set(count + added - removed);
+        }
+    }
+}
+
+impl Default for OccupiedCount {
+    fn default() -> OccupiedCount {
+        OccupiedCount::new(UNKNOWN_COUNT)
+    }
+}
+
+impl Clone for OccupiedCount {
+    fn clone(&self) -> OccupiedCount {
+        OccupiedCount::new(self.0.load(Ordering::Relaxed))
+    }
+}
+
+/// Side of the subtrees remembered by a `PathCache<T>`, leaving only the last levels of each
+/// lookup to walk
+const PATH_CACHE_SIDE: u16 = 4;
+
+/// Subtree cached by a `PathCache<T>`, along with the origin it covers
+type PathEntry<T> = ([u16; 3], Arc<OctreeNode<T>>);
+
+/// Recently walked subtrees of an `Octree<T>`, keyed by the origin they cover, letting lookups
+/// and inserts near a recent one skip the upper levels of the tree. The cached nodes are shared
+/// with the tree, so every entry overlapping an edit is dropped before the edit is made.
+struct PathCache<T> {
+    capacity: usize,
+    entries: VecDeque<PathEntry<T>>,
+}
+
+impl<T> PathCache<T> {
+    fn new(capacity: usize) -> PathCache<T> {
+        PathCache {
+            capacity,
+            entries: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    fn enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    /// Drop every cached subtree
+    fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Cache a subtree as the most recently used, evicting the least recently used if full
+    fn push(&mut self, entry: PathEntry<T>) {
+        self.entries.truncate(self.capacity - 1);
+        self.entries.push_front(entry);
+    }
+}
+
+impl<T> PathCache<T>
+where
+    T: Clone + PartialEq,
+{
+    /// Test if a cached subtree covers a location
+    fn covers((origin, node): &PathEntry<T>, loc: [u16; 3]) -> bool {
+        (0..3).all(|axis| loc[axis] >= origin[axis] && loc[axis] - origin[axis] < node.dimension())
+    }
+
+    /// Drop every cached subtree covering a location, returning the origin and dimension of the
+    /// most recently used one
+    fn remove_covering(&mut self, loc: [u16; 3]) -> Option<([u16; 3], u16)> {
+        let hit = self
+            .entries
+            .iter()
+            .find(|entry| PathCache::covers(entry, loc))
+            .map(|(origin, node)| (*origin, node.dimension()));
+        self.entries.retain(|entry| !PathCache::covers(entry, loc));
+        hit
+    }
+}
+
+impl<T> PathCache<T>
+where
+    T: Copy + PartialEq,
+{
+    /// Get the data stored at a location, starting from the most recently used subtree
+    /// covering it, or caching the subtree walked through to reach it on a miss
+    fn at(&mut self, root: &Arc<OctreeNode<T>>, loc: [u16; 3]) -> Option<T> {
+        let hit = self
+            .entries
+            .iter()
+            .position(|entry| PathCache::covers(entry, loc));
+        match hit {
+            Some(index) => self.entries.make_contiguous()[..=index].rotate_right(1),
+            None => self.push(OctreeNode::descend(root, loc, PATH_CACHE_SIDE)),
+        }
+
+        let (origin, node) = &self.entries[0];
+        let mut node_loc =
+            NodeLoc::new((loc[0] - origin[0], loc[1] - origin[1], loc[2] - origin[2]));
+        node.at(&mut node_loc)
+    }
+}
+
+impl<T> Default for PathCache<T> {
+    fn default() -> PathCache<T> {
+        PathCache::new(0)
+    }
+}
+
+impl<T> Clone for PathCache<T> {
+    fn clone(&self) -> PathCache<T> {
+        PathCache::new(self.capacity)
+    }
+}
+
+/// Callback notified of each voxel changed in an `Octree<T>`, registered with
+/// `Octree::set_on_change`
+pub type ChangeCallback<T> = Box<dyn FnMut([u16; 3], Option<T>, Option<T>) + Send>;
+
+/// Observers of the changes made to an `Octree<T>`, which are not carried over to clones
+struct ChangeTracker<T> {
+    on_change: Option<Mutex<ChangeCallback<T>>>,
+    dirty: Option<([u16; 3], [u16; 3])>,
+}
+
+impl<T> Default for ChangeTracker<T> {
+    fn default() -> ChangeTracker<T> {
+        ChangeTracker {
+            on_change: None,
+            dirty: None,
+        }
+    }
+}
+
+impl<T> Clone for ChangeTracker<T> {
+    fn clone(&self) -> ChangeTracker<T> {
+        ChangeTracker::default()
+    }
+}
+
+impl<T> ChangeTracker<T>
+where
+    T: Clone + PartialEq,
+{
+    /// Test if any observer needs the old and new value of each change
+    fn watching(&self) -> bool {
+        self.on_change.is_some()
+    }
+
+    /// Report a write to a location, marking it dirty and notifying the callback if it changed
+    /// the stored value
+    fn record(&mut self, loc: [u16; 3], old: Option<T>, new: Option<T>) {
+        self.extend_dirty(loc);
+
+        if old == new {
+            return;
+        }
+        if let Some(on_change) = self.on_change.as_mut() {
+            let on_change = on_change.get_mut().unwrap_or_else(|e| e.into_inner());
+            on_change(loc, old, new);
+        }
+    }
+
+    /// Report a write to every location within the inclusive box `min`..=`max`, all of which
+    /// held `old`, notifying the callback of each location only if one is registered
+    fn record_region(&mut self, min: [u16; 3], max: [u16; 3], old: Option<T>, new: Option<T>) {
+        self.extend_dirty(min);
+        self.extend_dirty(max);
+
+        if old == new {
+            return;
+        }
+        if let Some(on_change) = self.on_change.as_mut() {
+            let on_change = on_change.get_mut().unwrap_or_else(|e| e.into_inner());
+            for z in min[2]..=max[2] {
+                for y in min[1]..=max[1] {
+                    for x in min[0]..=max[0] {
+                        on_change([x, y, z], old.clone(), new.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    /// Grow the dirty bounds to include a location
+    fn extend_dirty(&mut self, loc: [u16; 3]) {
+        self.dirty = Some(match self.dirty {
+            Some((min, max)) => {
+                let mut bounds = (min, max);
+                for axis in 0..3 {
+                    bounds.0[axis] = min[axis].min(loc[axis]);
+                    bounds.1[axis] = max[axis].max(loc[axis]);
+                }
+                bounds
+            }
+            None => (loc, loc),
+        });
+    }
+}
+
+/// Metadata describing an `Octree<T>` as a whole
+///
+/// Exporters use `origin` and `voxel_size` to place voxels in physical space, so that the voxel
+/// at `[x, y, z]` starts at `origin + [x, y, z] * voxel_size`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OctreeMeta {
+    /// Position of the `[0, 0, 0]` corner of the `Octree<T>` in physical space
+    pub origin: [f32; 3],
+    /// Edge length of a single voxel in physical units, such as meters
+    pub voxel_size: f32,
+    /// Name of the `Octree<T>`
+    pub name: String,
+}
+
+impl Default for OctreeMeta {
+    fn default() -> OctreeMeta {
+        OctreeMeta {
+            origin: [0.0; 3],
+            voxel_size: 1.0,
+            name: String::new(),
+        }
+    }
+}
+
+impl OctreeMeta {
+    /// Get the physical position of the centre of the voxel at a given location
+    fn voxel_center(&self, loc: [u16; 3]) -> [f32; 3] {
+        let mut center = [0.0; 3];
+        for axis in 0..3 {
+            center[axis] = self.origin[axis] + (f32::from(loc[axis]) + 0.5) * self.voxel_size;
+        }
+        center
+    }
+
+    /// Get the location of a physical position in voxel units, without rounding it to a voxel
+    fn to_voxel_space(&self, position: [f32; 3]) -> [f32; 3] {
+        let mut loc = [0.0; 3];
+        for axis in 0..3 {
+            loc[axis] = (position[axis] - self.origin[axis]) / self.voxel_size;
+        }
+        loc
+    }
+}
+
+/// Policy deciding when regions of an `Octree<T>` are simplified into a single node
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum SimplificationMode {
+    /// Simplify regions as soon as all of their values are equal
+    Eager,
+    /// Never simplify regions
+    Off,
+    /// Simplify regions as soon as all of their values are within a tolerance of each other,
+    /// as measured by the metric given to `OctreeBuilder::metric`
+    Tolerance(f64),
+}
+
+/// Policy deciding how an `Octree<T>` treats out of bounds locations given to `insert`, `take`
+/// and `at`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum BoundsPolicy {
+    /// Reject out of bounds inserts with an `OutOfBoundsError`
+    #[default]
+    Error,
+    /// Clamp each coordinate of an out of bounds location to the nearest in bounds cell
+    Clamp,
+    /// Skip out of bounds inserts, returning `Ok`
+    Ignore,
+}
+
+/// Builder for an `Octree<T>` with non-default configuration
+///
+/// # Examples
+///
+/// ```
+/// # use octo::octree::{OctreeBuilder, SimplificationMode};
+/// let octree = OctreeBuilder::<u8>::new()
+///     .dimension(16)
+///     .default(0)
+///     .simplification(SimplificationMode::Off)
+///     .build()
+///     .unwrap();
+///
+/// assert_eq!(octree.at([0, 0, 0]), Some(0));
+/// ```
+pub struct OctreeBuilder<T> {
+    dimension: Option<u16>,
+    default: Option<T>,
+    simplification: SimplificationMode,
+    metric: Option<fn(T, T) -> f64>,
+}
+
+impl<T> Default for OctreeBuilder<T> {
+    fn default() -> OctreeBuilder<T> {
+        OctreeBuilder {
+            dimension: None,
+            default: None,
+            simplification: SimplificationMode::Eager,
+            metric: None,
+        }
+    }
+}
+
+impl<T> OctreeBuilder<T>
 where
     T: Copy + PartialEq,
+{
+    /// Constructs a new `OctreeBuilder<T>`, with eager simplification and no default value
+    pub fn new() -> OctreeBuilder<T> {
+        Default::default()
+    }
+
+    /// Set the x/y/z dimension of the `Octree<T>`
+    pub fn dimension(mut self, dimension: u16) -> OctreeBuilder<T> {
+        self.dimension = Some(dimension);
+        self
+    }
+
+    /// Set the value held by unoccupied locations of the `Octree<T>`
+    pub fn default(mut self, default: T) -> OctreeBuilder<T> {
+        self.default = Some(default);
+        self
+    }
+
+    /// Set the policy deciding when regions of the `Octree<T>` are simplified
+    pub fn simplification(mut self, simplification: SimplificationMode) -> OctreeBuilder<T> {
+        self.simplification = simplification;
+        self
+    }
+
+    /// Set the distance metric used by `SimplificationMode::Tolerance`
+    ///
+    /// Without a metric, `SimplificationMode::Tolerance` only simplifies equal values.
+    pub fn metric(mut self, metric: fn(T, T) -> f64) -> OctreeBuilder<T> {
+        self.metric = Some(metric);
+        self
+    }
+
+    /// Constructs the configured `Octree<T>`
+    ///
+    /// Returns a `DimensionError` if the dimension is invalid or was never set.
+    pub fn build(self) -> Result<Octree<T>, OctreeError> {
+        let mut octree = Octree::new(self.dimension.ok_or(OctreeError::DimensionError)?)?;
+        octree.default = self.default;
+        octree.simplification = self.simplification;
+        octree.metric = self.metric;
+        Ok(octree)
+    }
+}
+
+impl<T> Octree<T>
+where
+    T: Clone + PartialEq,
 {
     /// Constructs a new `Octree<T>`.
     ///
+    /// `dimension` must be a power of two. A dimension of 1 gives a single-cell tree, whose root
+    /// holds the value at `[0, 0, 0]` directly.
+    ///
     /// # Examples
     ///
     /// ```
@@ -25,43 +437,131 @@ where
     /// ```
     ///
     pub fn new(dimension: u16) -> Result<Octree<T>, OctreeError> {
-        let depth = f64::from(dimension).sqrt();
-        let remainder = depth.fract();
-
-        if remainder == 0.0 && ((depth as u8) < core::u8::MAX) {
+        if dimension.is_power_of_two() {
             Ok(Octree {
                 dimension,
-                max_depth: depth as u8,
-                root: OctreeNode::construct_root(dimension),
+                extents: [dimension; 3],
+                max_depth: dimension.trailing_zeros() as u8,
+                root: Arc::new(OctreeNode::construct_root(dimension)),
+                default: None,
+                simplification: SimplificationMode::Eager,
+                bounds_policy: BoundsPolicy::Error,
+                metric: None,
+                meta: OctreeMeta::default(),
+                tracker: ChangeTracker::default(),
+                occupied: OccupiedCount::new(0),
+                paths: PathCache::default(),
             })
         } else {
             Err(OctreeError::DimensionError)
         }
     }
 
-    /// Insert a new `OctreeNode<T>` into the `Octree<T>`
-    /// If this is called on a location where a node already exists, just set the `data` field
+    /// Constructs a new `Octree<T>` with an independent power of two extent along each axis
+    ///
+    /// Locations are bounded separately on each axis. The nodes of the `Octree<T>` remain
+    /// cubes, with the root as large as the largest extent, but space beyond the smaller
+    /// extents is never allocated, so it costs nothing to store. `dimension` returns the root
+    /// size, while `extents` returns the bounds along each axis.
     ///
     /// # Examples
     ///
     /// ```
     /// # use octo::octree::Octree;
-    /// #
-    /// # let mut octree = Octree::<u8>::new(16).unwrap();
-    /// octree.insert([0, 0, 0], 255).unwrap();
+    /// let mut octree = Octree::<u8>::new_rect([256, 256, 64]).unwrap();
+    ///
+    /// assert!(octree.insert([255, 255, 63], 255).is_ok());
+    /// assert!(octree.insert([0, 0, 64], 255).is_err());
     /// ```
     ///
-    pub fn insert(&mut self, loc: [u16; 3], data: T) -> Result<(), OctreeError> {
-        let mut node_loc = self.loc_from_array(loc);
-        if self.contains_loc(&node_loc) {
-            self.root.insert(&mut node_loc, data);
-            Ok(())
-        } else {
-            Err(OctreeError::OutOfBoundsError)
+    pub fn new_rect(extents: [u16; 3]) -> Result<Octree<T>, OctreeError> {
+        if !extents.iter().all(|extent| extent.is_power_of_two()) {
+            return Err(OctreeError::DimensionError);
         }
+
+        let mut octree = Octree::new(extents.iter().cloned().max().unwrap())?;
+        octree.extents = extents;
+        Ok(octree)
     }
 
-    /// Get the value stored by the `Octree<T>` at a given node
+    /// Constructs a new `Octree<T>`, where every unoccupied location holds a default value
+    ///
+    /// Empty regions of the `Octree<T>` are never allocated, so regions holding the default value
+    /// cost nothing to store, in the same way as a simplified region.
+    ///
+    /// The default value is never stored. Inserting, setting, filling or mapping to a value equal
+    /// to the default clears the locations instead, as `insert_none` does, so they stay unoccupied and are
+    /// skipped by `len` and iteration.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// let octree = Octree::<u8>::new_with_default(16, 0).unwrap();
+    ///
+    /// assert_eq!(octree.at([0, 0, 0]), Some(0));
+    /// ```
+    ///
+    pub fn new_with_default(dimension: u16, default: T) -> Result<Octree<T>, OctreeError> {
+        let mut octree = Octree::new(dimension)?;
+        octree.default = Some(default);
+        Ok(octree)
+    }
+
+    /// Constructs the smallest `Octree<T>` bounding every location of a set of points, and
+    /// inserts them in order
+    ///
+    /// The dimension is the smallest power of two greater than the largest coordinate on any
+    /// axis, and an empty set of points produces a single-voxel `Octree<T>`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any coordinate exceeds 32767, the largest that a power of two `u16` dimension
+    /// can bound.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// let octree = Octree::<u8>::from_points(vec![([0, 0, 0], 255), ([12, 10, 6], 128)]);
+    ///
+    /// assert_eq!(octree.dimension(), 16);
+    /// assert_eq!(octree.at([12, 10, 6]), Some(128));
+    /// ```
+    ///
+    pub fn from_points<I: IntoIterator<Item = ([u16; 3], T)>>(points: I) -> Octree<T> {
+        let points: Vec<_> = points.into_iter().collect();
+        let max = points
+            .iter()
+            .flat_map(|(loc, _)| loc.iter().cloned())
+            .max()
+            .unwrap_or(0);
+        assert!(
+            max < 1 << 15,
+            "Coordinate {} is too large for an Octree",
+            max
+        );
+
+        let mut octree = Octree::new((max + 1).next_power_of_two()).unwrap();
+        for (loc, data) in points {
+            octree.insert(loc, data).unwrap();
+        }
+        octree
+    }
+
+    /// Insert a new `OctreeNode<T>` into the `Octree<T>`
+    /// If this is called on a location where a node already exists, just set the `data` field
+    ///
+    /// Writing into a simplified region only changes the written location. The region is split
+    /// back into smaller blocks along the path to it, and every other location keeps the
+    /// region's value. A value equivalent to the region's own under the `SimplificationMode`
+    /// leaves the region as it is.
+    ///
+    /// Inserting the default value of the `Octree<T>`, if it has one, clears the location as
+    /// `insert_none` does.
+    ///
+    /// Out of bounds locations are handled according to the `BoundsPolicy` of the `Octree<T>`,
+    /// returning an `OutOfBoundsError` by default.
     ///
     /// # Examples
     ///
@@ -70,16 +570,39 @@ where
     /// #
     /// # let mut octree = Octree::<u8>::new(16).unwrap();
     /// octree.insert([0, 0, 0], 255).unwrap();
-    /// assert_eq!(octree.at([0, 0, 0]), Some(255));
     /// ```
     ///
-    pub fn at(&self, loc: [u16; 3]) -> Option<T> {
-        let mut node_loc = self.loc_from_array(loc);
-        self.root.at(&mut node_loc)
+    pub fn insert(&mut self, loc: [u16; 3], data: T) -> Result<(), OctreeError> {
+        if let Some(loc) = self.resolve_loc(loc) {
+            if self.default.as_ref() == Some(&data) {
+                self.insert_none(loc);
+                return Ok(());
+            }
+
+            let within = self.equivalence();
+            let equivalent: Option<&dyn Fn(T, T) -> bool> = match self.simplification {
+                SimplificationMode::Off => None,
+                _ => Some(&within),
+            };
+
+            let old = self.watched_at(loc);
+            self.count_insert(loc);
+            self.insert_node(loc, data, equivalent);
+            let new = self.watched_at(loc);
+            self.tracker.record(loc, old, new);
+            Ok(())
+        } else if self.bounds_policy == BoundsPolicy::Ignore {
+            Ok(())
+        } else {
+            Err(OctreeError::OutOfBoundsError(loc))
+        }
     }
 
     /// Get the value stored by the `Octree<T>` at a given node, and replace with `None`
     ///
+    /// Under `BoundsPolicy::Clamp`, out of bounds locations take from the nearest in bounds
+    /// cell. Otherwise they leave the `Octree<T>` unchanged and return `None`.
+    ///
     /// # Examples
     ///
     /// ```
@@ -93,12 +616,19 @@ where
     /// assert_eq!(val, Some(255));
     /// ```
     pub fn take(&mut self, loc: [u16; 3]) -> Option<T> {
+        let loc = self.resolve_loc(loc)?;
         let mut node_loc = self.loc_from_array(loc);
-        self.root.take(&mut node_loc)
+        let taken = self.root_mut().take(&mut node_loc);
+        self.occupied.adjust(0, u64::from(taken.is_some()));
+        self.tracker.record(loc, taken.clone(), None);
+        taken
     }
 
     /// Insert `None` into the `Octree<T>` at a given node
     ///
+    /// Under `BoundsPolicy::Clamp`, out of bounds locations clear the nearest in bounds cell.
+    /// Otherwise they leave the `Octree<T>` unchanged.
+    ///
     /// # Examples
     ///
     /// ```
@@ -112,8 +642,16 @@ where
     /// ```
     ///
     pub fn insert_none(&mut self, loc: [u16; 3]) {
+        let loc = match self.resolve_loc(loc) {
+            Some(loc) => loc,
+            None => return,
+        };
         let mut node_loc = self.loc_from_array(loc);
-        self.root.insert_none(&mut node_loc);
+        let old = self.watched_at(loc);
+        self.count_clear(loc);
+        self.root_mut().insert_none(&mut node_loc);
+        let new = self.watched_at(loc);
+        self.tracker.record(loc, old, new);
     }
 
     /// Returns the x/y/z dimension of an `Octree<T>`
@@ -121,18 +659,14 @@ where
         self.dimension
     }
 
-    /// Returns the maximum depth of an `Octree<T>`
-    pub fn max_depth(&self) -> u8 {
-        self.max_depth
-    }
-
-    /// Get a shared reference to a given `OctreeNode<T>`
-    pub fn node_as_ref(&self, loc: [u16; 3]) -> Option<&OctreeNode<T>> {
-        let mut node_loc = self.loc_from_array(loc);
-        self.root.node_as_ref(&mut node_loc)
+    /// Returns the bounds of the `Octree<T>` along the x, y and z axes
+    ///
+    /// These all equal `dimension` unless the `Octree<T>` was constructed with `new_rect`.
+    pub fn extents(&self) -> [u16; 3] {
+        self.extents
     }
 
-    /// Transform the `Octree<T>` into an iterator, consuming the `Octree<T>`
+    /// Returns the number of nodes making up the `Octree<T>`, including the root
     ///
     /// # Examples
     ///
@@ -140,124 +674,4927 @@ where
     /// # use octo::octree::Octree;
     /// #
     /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// assert_eq!(octree.node_count(), 1);
     /// octree.insert([0, 0, 0], 255).unwrap();
-    /// octree.insert([12, 10, 6], 128).unwrap();
     ///
-    /// // This will print "255, 128"
-    /// for val in octree.iter() {
-    ///     print!("{:?}", val);
-    /// }
+    /// assert_eq!(octree.node_count(), 5);
     /// ```
     ///
-    pub fn iter(&mut self) -> OctreeIterator<T> {
-        OctreeIterator::new_from_ref(&self)
+    pub fn node_count(&self) -> usize {
+        self.root.node_count()
     }
 
-    /// Create a NodeLoc from a 3-index co-ordinate array
-    fn loc_from_array(&self, array: [u16; 3]) -> NodeLoc {
-        NodeLoc::new((array[0], array[1], array[2]))
+    /// Returns the maximum depth of an `Octree<T>`
+    pub fn max_depth(&self) -> u8 {
+        self.max_depth
     }
 
-    /// Test if the `Octree<T>` bounds the given `NodeLoc`
-    fn contains_loc(&self, loc: &NodeLoc) -> bool {
-        loc.x() < self.dimension && loc.y() < self.dimension && loc.z() < self.dimension
+    /// Get a shared reference to the root `OctreeNode<T>`, for walking the structure of the
+    /// `Octree<T>` from the top
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::{Octree, OctreeNode};
+    /// #
+    /// fn count_leaves(node: &OctreeNode<u8>) -> usize {
+    ///     if node.leaf() {
+    ///         return 1;
+    ///     }
+    ///     node.children_ref().flatten().map(count_leaves).sum()
+    /// }
+    ///
+    /// let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([0, 0, 0], 255).unwrap();
+    /// octree.insert([12, 10, 6], 128).unwrap();
+    ///
+    /// assert_eq!(octree.root().dimension(), 16);
+    /// assert_eq!(count_leaves(octree.root()), 2);
+    /// ```
+    ///
+    pub fn root(&self) -> &OctreeNode<T> {
+        &self.root
     }
-}
-
-/// Struct providing iterator functionality for `Octree<T>`
-pub struct OctreeIterator<T> {
-    node_stack: Vec<OctreeNode<T>>,
-    value_stack: Vec<T>,
-}
 
-impl<T> IntoIterator for Octree<T>
-where
-    T: Copy + PartialEq,
-{
-    type Item = T;
-    type IntoIter = OctreeIterator<T>;
+    /// Get a reference to the value stored by the `Octree<T>` at a given location
+    ///
+    /// This follows `at`, returning a reference to the default value for unoccupied locations
+    /// and `None` out of bounds, but borrows the value from the `Octree<T>` instead of copying
+    /// it out, so it suits large or non-`Copy` values.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<String>::new(16).unwrap();
+    /// octree.insert([12, 10, 6], String::from("stone")).unwrap();
+    ///
+    /// assert_eq!(octree.at_ref([12, 10, 6]).map(String::as_str), Some("stone"));
+    /// assert_eq!(octree.at_ref([0, 0, 0]), None);
+    /// ```
+    ///
+    pub fn at_ref(&self, loc: [u16; 3]) -> Option<&T> {
+        let loc = self.resolve_loc(loc)?;
+        self.stored_ref(loc).or(self.default.as_ref())
+    }
 
-    /// Transform the `Octree<T>` into an iterator, consuming the `Octree<T>`
+    /// Test if a location is within the bounds of the `Octree<T>`
+    ///
+    /// Locations for which this returns `false` are rejected by `insert` with an
+    /// `OutOfBoundsError` under the default `BoundsPolicy`, so this can be used to validate
+    /// coordinates up front.
     ///
     /// # Examples
     ///
     /// ```
     /// # use octo::octree::Octree;
     /// #
-    /// # let mut octree = Octree::<u8>::new(16).unwrap();
-    /// octree.insert([0, 0, 0], 255).unwrap();
-    /// let mut iter = octree.into_iter();
+    /// # let mut octree = Octree::<u8>::new_rect([16, 16, 4]).unwrap();
+    /// let points = vec![([0, 0, 0], 255), ([12, 10, 6], 128), ([3, 2, 1], 64)];
+    /// let (valid, invalid): (Vec<_>, Vec<_>) =
+    ///     points.into_iter().partition(|&(loc, _)| octree.in_bounds(loc));
     ///
-    /// assert_eq!(iter.nth(0), Some(255));
+    /// octree.insert_many(valid).unwrap();
+    ///
+    /// assert_eq!(octree.len(), 2);
+    /// assert_eq!(invalid, vec![([12, 10, 6], 128)]);
     /// ```
     ///
-    fn into_iter(self) -> OctreeIterator<T> {
-        OctreeIterator::new(self)
+    pub fn in_bounds(&self, loc: [u16; 3]) -> bool {
+        self.contains_loc(&self.loc_from_array(loc))
     }
-}
+
+    /// Set the policy deciding how `insert`, `take`, `at` and the other single-location
+    /// accessors treat out of bounds locations
+    ///
+    /// `try_at` always rejects out of bounds locations, whatever the policy.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::{BoundsPolicy, Octree};
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.set_bounds_policy(BoundsPolicy::Clamp);
+    /// octree.insert([20, 3, 7], 255).unwrap();
+    ///
+    /// assert_eq!(octree.at([15, 3, 7]), Some(255));
+    /// ```
+    ///
+    pub fn set_bounds_policy(&mut self, policy: BoundsPolicy) {
+        self.bounds_policy = policy;
+    }
+
+    /// Returns the policy deciding how out of bounds locations are treated
+    pub fn bounds_policy(&self) -> BoundsPolicy {
+        self.bounds_policy
+    }
+
+    /// Get the location that a given location refers to under the `BoundsPolicy`, or `None` if
+    /// it is out of bounds and not clamped
+    fn resolve_loc(&self, loc: [u16; 3]) -> Option<[u16; 3]> {
+        if self.contains_loc(&self.loc_from_array(loc)) {
+            Some(loc)
+        } else if self.bounds_policy == BoundsPolicy::Clamp {
+            Some([
+                loc[0].min(self.extents[0] - 1),
+                loc[1].min(self.extents[1] - 1),
+                loc[2].min(self.extents[2] - 1),
+            ])
+        } else {
+            None
+        }
+    }
+
+    /// Get a shared reference to a given `OctreeNode<T>`
+    pub fn node_as_ref(&self, loc: [u16; 3]) -> Option<&OctreeNode<T>> {
+        let mut node_loc = self.loc_from_array(loc);
+        self.root.node_as_ref(&mut node_loc)
+    }
+
+    /// Create a NodeLoc from a 3-index co-ordinate array
+    fn loc_from_array(&self, array: [u16; 3]) -> NodeLoc {
+        NodeLoc::new((array[0], array[1], array[2]))
+    }
+
+    /// Test if the `Octree<T>` bounds the given `NodeLoc`
+    fn contains_loc(&self, loc: &NodeLoc) -> bool {
+        loc.x() < self.extents[0] && loc.y() < self.extents[1] && loc.z() < self.extents[2]
+    }
+
+    /// Get the predicate deciding when two values are close enough to be simplified together
+    fn equivalence(&self) -> impl Fn(T, T) -> bool {
+        let simplification = self.simplification;
+        let metric = self.metric;
+        move |a: T, b: T| match (simplification, metric) {
+            (SimplificationMode::Tolerance(tolerance), Some(metric)) => metric(a, b) <= tolerance,
+            _ => a == b,
+        }
+    }
+
+    /// Get a reference to the value stored at a given in-bounds location, ignoring any default
+    /// value
+    fn stored_ref(&self, loc: [u16; 3]) -> Option<&T> {
+        let mut node_loc = self.loc_from_array(loc);
+        self.root.at_ref(&mut node_loc)
+    }
+
+    /// Insert a value at an in-bounds location, starting from the cached subtree covering it
+    /// when there is one, and keep the path cache in step with the edit
+    fn insert_node(&mut self, loc: [u16; 3], data: T, equivalent: Option<&dyn Fn(T, T) -> bool>) {
+        let mut data = Some(data);
+        if let Some((origin, dimension)) = self.paths.remove_covering(loc) {
+            if let Some(subtree) = OctreeNode::descend_mut(&mut self.root, origin, dimension) {
+                let was_leaf = subtree.leaf();
+                let mut node_loc =
+                    NodeLoc::new((loc[0] - origin[0], loc[1] - origin[1], loc[2] - origin[2]));
+                Arc::make_mut(subtree).insert(&mut node_loc, data.take().unwrap(), equivalent);
+
+                // A subtree merged into a single value may let its ancestors merge too, so
+                // redo the insert from the root to simplify them
+                if !subtree.leaf() || was_leaf || dimension == self.dimension {
+                    let entry = (origin, Arc::clone(subtree));
+                    self.paths.push(entry);
+                    return;
+                }
+                data = subtree.get_ref().cloned();
+            }
+        }
+
+        if let Some(data) = data {
+            let mut node_loc = self.loc_from_array(loc);
+            self.root_mut().insert(&mut node_loc, data, equivalent);
+            if self.paths.enabled() {
+                let entry = OctreeNode::descend(&self.root, loc, PATH_CACHE_SIDE);
+                self.paths.push(entry);
+            }
+        }
+    }
+
+    /// Get mutable access to the root node, first dropping any cached paths sharing its nodes
+    fn root_mut(&mut self) -> &mut OctreeNode<T> {
+        self.paths.clear();
+        Arc::make_mut(&mut self.root)
+    }
+
+    /// Count a location about to be written by an insert in the cached occupied count, if it
+    /// is empty
+    fn count_insert(&self, loc: [u16; 3]) {
+        if self.occupied.get().is_some() && self.stored_ref(loc).is_none() {
+            self.occupied.adjust(1, 0);
+        }
+    }
+
+    /// Update the cached occupied count before clearing a location
+    fn count_clear(&self, loc: [u16; 3]) {
+        if self.occupied.get().is_some() && self.stored_ref(loc).is_some() {
+            self.occupied.adjust(0, 1);
+        }
+    }
+
+    /// Get the value stored at a given location for the change observers, if any are
+    /// registered and the location is in bounds
+    fn watched_at(&self, loc: [u16; 3]) -> Option<T> {
+        if self.tracker.watching() && self.contains_loc(&self.loc_from_array(loc)) {
+            self.stored_ref(loc).cloned()
+        } else {
+            None
+        }
+    }
+}
+
+impl<T> Octree<T>
+where
+    T: Copy + PartialEq,
+{
+    /// Constructs a new `Octree<T>` from a `HashMap` of voxel locations to values
+    ///
+    /// Returns an `OutOfBoundsError` holding the first offending location found, if any location
+    /// lies outside the `Octree<T>` bounds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// # use std::collections::HashMap;
+    /// #
+    /// let mut map = HashMap::new();
+    /// map.insert([12, 10, 6], 128);
+    /// let octree = Octree::<u8>::from_hashmap(16, &map).unwrap();
+    ///
+    /// assert_eq!(octree.at([12, 10, 6]), Some(128));
+    /// ```
+    ///
+    pub fn from_hashmap(
+        dimension: u16,
+        map: &HashMap<[u16; 3], T>,
+    ) -> Result<Octree<T>, OctreeError> {
+        let mut octree = Octree::new(dimension)?;
+        for (loc, data) in map {
+            octree.insert(*loc, *data)?;
+        }
+        Ok(octree)
+    }
+
+    /// Constructs a new `Octree<T>` bottom-up from voxels sorted by ascending Morton key
+    ///
+    /// Each octant is built once all of its voxels have arrived, and simplified as it is
+    /// completed, avoiding a descent from the root for every voxel. Keys are produced by
+    /// `math::morton_encode`, and a repeated key overwrites the earlier value.
+    ///
+    /// The input is expected to be sorted. Any voxel whose key is lower than the key before it
+    /// is still inserted, but through the ordinary `insert` path once the sorted voxels have been
+    /// built, so unsorted input is correct but slower. Returns an `OutOfBoundsError` if any key
+    /// lies outside the `Octree<T>` bounds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// # use octo::math::morton_encode;
+    /// #
+    /// let voxels = vec![
+    ///     (morton_encode([1, 0, 0]), 255),
+    ///     (morton_encode([12, 10, 6]), 128),
+    /// ];
+    /// let octree = Octree::<u8>::from_morton_sorted(16, voxels).unwrap();
+    ///
+    /// assert_eq!(octree.at([12, 10, 6]), Some(128));
+    /// ```
+    ///
+    pub fn from_morton_sorted<I: IntoIterator<Item = (u64, T)>>(
+        dimension: u16,
+        sorted: I,
+    ) -> Result<Octree<T>, OctreeError> {
+        let mut octree = Octree::new(dimension)?;
+        let depth = usize::from(octree.max_depth);
+        let within = octree.equivalence();
+
+        let mut path: MortonPath<T> = vec![];
+        let mut unsorted = vec![];
+        let mut previous = None;
+        for (key, data) in sorted {
+            let loc = morton_decode(key);
+            if key >> (3 * depth) != 0 {
+                return Err(OctreeError::OutOfBoundsError(loc));
+            }
+            if depth == 0 || previous.is_some_and(|previous| key < previous) {
+                unsorted.push((loc, data));
+                continue;
+            }
+            previous = Some(key);
+
+            while path.len() > 1 && key >> (3 * (depth + 1 - path.len())) != path.last().unwrap().0
+            {
+                Octree::finish_morton_level(&mut path, dimension, &within);
+            }
+            while path.len() < depth {
+                path.push((key >> (3 * (depth - path.len())), vec![None; 8]));
+            }
+
+            let leaf = OctreeNode::new(2, data);
+            path.last_mut().unwrap().1[morton_child(key)] = Some(Arc::new(leaf));
+        }
+
+        while path.len() > 1 {
+            Octree::finish_morton_level(&mut path, dimension, &within);
+        }
+        if let Some((_, children)) = path.pop() {
+            octree.root = Arc::new(OctreeNode::from_children(
+                dimension,
+                children,
+                Some(&within),
+            ));
+            octree.occupied.invalidate();
+        }
+
+        octree.insert_many(unsorted)?;
+        Ok(octree)
+    }
+
+    /// Constructs a new `Octree<T>` from columns of voxels, as stored by many game map formats
+    ///
+    /// Each column is an `[x, y]` location along with the `(z, value)` pairs occupied within it.
+    /// The voxels are inserted unsimplified, and the whole `Octree<T>` is simplified once they
+    /// are all in place. Returns an `OutOfBoundsError` for the first voxel outside `dimension`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// let columns = vec![([0, 0], vec![(0, 1), (1, 2)]), ([12, 10], vec![(6, 128)])];
+    /// let octree = Octree::<u8>::from_columns(16, columns).unwrap();
+    ///
+    /// assert_eq!(octree.at([0, 0, 1]), Some(2));
+    /// assert_eq!(octree.at([12, 10, 6]), Some(128));
+    /// ```
+    ///
+    pub fn from_columns<I: IntoIterator<Item = ([u16; 2], Vec<(u16, T)>)>>(
+        dimension: u16,
+        columns: I,
+    ) -> Result<Octree<T>, OctreeError> {
+        let mut octree = Octree::new(dimension)?;
+        for ([x, y], voxels) in columns {
+            for (z, data) in voxels {
+                octree.set_raw([x, y, z], data)?;
+            }
+        }
+        octree.resimplify();
+        Ok(octree)
+    }
+
+    /// Constructs a new `Octree<T>` by evaluating `f` at every voxel, leaving voxels where it
+    /// returns `None` empty
+    ///
+    /// The `Octree<T>` is built bottom-up, and uniform regions are simplified as they are
+    /// completed. Use `from_fn_with_uniform` to skip evaluating every voxel of regions known to
+    /// be uniform.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// let octree = Octree::<u8>::from_fn(16, |loc| if loc[2] < 4 { Some(255) } else { None })
+    ///     .unwrap();
+    ///
+    /// assert_eq!(octree.at([12, 10, 3]), Some(255));
+    /// assert_eq!(octree.at([12, 10, 4]), None);
+    /// ```
+    ///
+    pub fn from_fn<F: FnMut([u16; 3]) -> Option<T>>(
+        dimension: u16,
+        f: F,
+    ) -> Result<Octree<T>, OctreeError> {
+        Octree::from_fn_with_uniform(dimension, f, |_, _| false)
+    }
+
+    /// Constructs a new `Octree<T>` by evaluating `f`, sampling it only once for any region
+    /// declared uniform
+    ///
+    /// `uniform` is given the minimum corner and edge length of each node before its children
+    /// are built. If it returns `true`, `f` is evaluated at the corner alone and the result is
+    /// used for the whole node. Otherwise, each voxel of the node is evaluated as in `from_fn`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// let octree = Octree::<u8>::from_fn_with_uniform(
+    ///     16,
+    ///     |loc| if loc[0] < 8 { Some(255) } else { None },
+    ///     |_, size| size <= 8,
+    /// )
+    /// .unwrap();
+    ///
+    /// assert_eq!(octree.len(), 8 * 16 * 16);
+    /// ```
+    ///
+    pub fn from_fn_with_uniform<F, U>(
+        dimension: u16,
+        mut f: F,
+        mut uniform: U,
+    ) -> Result<Octree<T>, OctreeError>
+    where
+        F: FnMut([u16; 3]) -> Option<T>,
+        U: FnMut([u16; 3], u16) -> bool,
+    {
+        let mut octree = Octree::new(dimension)?;
+        let within = octree.equivalence();
+        let root = Octree::build_from_fn([0, 0, 0], dimension, &mut f, &mut uniform, &within);
+        if let Some(root) = root {
+            octree.root = root;
+            octree.occupied.invalidate();
+        }
+        Ok(octree)
+    }
+
+    /// Expand the simplified node covering a location into eight equal-valued children, and
+    /// the child covering the location in turn, until the location is held by its own voxel
+    ///
+    /// This is the inverse of simplification, giving algorithms per-voxel granularity around a
+    /// location before they edit it. The contents of the `Octree<T>` are unchanged. Locations
+    /// that are empty or already held by their own voxel are left as they are. Returns an
+    /// `OutOfBoundsError` if the location is out of bounds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.fill([0, 0, 0], [15, 15, 15], 255).unwrap();
+    /// assert_eq!(octree.node_count(), 1);
+    ///
+    /// octree.desimplify_at([12, 10, 6]).unwrap();
+    /// assert_eq!(octree.node_count(), 33);
+    /// assert_eq!(octree.at([12, 10, 6]), Some(255));
+    /// ```
+    ///
+    pub fn desimplify_at(&mut self, loc: [u16; 3]) -> Result<(), OctreeError> {
+        let mut node_loc = self.loc_from_array(loc);
+        if self.contains_loc(&node_loc) {
+            self.root_mut().desimplify(&mut node_loc);
+            Ok(())
+        } else {
+            Err(OctreeError::OutOfBoundsError(loc))
+        }
+    }
+
+    /// Set the value at a given location without simplifying the `Octree<T>`
+    ///
+    /// The value is stored in a leaf at the maximum depth, whatever the simplification mode.
+    /// Between calls the `Octree<T>` is valid, and answers every query correctly, but may hold
+    /// uniform regions that have not been merged into single nodes. Call `resimplify` once the
+    /// tree has been built to merge them. Setting the default value of the `Octree<T>` clears
+    /// the location instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// let mut octree = Octree::<u8>::new(2).unwrap();
+    /// for loc in (0..8).map(|i| [i & 1, (i >> 1) & 1, i >> 2]) {
+    ///     octree.set_raw(loc, 255).unwrap();
+    /// }
+    /// assert_eq!(octree.node_count(), 9);
+    ///
+    /// octree.resimplify();
+    /// assert_eq!(octree.node_count(), 1);
+    /// ```
+    ///
+    pub fn set_raw(&mut self, loc: [u16; 3], data: T) -> Result<(), OctreeError> {
+        let mut node_loc = self.loc_from_array(loc);
+        if self.contains_loc(&node_loc) {
+            if self.default == Some(data) {
+                self.count_clear(loc);
+                self.root_mut().insert_none(&mut node_loc);
+            } else {
+                self.count_insert(loc);
+                self.root_mut().insert(&mut node_loc, data, None);
+            }
+            Ok(())
+        } else {
+            Err(OctreeError::OutOfBoundsError(loc))
+        }
+    }
+
+    /// Merge every uniform region of the `Octree<T>` into a single node, as the simplification
+    /// mode would have done had each value been inserted with `insert`
+    ///
+    /// Does nothing with `SimplificationMode::Off`.
+    pub fn resimplify(&mut self) {
+        if self.simplification == SimplificationMode::Off {
+            return;
+        }
+
+        let within = self.equivalence();
+        self.root_mut().resimplify(&within);
+    }
+
+    /// Insert a value into the `Octree<T>`, first growing it until it bounds the location
+    ///
+    /// The `Octree<T>` is re-rooted at double its dimension, like a larger tree holding the old
+    /// one in its first octant, until the location fits. Every extent equal to the dimension
+    /// grows with it, while smaller extents of a `new_rect` tree are doubled on their own first.
+    /// Existing content keeps its coordinates. Returns an `OutOfBoundsError` without modifying the `Octree<T>` if
+    /// the location lies beyond the largest possible dimension of 32768.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([0, 0, 0], 255).unwrap();
+    /// octree.insert_growing([40, 0, 3], 128).unwrap();
+    ///
+    /// assert_eq!(octree.dimension(), 64);
+    /// assert_eq!(octree.at([0, 0, 0]), Some(255));
+    /// assert_eq!(octree.at([40, 0, 3]), Some(128));
+    /// ```
+    ///
+    pub fn insert_growing(&mut self, loc: [u16; 3], data: T) -> Result<(), OctreeError> {
+        let mut extents = self.extents;
+        let mut dimension = self.dimension;
+        while let Some(axis) = (0..3).find(|&axis| loc[axis] >= extents[axis]) {
+            if extents[axis] < dimension {
+                extents[axis] *= 2;
+                continue;
+            }
+
+            let grown = dimension
+                .checked_mul(2)
+                .ok_or(OctreeError::OutOfBoundsError(loc))?;
+            for extent in extents.iter_mut().filter(|extent| **extent == dimension) {
+                *extent = grown;
+            }
+            dimension = grown;
+        }
+
+        self.paths.clear();
+        while self.dimension < dimension {
+            let mut children = vec![None; 8];
+            let root = mem::replace(
+                &mut self.root,
+                Arc::new(OctreeNode::construct_root(self.dimension)),
+            );
+            if !(root.leaf() && root.get().is_none()) {
+                children[0] = Some(root);
+            }
+            self.dimension *= 2;
+            self.max_depth += 1;
+            self.root = Arc::new(OctreeNode::from_children(self.dimension, children, None));
+        }
+        self.extents = extents;
+        self.insert(loc, data)
+    }
+
+    /// Insert `data` at a location only if it is currently unoccupied
+    ///
+    /// Returns `Ok(true)` if `data` was inserted, or `Ok(false)` if the location already held a
+    /// value, which is left unchanged. Locations holding only the default value count as
+    /// unoccupied. Out of bounds locations are handled according to the `BoundsPolicy`, as by
+    /// `insert`, and return `Ok(false)` when ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// assert_eq!(octree.insert_if_empty([0, 0, 0], 255), Ok(true));
+    /// assert_eq!(octree.insert_if_empty([0, 0, 0], 128), Ok(false));
+    /// ```
+    ///
+    pub fn insert_if_empty(&mut self, loc: [u16; 3], data: T) -> Result<bool, OctreeError> {
+        let loc = match self.resolve_loc(loc) {
+            Some(loc) => loc,
+            None if self.bounds_policy == BoundsPolicy::Ignore => return Ok(false),
+            None => return Err(OctreeError::OutOfBoundsError(loc)),
+        };
+
+        if self.stored_at(loc).is_some() {
+            Ok(false)
+        } else {
+            self.insert(loc, data)?;
+            Ok(true)
+        }
+    }
+
+    /// Insert every location and value pair from an iterator, in order
+    ///
+    /// Stops at the first location outside the `Octree<T>` and returns an `OutOfBoundsError`
+    /// for it, keeping the values inserted before it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree
+    ///     .insert_many(vec![([0, 0, 0], 255), ([12, 10, 6], 128)])
+    ///     .unwrap();
+    ///
+    /// assert_eq!(octree.len(), 2);
+    /// ```
+    ///
+    pub fn insert_many<I: IntoIterator<Item = ([u16; 3], T)>>(
+        &mut self,
+        items: I,
+    ) -> Result<(), OctreeError> {
+        for (loc, data) in items {
+            self.insert(loc, data)?;
+        }
+        Ok(())
+    }
+
+    /// Insert every location and value pair from an iterator, or none of them
+    ///
+    /// Every location is checked before anything is inserted, so if any is outside the
+    /// `Octree<T>`, an `OutOfBoundsError` for the first such location is returned and the
+    /// `Octree<T>` is left unmodified. Unlike `insert_many`, this buffers the whole iterator.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// # use octo::OctreeError;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// let result = octree.try_insert_all(vec![([0, 0, 0], 255), ([16, 0, 0], 128)]);
+    ///
+    /// assert_eq!(result, Err(OctreeError::OutOfBoundsError([16, 0, 0])));
+    /// assert!(octree.is_empty());
+    /// ```
+    ///
+    pub fn try_insert_all<I: IntoIterator<Item = ([u16; 3], T)>>(
+        &mut self,
+        items: I,
+    ) -> Result<(), OctreeError> {
+        let items: Vec<([u16; 3], T)> = items.into_iter().collect();
+        if let Some(&(loc, _)) = items
+            .iter()
+            .find(|(loc, _)| !self.contains_loc(&self.loc_from_array(*loc)))
+        {
+            return Err(OctreeError::OutOfBoundsError(loc));
+        }
+        self.insert_many(items)
+    }
+
+    /// Insert `data` at every voxel along a straight line between two locations, inclusive
+    ///
+    /// The line is rasterized using 3D Bresenham. Returns an `OutOfBoundsError` without
+    /// inserting anything if either endpoint is outside the `Octree<T>`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert_line([0, 0, 0], [3, 0, 0], 255).unwrap();
+    ///
+    /// assert_eq!(octree.at([2, 0, 0]), Some(255));
+    /// assert_eq!(octree.len(), 4);
+    /// ```
+    ///
+    pub fn insert_line(
+        &mut self,
+        from: [u16; 3],
+        to: [u16; 3],
+        data: T,
+    ) -> Result<(), OctreeError> {
+        for loc in &[from, to] {
+            if !self.contains_loc(&self.loc_from_array(*loc)) {
+                return Err(OctreeError::OutOfBoundsError(*loc));
+            }
+        }
+
+        let mut curr = [i32::from(from[0]), i32::from(from[1]), i32::from(from[2])];
+        let end = [i32::from(to[0]), i32::from(to[1]), i32::from(to[2])];
+        let delta = [
+            (end[0] - curr[0]).abs(),
+            (end[1] - curr[1]).abs(),
+            (end[2] - curr[2]).abs(),
+        ];
+        let step = [
+            (end[0] - curr[0]).signum(),
+            (end[1] - curr[1]).signum(),
+            (end[2] - curr[2]).signum(),
+        ];
+
+        let driving = (0..3).max_by_key(|&axis| delta[axis]).unwrap();
+        let others = [(driving + 1) % 3, (driving + 2) % 3];
+        let mut errors = [
+            2 * delta[others[0]] - delta[driving],
+            2 * delta[others[1]] - delta[driving],
+        ];
+
+        for _ in 0..=delta[driving] {
+            self.insert([curr[0] as u16, curr[1] as u16, curr[2] as u16], data)?;
+
+            for (i, &axis) in others.iter().enumerate() {
+                if errors[i] > 0 {
+                    curr[axis] += step[axis];
+                    errors[i] -= 2 * delta[driving];
+                }
+                errors[i] += 2 * delta[axis];
+            }
+            curr[driving] += step[driving];
+        }
+
+        Ok(())
+    }
+
+    /// Set every location within the inclusive box `min`..=`max` to `data`
+    ///
+    /// Nodes entirely inside the box are set as a whole, so large regions are filled without
+    /// visiting each voxel. Filling with the default value of the `Octree<T>` clears the box
+    /// instead. The box is added to the dirty bounds, and changed locations are reported to the
+    /// `set_on_change` callback. A box with `min` above `max` on any axis is empty, so nothing
+    /// is filled. Returns an `OutOfBoundsError` if either corner is out of bounds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.fill([0, 0, 0], [7, 7, 7], 255).unwrap();
+    ///
+    /// assert_eq!(octree.at([5, 6, 7]), Some(255));
+    /// assert_eq!(octree.len(), 512);
+    /// ```
+    ///
+    pub fn fill(&mut self, min: [u16; 3], max: [u16; 3], data: T) -> Result<(), OctreeError> {
+        for loc in &[min, max] {
+            if !self.contains_loc(&self.loc_from_array(*loc)) {
+                return Err(OctreeError::OutOfBoundsError(*loc));
+            }
+        }
+        if (0..3).any(|axis| min[axis] > max[axis]) {
+            return Ok(());
+        }
+        if self.default == Some(data) {
+            return self.map_region(min, max, |_, _| None);
+        }
+
+        match self.simplification {
+            SimplificationMode::Off => {
+                for z in min[2]..=max[2] {
+                    for y in min[1]..=max[1] {
+                        for x in min[0]..=max[0] {
+                            self.insert([x, y, z], data)?;
+                        }
+                    }
+                }
+            }
+            _ => {
+                if self.occupied.get().is_some() {
+                    let before = self.root.count_occupied([0, 0, 0], min, max);
+                    let volume = (0..3)
+                        .map(|axis| u64::from(max[axis] - min[axis]) + 1)
+                        .product::<u64>();
+                    self.occupied.adjust(volume, before);
+                }
+                let within = self.equivalence();
+                let mut changes = vec![];
+                if self.tracker.watching() {
+                    self.root.for_each_differing(
+                        [0, 0, 0],
+                        min,
+                        max,
+                        data,
+                        &within,
+                        &mut |min, max, old| changes.push((min, max, old)),
+                    );
+                }
+                self.tracker.extend_dirty(min);
+                self.tracker.extend_dirty(max);
+                for (min, max, old) in changes {
+                    self.tracker.record_region(min, max, old, Some(data));
+                }
+
+                self.root_mut().fill([0, 0, 0], min, max, data, &within);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Set every location within the inclusive box `min`..=`max` to `data`, writing only the
+    /// locations not already holding `data`, and return the number of locations changed
+    ///
+    /// Simplified nodes already holding `data` are skipped without visiting their voxels. Only
+    /// the changed locations are reported to the `set_on_change` callback and the dirty bounds,
+    /// so filling an already filled box leaves them untouched. Under
+    /// `SimplificationMode::Tolerance`, locations holding a value within tolerance of `data`
+    /// count as unchanged, as they do for `fill`. Filling with the default value of
+    /// the `Octree<T>` clears the box instead, counting the occupied locations cleared. A box
+    /// with `min` above `max` on any axis is empty, changing nothing. Returns an
+    /// `OutOfBoundsError` if either corner is out of bounds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// assert_eq!(octree.fill_changed([0, 0, 0], [7, 7, 7], 255).unwrap(), 512);
+    /// assert_eq!(octree.fill_changed([4, 4, 4], [11, 11, 11], 255).unwrap(), 448);
+    /// ```
+    ///
+    pub fn fill_changed(
+        &mut self,
+        min: [u16; 3],
+        max: [u16; 3],
+        data: T,
+    ) -> Result<usize, OctreeError> {
+        for loc in &[min, max] {
+            if !self.contains_loc(&self.loc_from_array(*loc)) {
+                return Err(OctreeError::OutOfBoundsError(*loc));
+            }
+        }
+        if (0..3).any(|axis| min[axis] > max[axis]) {
+            return Ok(0);
+        }
+
+        if self.default == Some(data) {
+            let cleared = self.root.count_occupied([0, 0, 0], min, max);
+            self.map_region(min, max, |_, _| None)?;
+            return Ok(cleared as usize);
+        }
+
+        let within = self.equivalence();
+        let mut regions = vec![];
+        self.root
+            .for_each_differing([0, 0, 0], min, max, data, &within, &mut |min, max, old| {
+                regions.push((min, max, old))
+            });
+        if regions.is_empty() {
+            return Ok(0);
+        }
+
+        let mut changed = 0;
+        for &(min, max, old) in &regions {
+            let volume = (0..3)
+                .map(|axis| u64::from(max[axis] - min[axis]) + 1)
+                .product::<u64>();
+            let removed = if old.is_some() { volume } else { 0 };
+            self.occupied.adjust(volume, removed);
+            self.tracker.record_region(min, max, old, Some(data));
+            changed += volume as usize;
+        }
+
+        match self.simplification {
+            SimplificationMode::Off => {
+                for (min, max, _) in regions {
+                    for z in min[2]..=max[2] {
+                        for y in min[1]..=max[1] {
+                            for x in min[0]..=max[0] {
+                                let mut node_loc = self.loc_from_array([x, y, z]);
+                                self.root_mut().insert(&mut node_loc, data, None);
+                            }
+                        }
+                    }
+                }
+            }
+            _ => self.root_mut().fill([0, 0, 0], min, max, data, &within),
+        }
+
+        Ok(changed)
+    }
+
+    /// Replace the value of every location within the inclusive box `min`..=`max` with `f` of
+    /// its location and current value, clearing locations where `f` returns `None`
+    ///
+    /// `f` is called once per location, and is given `None` for empty locations, ignoring any
+    /// default value. Returning the default value clears the location. Simplified nodes overlapping the box are split as needed, and the affected
+    /// nodes are simplified again afterwards. Changes are reported to the `set_on_change`
+    /// callback and the dirty bounds. Returns an `OutOfBoundsError` if either corner is out of
+    /// bounds, leaving the `Octree<T>` unchanged.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.fill([0, 0, 0], [3, 3, 3], 1).unwrap();
+    /// octree
+    ///     .map_region([2, 0, 0], [5, 0, 0], |_, value| Some(value.unwrap_or(0) + 10))
+    ///     .unwrap();
+    ///
+    /// assert_eq!(octree.at([1, 0, 0]), Some(1));
+    /// assert_eq!(octree.at([3, 0, 0]), Some(11));
+    /// assert_eq!(octree.at([5, 0, 0]), Some(10));
+    /// ```
+    ///
+    pub fn map_region<F: FnMut([u16; 3], Option<T>) -> Option<T>>(
+        &mut self,
+        min: [u16; 3],
+        max: [u16; 3],
+        mut f: F,
+    ) -> Result<(), OctreeError> {
+        for loc in &[min, max] {
+            if !self.contains_loc(&self.loc_from_array(*loc)) {
+                return Err(OctreeError::OutOfBoundsError(*loc));
+            }
+        }
+
+        let within = self.equivalence();
+        let equivalent: Option<&dyn Fn(T, T) -> bool> = match self.simplification {
+            SimplificationMode::Off => None,
+            _ => Some(&within),
+        };
+        let default = self.default;
+        let mut changes = vec![];
+        self.root_mut().map_region(
+            [0, 0, 0],
+            min,
+            max,
+            &mut |loc, old| {
+                let new = f(loc, old).filter(|value| Some(*value) != default);
+                if new != old {
+                    changes.push((loc, old, new));
+                }
+                new
+            },
+            equivalent,
+        );
+
+        for (loc, old, new) in changes {
+            self.occupied
+                .adjust(u64::from(old.is_none()), u64::from(new.is_none()));
+            self.tracker.record(loc, old, new);
+        }
+        Ok(())
+    }
+
+    /// Set every location on an axis-aligned plane to `data`
+    ///
+    /// The plane is the slice of the `Octree<T>` where the co-ordinate on `axis` equals `coord`.
+    /// Returns an `OutOfBoundsError` if `coord` is out of bounds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::{Axis, Octree};
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert_plane(Axis::Y, 3, 255).unwrap();
+    ///
+    /// assert_eq!(octree.at([9, 3, 12]), Some(255));
+    /// assert_eq!(octree.at([9, 4, 12]), None);
+    /// ```
+    ///
+    pub fn insert_plane(&mut self, axis: Axis, coord: u16, data: T) -> Result<(), OctreeError> {
+        let mut min = [0, 0, 0];
+        let mut max = [
+            self.extents[0] - 1,
+            self.extents[1] - 1,
+            self.extents[2] - 1,
+        ];
+        min[axis as usize] = coord;
+        max[axis as usize] = coord;
+
+        if coord >= self.extents[axis as usize] {
+            return Err(OctreeError::OutOfBoundsError(min));
+        }
+        self.fill(min, max, data)
+    }
+
+    /// Replace every occupied voxel holding `from` with `to`, returning the number of voxels
+    /// changed
+    ///
+    /// Regions made uniform by the replacement are simplified according to the
+    /// `SimplificationMode` of the `Octree<T>`. The default value is left unchanged, and
+    /// replacing with it clears the matching voxels instead, as `insert` does. Changes are
+    /// reported to the `set_on_change` callback and the dirty bounds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([0, 0, 0], 1).unwrap();
+    /// octree.insert([12, 10, 6], 1).unwrap();
+    ///
+    /// assert_eq!(octree.replace_value(1, 2), 2);
+    /// assert_eq!(octree.at([12, 10, 6]), Some(2));
+    /// ```
+    ///
+    pub fn replace_value(&mut self, from: T, to: T) -> usize {
+        if from == to || !self.contains_value(from) {
+            return 0;
+        }
+
+        let mut blocks = vec![];
+        self.root
+            .for_each_leaf([0, 0, 0], &mut |origin, size, data| {
+                if data == from {
+                    blocks.push((origin, size));
+                }
+            });
+        let extents = self.extents;
+        let last = |origin: [u16; 3], size: u16| {
+            [
+                (origin[0] + (size - 1)).min(extents[0] - 1),
+                (origin[1] + (size - 1)).min(extents[1] - 1),
+                (origin[2] + (size - 1)).min(extents[2] - 1),
+            ]
+        };
+
+        if self.default == Some(to) {
+            let mut replaced = 0;
+            for (origin, size) in blocks {
+                self.map_region(origin, last(origin, size), |_, _| None)
+                    .unwrap();
+                replaced += usize::from(size).pow(3);
+            }
+            return replaced;
+        }
+
+        let within = self.equivalence();
+        let equivalent: Option<&dyn Fn(T, T) -> bool> = match self.simplification {
+            SimplificationMode::Off => None,
+            _ => Some(&within),
+        };
+        let replaced = self.root_mut().replace(from, to, equivalent);
+        for (origin, size) in blocks {
+            self.tracker
+                .record_region(origin, last(origin, size), Some(from), Some(to));
+        }
+        replaced
+    }
+
+    /// Get the value stored by the `Octree<T>` at a given node
+    ///
+    /// Unoccupied locations return the default value if the `Octree<T>` has one, or `None`
+    /// otherwise. Out of bounds locations return `None`, unless the `BoundsPolicy` is
+    /// `BoundsPolicy::Clamp`, in which case they read the nearest in bounds cell.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([0, 0, 0], 255).unwrap();
+    /// assert_eq!(octree.at([0, 0, 0]), Some(255));
+    /// ```
+    ///
+    pub fn at(&self, loc: [u16; 3]) -> Option<T> {
+        let loc = self.resolve_loc(loc)?;
+        self.stored_at(loc).or(self.default)
+    }
+
+    /// Get the value stored by the `Octree<T>` at a given node through the path cache
+    ///
+    /// This follows `at`, but starts from the cached subtree covering the location when there
+    /// is one, caching the subtree walked through otherwise. See `enable_path_cache`. Without
+    /// a path cache this is the same as `at`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.enable_path_cache(4);
+    /// octree.insert([12, 10, 6], 255).unwrap();
+    ///
+    /// assert_eq!(octree.at_cached([12, 10, 6]), Some(255));
+    /// assert_eq!(octree.at_cached([12, 10, 7]), None);
+    /// ```
+    ///
+    pub fn at_cached(&mut self, loc: [u16; 3]) -> Option<T> {
+        let loc = self.resolve_loc(loc)?;
+        if !self.paths.enabled() {
+            return self.stored_at(loc).or(self.default);
+        }
+        self.paths.at(&self.root, loc).or(self.default)
+    }
+
+    /// Cache the subtrees walked through by the last `entries` lookups, so that lookups near a
+    /// recent one skip the upper levels of the `Octree<T>`
+    ///
+    /// This speeds up spatially coherent access, such as sampling neighbouring locations.
+    /// `at_cached` and `insert` consult the cache, and `insert` updates the cached subtree it
+    /// edits rather than dropping the cache. Reads through `&self`, such as `at`, always descend
+    /// from the root, and the other edits drop every cached subtree. Passing 0 disables the
+    /// cache, which is the default.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.enable_path_cache(4);
+    /// for x in 8..12 {
+    ///     octree.insert([x, 10, 6], 255).unwrap();
+    /// }
+    ///
+    /// assert_eq!(octree.at_cached([10, 10, 6]), Some(255));
+    /// assert_eq!(octree.at_cached([12, 10, 6]), None);
+    /// ```
+    ///
+    pub fn enable_path_cache(&mut self, entries: usize) {
+        self.paths = PathCache::new(entries);
+    }
+
+    /// Get the value stored by the `Octree<T>` at a given node, distinguishing out of bounds
+    /// locations from empty ones
+    ///
+    /// Returns an `OutOfBoundsError` if the location is outside the `Octree<T>`, rather than the
+    /// `None` returned by `at`. This validates the location as given, so it ignores the
+    /// `BoundsPolicy` and never clamps.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([0, 0, 0], 255).unwrap();
+    ///
+    /// assert_eq!(octree.try_at([0, 0, 0]), Ok(Some(255)));
+    /// assert_eq!(octree.try_at([0, 0, 1]), Ok(None));
+    /// assert!(octree.try_at([0, 0, 16]).is_err());
+    /// ```
+    ///
+    pub fn try_at(&self, loc: [u16; 3]) -> Result<Option<T>, OctreeError> {
+        if self.contains_loc(&self.loc_from_array(loc)) {
+            Ok(self.at(loc))
+        } else {
+            Err(OctreeError::OutOfBoundsError(loc))
+        }
+    }
+
+    /// Get the values stored by the `Octree<T>` at many locations, in the order given
+    ///
+    /// Queries are grouped by octant so that branches shared between locations are only walked
+    /// once, which pays off for spatially coherent batches. Out of bounds locations yield `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([0, 0, 0], 255).unwrap();
+    /// octree.insert([12, 10, 6], 128).unwrap();
+    ///
+    /// let values = octree.at_many(&[[12, 10, 6], [1, 1, 1], [0, 0, 0]]);
+    /// assert_eq!(values, vec![Some(128), None, Some(255)]);
+    /// ```
+    ///
+    pub fn at_many(&self, locs: &[[u16; 3]]) -> Vec<Option<T>> {
+        let mut results = vec![None; locs.len()];
+        let mut queries = vec![];
+        for (index, loc) in locs.iter().enumerate() {
+            let node_loc = self.loc_from_array(*loc);
+            if self.contains_loc(&node_loc) {
+                results[index] = self.default;
+                queries.push((index, node_loc));
+            }
+        }
+
+        self.root.at_many(queries, &mut results);
+        results
+    }
+
+    /// Get the location and value of each of the six face neighbors of a location
+    ///
+    /// Neighbors are returned in the order -X, +X, -Y, +Y, -Z, +Z. The location is `None` for
+    /// a neighbor outside the `Octree<T>`, and the value is `None` if the neighbor is out of
+    /// bounds or empty, following `at`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([1, 0, 0], 255).unwrap();
+    /// let neighbors = octree.neighbors6_coords([0, 0, 0]);
+    ///
+    /// assert_eq!(neighbors[0], (None, None));
+    /// assert_eq!(neighbors[1], (Some([1, 0, 0]), Some(255)));
+    /// ```
+    ///
+    pub fn neighbors6_coords(&self, loc: [u16; 3]) -> [(Option<[u16; 3]>, Option<T>); 6] {
+        let mut neighbors = [(None, None); 6];
+        for axis in 0..3 {
+            for (side, delta) in [-1i32, 1].iter().enumerate() {
+                let coord = i32::from(loc[axis]) + delta;
+                if coord < 0 || coord > i32::from(u16::MAX) {
+                    continue;
+                }
+
+                let mut neighbor = loc;
+                neighbor[axis] = coord as u16;
+                if self.contains_loc(&self.loc_from_array(neighbor)) {
+                    neighbors[axis * 2 + side] = (Some(neighbor), self.at(neighbor));
+                }
+            }
+        }
+        neighbors
+    }
+
+    /// Remove and return the value stored at a given location, only if it satisfies `predicate`
+    ///
+    /// Returns `None`, leaving the `Octree<T>` unchanged, if the location is empty or out of
+    /// bounds, or if `predicate` returns `false`. Default values are never removed. Under
+    /// `BoundsPolicy::Clamp`, out of bounds locations refer to the nearest in bounds cell.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([0, 0, 0], 255).unwrap();
+    ///
+    /// assert_eq!(octree.take_if([0, 0, 0], |value| value < 128), None);
+    /// assert_eq!(octree.take_if([0, 0, 0], |value| value > 128), Some(255));
+    /// assert_eq!(octree.at([0, 0, 0]), None);
+    /// ```
+    ///
+    pub fn take_if<F: FnOnce(T) -> bool>(&mut self, loc: [u16; 3], predicate: F) -> Option<T> {
+        let loc = self.resolve_loc(loc)?;
+        match self.stored_at(loc) {
+            Some(value) if predicate(value) => self.take(loc),
+            _ => None,
+        }
+    }
+
+    /// Returns the number of occupied voxels in the `Octree<T>`, counting every voxel covered
+    /// by a simplified node
+    ///
+    /// The count is cached and kept up to date by `insert`, `take`, `insert_none`, `fill` and
+    /// `map_region`, so this is O(1) between them. After edits that cannot count their own
+    /// changes, such as `restore` or loading a serialized `Octree<T>`, the next call counts the
+    /// voxels again.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([0, 0, 0], 255).unwrap();
+    /// octree.insert([12, 10, 6], 128).unwrap();
+    ///
+    /// assert_eq!(octree.len(), 2);
+    /// ```
+    ///
+    pub fn len(&self) -> usize {
+        self.volume() as usize
+    }
+
+    /// Returns the total number of occupied voxels as a `u64`, counting every voxel covered by
+    /// a simplified node
+    ///
+    /// This is the same count as `len`, but cannot overflow for the largest `Octree<T>`
+    /// dimensions.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.fill([0, 0, 0], [7, 7, 7], 255).unwrap();
+    ///
+    /// assert_eq!(octree.volume(), 512);
+    /// ```
+    ///
+    pub fn volume(&self) -> u64 {
+        if let Some(volume) = self.occupied.get() {
+            return volume;
+        }
+
+        let mut volume = 0;
+        self.root.for_each_leaf([0, 0, 0], &mut |_, size, _| {
+            volume += u64::from(size).pow(3);
+        });
+        self.occupied.set(volume);
+        volume
+    }
+
+    /// Returns `true` if the `Octree<T>` has no occupied voxels
+    ///
+    /// Answered from the cached occupied count when it is known, and otherwise by stopping at
+    /// the first occupied leaf.
+    pub fn is_empty(&self) -> bool {
+        match self.occupied.get() {
+            Some(volume) => volume == 0,
+            None => !self.root.any_leaf(&mut |_| true),
+        }
+    }
+
+    /// Get every occupied voxel within the inclusive box `min`..=`max`, with its value
+    ///
+    /// Parts of the box outside the `Octree<T>` are ignored, and subtrees outside the box are
+    /// skipped without being visited.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([2, 2, 2], 255).unwrap();
+    /// octree.insert([12, 10, 6], 128).unwrap();
+    ///
+    /// assert_eq!(octree.query_region([0, 0, 0], [7, 7, 7]), vec![([2, 2, 2], 255)]);
+    /// ```
+    ///
+    pub fn query_region(&self, min: [u16; 3], max: [u16; 3]) -> Vec<([u16; 3], T)> {
+        let mut voxels = vec![];
+        self.root
+            .for_each_occupied([0, 0, 0], min, max, &mut |loc, data| {
+                voxels.push((loc, data))
+            });
+        voxels
+    }
+
+    /// Get every occupied voxel whose center lies within `radius` of `center`, with its value
+    ///
+    /// `center` is given in voxel coordinates, so the center of the voxel at `[x, y, z]` is
+    /// `[x + 0.5, y + 0.5, z + 0.5]`. Subtrees entirely outside the sphere are skipped without
+    /// being visited, and only the part of a simplified block near the sphere is expanded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([4, 4, 4], 255).unwrap();
+    /// octree.insert([6, 4, 4], 128).unwrap();
+    ///
+    /// assert_eq!(octree.query_sphere([4.5, 4.5, 4.5], 1.5), vec![([4, 4, 4], 255)]);
+    /// ```
+    ///
+    pub fn query_sphere(&self, center: [f32; 3], radius: f32) -> Vec<([u16; 3], T)> {
+        let mut voxels = vec![];
+        if radius >= 0.0 {
+            self.sphere_voxels_in(&self.root, [0, 0, 0], center, radius, &mut voxels);
+        }
+        voxels
+    }
+
+    /// Test if any voxel within the inclusive box `min`..=`max` is occupied
+    ///
+    /// Only nodes intersecting the box are visited, and the search stops at the first occupied
+    /// leaf found, so a simplified block overlapping the box answers immediately. Parts of the
+    /// box outside the `Octree<T>` are ignored, and default values do not count as occupied.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([12, 10, 6], 128).unwrap();
+    ///
+    /// assert!(octree.any_in_region([8, 8, 0], [15, 15, 7]));
+    /// assert!(!octree.any_in_region([0, 0, 0], [7, 7, 7]));
+    /// ```
+    ///
+    pub fn any_in_region(&self, min: [u16; 3], max: [u16; 3]) -> bool {
+        let mut clamped = max;
+        for axis in 0..3 {
+            clamped[axis] = max[axis].min(self.extents[axis] - 1);
+            if min[axis] > clamped[axis] {
+                return false;
+            }
+        }
+        self.root.any_occupied([0, 0, 0], min, clamped)
+    }
+
+    /// Count the voxels within the inclusive box `min`..=`max` holding `value`
+    ///
+    /// Simplified blocks are counted by their overlap with the box without visiting their
+    /// voxels. Parts of the box outside the `Octree<T>` are ignored, and empty voxels are never
+    /// counted, whatever the default value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.fill([0, 0, 0], [7, 7, 7], 1).unwrap();
+    /// octree.insert([12, 10, 6], 1).unwrap();
+    ///
+    /// assert_eq!(octree.count_value_in_region([4, 4, 4], [15, 15, 15], 1), 65);
+    /// ```
+    ///
+    pub fn count_value_in_region(&self, min: [u16; 3], max: [u16; 3], value: T) -> usize {
+        let mut clamped = max;
+        for axis in 0..3 {
+            clamped[axis] = max[axis].min(self.extents[axis] - 1);
+            if min[axis] > clamped[axis] {
+                return 0;
+            }
+        }
+        self.root
+            .count_where([0, 0, 0], min, clamped, &|data| data == value) as usize
+    }
+
+    /// Test if any occupied voxel overlaps a box given in physical units
+    ///
+    /// The box is converted to voxel locations with the `origin` and `voxel_size` of the
+    /// `OctreeMeta`, where the voxel at `[x, y, z]` covers from `origin + [x, y, z] * voxel_size`
+    /// up to, but not including, one `voxel_size` further along each axis. Parts of the box
+    /// outside the `Octree<T>` are ignored, as in `any_in_region`, and an inverted box never
+    /// intersects.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.meta_mut().voxel_size = 0.5;
+    /// octree.insert([4, 2, 2], 255).unwrap();
+    ///
+    /// assert!(octree.intersects_aabb([2.2, 1.1, 1.1], [2.3, 1.2, 1.2]));
+    /// assert!(!octree.intersects_aabb([2.5, 1.1, 1.1], [3.0, 1.2, 1.2]));
+    /// ```
+    ///
+    pub fn intersects_aabb(&self, world_min: [f32; 3], world_max: [f32; 3]) -> bool {
+        let low = self.meta.to_voxel_space(world_min);
+        let high = self.meta.to_voxel_space(world_max);
+
+        let mut min = [0; 3];
+        let mut max = [0; 3];
+        for axis in 0..3 {
+            let (first, last) = (low[axis].floor(), high[axis].floor());
+            let outside = last < 0.0 || first >= f32::from(self.extents[axis]);
+            if world_min[axis] > world_max[axis] || outside {
+                return false;
+            }
+            min[axis] = first.max(0.0) as u16;
+            max[axis] = last.min(f32::from(u16::MAX)) as u16;
+        }
+        self.any_in_region(min, max)
+    }
+
+    /// Iterate over the locations occupied in exactly one of this `Octree<T>` and `other`,
+    /// whatever their values
+    ///
+    /// Both trees are traversed in lockstep, skipping subtrees they share and matching
+    /// simplified blocks without visiting their voxels. The locations are gathered before the
+    /// iterator is returned. Default values do not count as occupied.
+    ///
+    /// Returns a `DimensionError` if the extents of the two `Octree<T>`s differ.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// let mut a = Octree::<u8>::new(16).unwrap();
+    /// let mut b = Octree::<u8>::new(16).unwrap();
+    /// a.insert([0, 0, 0], 255).unwrap();
+    /// b.insert([0, 0, 0], 128).unwrap();
+    /// b.insert([12, 10, 6], 128).unwrap();
+    ///
+    /// let difference: Vec<_> = a.symmetric_difference(&b).unwrap().collect();
+    /// assert_eq!(difference, vec![[12, 10, 6]]);
+    /// ```
+    ///
+    pub fn symmetric_difference<'a>(
+        &'a self,
+        other: &'a Octree<T>,
+    ) -> Result<impl Iterator<Item = [u16; 3]> + 'a, OctreeError> {
+        if self.extents != other.extents {
+            return Err(OctreeError::DimensionError);
+        }
+
+        let mut locs = vec![];
+        OctreeNode::for_each_occupancy_difference(
+            Some(&self.root),
+            Some(&other.root),
+            [0, 0, 0],
+            self.dimension,
+            &mut |loc| locs.push(loc),
+        );
+        Ok(locs.into_iter())
+    }
+
+    /// Iterate over every location occupied in either this `Octree<T>` or `other`, with the
+    /// value each holds there
+    ///
+    /// Each location is yielded once with `None` on the side where it is empty, including
+    /// locations where both values are equal. Both trees are traversed in lockstep, and the
+    /// locations are gathered before the iterator is returned. Default values do not count as
+    /// occupied, and are not substituted for empty sides.
+    ///
+    /// Returns a `DimensionError` if the extents of the two `Octree<T>`s differ.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// let mut a = Octree::<u8>::new(16).unwrap();
+    /// let mut b = Octree::<u8>::new(16).unwrap();
+    /// a.insert([0, 0, 0], 255).unwrap();
+    /// b.insert([0, 0, 0], 128).unwrap();
+    /// b.insert([12, 10, 6], 128).unwrap();
+    ///
+    /// let joined: Vec<_> = a.join(&b).unwrap().collect();
+    /// assert_eq!(
+    ///     joined,
+    ///     vec![([0, 0, 0], Some(255), Some(128)), ([12, 10, 6], None, Some(128))]
+    /// );
+    /// ```
+    ///
+    pub fn join<'a>(
+        &'a self,
+        other: &'a Octree<T>,
+    ) -> Result<impl Iterator<Item = JoinedCell<T>> + 'a, OctreeError> {
+        if self.extents != other.extents {
+            return Err(OctreeError::DimensionError);
+        }
+
+        let max = [
+            self.extents[0] - 1,
+            self.extents[1] - 1,
+            self.extents[2] - 1,
+        ];
+        let mut cells = vec![];
+        OctreeNode::for_each_joined(
+            Some(&self.root),
+            Some(&other.root),
+            [0, 0, 0],
+            self.dimension,
+            max,
+            &mut |loc, a, b| cells.push((loc, a, b)),
+        );
+        Ok(cells.into_iter())
+    }
+
+    /// Lazily iterate over every occupied voxel within the inclusive box `min`..=`max`, with its
+    /// value
+    ///
+    /// The box is clamped to the bounds of the `Octree<T>` rather than rejected, so it may
+    /// extend past the edge. Subtrees outside the box are skipped, and each node is only
+    /// visited as the iterator reaches it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([15, 15, 15], 255).unwrap();
+    /// let mut region = octree.iter_region([12, 12, 12], [40, 40, 40]);
+    ///
+    /// assert_eq!(region.next(), Some(([15, 15, 15], 255)));
+    /// assert_eq!(region.next(), None);
+    /// ```
+    ///
+    pub fn iter_region(
+        &self,
+        min: [u16; 3],
+        max: [u16; 3],
+    ) -> impl Iterator<Item = ([u16; 3], T)> + '_ {
+        let max = [
+            max[0].min(self.extents[0] - 1),
+            max[1].min(self.extents[1] - 1),
+            max[2].min(self.extents[2] - 1),
+        ];
+        let stack = if (0..3).all(|axis| min[axis] <= max[axis]) {
+            vec![(&*self.root, [0, 0, 0])]
+        } else {
+            vec![]
+        };
+
+        RegionIterator {
+            min,
+            max,
+            stack,
+            block: None,
+        }
+    }
+
+    /// Iterate over every occupied voxel of the `Octree<T>` with its location
+    ///
+    /// Simplified nodes are expanded into their individual voxels.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([12, 10, 6], 128).unwrap();
+    /// let voxels: Vec<([u16; 3], u8)> = octree.iter_positions().collect();
+    ///
+    /// assert_eq!(voxels, vec![([12, 10, 6], 128)]);
+    /// ```
+    ///
+    pub fn iter_positions(&self) -> impl Iterator<Item = ([u16; 3], T)> {
+        let mut voxels = vec![];
+        self.for_each_voxel(|loc, data| voxels.push((loc, data)));
+        voxels.into_iter()
+    }
+
+    /// Iterate over every occupied voxel whose coordinates are all multiples of `stride`, with
+    /// its location
+    ///
+    /// Simplified nodes only contribute the voxels on the stride grid, without visiting the
+    /// rest. A stride of 1 yields every occupied voxel, like `iter_positions`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `stride` is 0.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.fill([0, 0, 0], [7, 7, 7], 255).unwrap();
+    ///
+    /// assert_eq!(octree.iter_strided(4).count(), 8);
+    /// ```
+    ///
+    pub fn iter_strided(&self, stride: u16) -> impl Iterator<Item = ([u16; 3], T)> {
+        assert!(stride >= 1, "stride must be at least 1");
+
+        let mut voxels = vec![];
+        self.root
+            .for_each_strided([0, 0, 0], stride, &mut |loc, data| voxels.push((loc, data)));
+        voxels.into_iter()
+    }
+
+    /// Returns `true` if any occupied voxel of the `Octree<T>` holds `value`
+    ///
+    /// The search stops at the first matching node, and a simplified node is compared once
+    /// rather than once per voxel. The default value is not considered occupied.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([12, 10, 6], 128).unwrap();
+    ///
+    /// assert!(octree.contains_value(128));
+    /// assert!(!octree.contains_value(255));
+    /// ```
+    ///
+    pub fn contains_value(&self, value: T) -> bool {
+        self.root.any_leaf(&mut |data| data == value)
+    }
+
+    /// Returns the inclusive minimum and maximum corners of the box bounding every occupied
+    /// voxel, or `None` if the `Octree<T>` is empty
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([1, 9, 4], 255).unwrap();
+    /// octree.insert([12, 2, 6], 128).unwrap();
+    ///
+    /// assert_eq!(octree.occupied_bounds(), Some(([1, 2, 4], [12, 9, 6])));
+    /// ```
+    ///
+    pub fn occupied_bounds(&self) -> Option<([u16; 3], [u16; 3])> {
+        let mut bounds: Option<([u16; 3], [u16; 3])> = None;
+        self.root.for_each_leaf([0, 0, 0], &mut |origin, size, _| {
+            let last = [
+                origin[0] + size - 1,
+                origin[1] + size - 1,
+                origin[2] + size - 1,
+            ];
+            bounds = Some(match bounds {
+                Some((min, max)) => (
+                    [
+                        min[0].min(origin[0]),
+                        min[1].min(origin[1]),
+                        min[2].min(origin[2]),
+                    ],
+                    [
+                        max[0].max(last[0]),
+                        max[1].max(last[1]),
+                        max[2].max(last[2]),
+                    ],
+                ),
+                None => (origin, last),
+            });
+        });
+        bounds
+    }
+
+    /// Get the location and value of the largest occupied voxel, or `None` if the `Octree<T>` is
+    /// empty
+    ///
+    /// The tree is traversed once, visiting octants depth first in index order, and ties go to
+    /// the first voxel reached. A simplified block is visited once, at its lowest corner, so
+    /// ties between voxels of one block go to that corner. The default value is not considered
+    /// occupied.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([1, 9, 4], 64).unwrap();
+    /// octree.insert([12, 10, 6], 255).unwrap();
+    ///
+    /// assert_eq!(octree.arg_max(), Some(([12, 10, 6], 255)));
+    /// ```
+    ///
+    pub fn arg_max(&self) -> Option<([u16; 3], T)>
+    where
+        T: Ord,
+    {
+        self.arg_best(|data, best| data > best)
+    }
+
+    /// Get the location and value of the smallest occupied voxel, or `None` if the `Octree<T>`
+    /// is empty
+    ///
+    /// Ties are resolved as for `arg_max`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([1, 9, 4], 64).unwrap();
+    /// octree.insert([12, 10, 6], 255).unwrap();
+    ///
+    /// assert_eq!(octree.arg_min(), Some(([1, 9, 4], 64)));
+    /// ```
+    ///
+    pub fn arg_min(&self) -> Option<([u16; 3], T)>
+    where
+        T: Ord,
+    {
+        self.arg_best(|data, best| data < best)
+    }
+
+    /// Returns a hash of the logical contents of the `Octree<T>` that is stable across runs
+    ///
+    /// Two `Octree<T>`s with the same dimension, default value and voxel values produce the same
+    /// hash, however they are simplified. The hash is computed with FNV-1a over a digest of each
+    /// node, where a uniform node has the same digest as the equivalent fully expanded node.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// let empty_hash = octree.content_hash();
+    /// octree.insert([12, 10, 6], 128).unwrap();
+    ///
+    /// assert_ne!(octree.content_hash(), empty_hash);
+    /// octree.take([12, 10, 6]);
+    /// assert_eq!(octree.content_hash(), empty_hash);
+    /// ```
+    ///
+    pub fn content_hash(&self) -> u64
+    where
+        T: Hash,
+    {
+        let mut hasher = FnvHasher::new();
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Returns the metadata of the `Octree<T>`
+    pub fn meta(&self) -> &OctreeMeta {
+        &self.meta
+    }
+
+    /// Returns a mutable reference to the metadata of the `Octree<T>`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.meta_mut().voxel_size = 0.25;
+    ///
+    /// assert_eq!(octree.meta().voxel_size, 0.25);
+    /// ```
+    ///
+    pub fn meta_mut(&mut self) -> &mut OctreeMeta {
+        &mut self.meta
+    }
+
+    /// Register a callback notified of every voxel changed by `insert`, `take` or `insert_none`
+    ///
+    /// The callback is given the location, the old stored value and the new stored value, once
+    /// per write that changes the stored value, however the write restructures the nodes of the
+    /// `Octree<T>`. Default values are ignored. Operations on whole regions, such as `fill`, are
+    /// not reported unless they are built on these methods. Clones of the `Octree<T>` start
+    /// without a callback, and any previous callback is replaced.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// # use std::sync::{Arc, Mutex};
+    /// #
+    /// let changes = Arc::new(Mutex::new(vec![]));
+    /// let log = changes.clone();
+    ///
+    /// let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.set_on_change(Box::new(move |loc, old, new| log.lock().unwrap().push((loc, old, new))));
+    /// octree.insert([12, 10, 6], 128).unwrap();
+    ///
+    /// assert_eq!(*changes.lock().unwrap(), vec![([12, 10, 6], None, Some(128))]);
+    /// ```
+    ///
+    pub fn set_on_change(&mut self, callback: ChangeCallback<T>) {
+        self.tracker.on_change = Some(Mutex::new(callback));
+    }
+
+    /// Returns the inclusive bounding box of every voxel written by `insert`, `take` or
+    /// `insert_none` since the last call, and resets it
+    ///
+    /// Returns `None` if nothing has been written. The box is conservative, as it also covers
+    /// writes that left a voxel unchanged. Clones of the `Octree<T>` start with nothing written.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([1, 2, 3], 255).unwrap();
+    /// octree.take([12, 10, 0]);
+    ///
+    /// assert_eq!(octree.take_dirty_bounds(), Some(([1, 2, 0], [12, 10, 3])));
+    /// assert_eq!(octree.take_dirty_bounds(), None);
+    /// ```
+    ///
+    pub fn take_dirty_bounds(&mut self) -> Option<([u16; 3], [u16; 3])> {
+        self.tracker.dirty.take()
+    }
+
+    /// Returns the value held by unoccupied locations, if the `Octree<T>` has one
+    pub fn default_value(&self) -> Option<T> {
+        self.default
+    }
+
+    /// Returns the policy deciding when regions of the `Octree<T>` are simplified
+    pub fn simplification(&self) -> SimplificationMode {
+        self.simplification
+    }
+
+    /// Returns the average number of occupied voxels represented by each occupied leaf
+    ///
+    /// A value of `8.0` means each leaf represents eight voxels on average, and `1.0` means no
+    /// simplification has taken place. An empty `Octree<T>` returns `0.0`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([0, 0, 0], 255).unwrap();
+    ///
+    /// assert_eq!(octree.simplification_ratio(), 1.0);
+    /// ```
+    ///
+    pub fn simplification_ratio(&self) -> f64 {
+        let mut voxels = 0u64;
+        let mut leaves = 0u64;
+        self.root.for_each_leaf([0, 0, 0], &mut |_, size, _| {
+            voxels += u64::from(size).pow(3);
+            leaves += 1;
+        });
+
+        if leaves == 0 {
+            0.0
+        } else {
+            voxels as f64 / leaves as f64
+        }
+    }
+
+    /// Returns the depth of the deepest occupied leaf in the `Octree<T>`, or `0` if it is empty
+    ///
+    /// This is at most `max_depth`, and is smaller when the `Octree<T>` is sparse or heavily
+    /// simplified.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([0, 0, 0], 255).unwrap();
+    ///
+    /// assert_eq!(octree.actual_depth(), octree.max_depth());
+    /// ```
+    ///
+    pub fn actual_depth(&self) -> u8 {
+        self.root.actual_depth().unwrap_or(0)
+    }
+
+    /// Remove nodes left empty by edits such as `take`
+    ///
+    /// `take` clears a voxel without removing its node, so a tree that has been sparsely
+    /// emptied keeps every node along the paths to the cleared voxels. Each node removed by
+    /// `compact` lowers `memory_bytes` by the node's own size plus its eight child slots, so the
+    /// saving depends on `T` and the target, and is best measured by comparing `memory_bytes`
+    /// before and after. Every internal node keeps all eight child slots, since node lookups
+    /// index them directly. Nodes shared with a `Snapshot` are only copied if they hold
+    /// something to remove.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// let empty_bytes = octree.memory_bytes();
+    /// octree.insert([12, 10, 6], 128).unwrap();
+    /// octree.take([12, 10, 6]);
+    /// octree.compact();
+    ///
+    /// assert_eq!(octree.memory_bytes(), empty_bytes);
+    /// ```
+    ///
+    pub fn compact(&mut self) {
+        if !self.root.any_empty() {
+            return;
+        }
+        self.root_mut().compact();
+    }
+
+    /// Merge internal nodes into single leaves until the `Octree<T>` has at most `max_nodes`
+    /// nodes, for rendering within a fixed node budget
+    ///
+    /// Only nodes whose children are all leaves are merged, each becoming a leaf holding
+    /// `reduce` of its eight octant values in octant order, with `None` for empty octants.
+    /// Nodes whose octants hold the fewest distinct values are merged first, and the smallest
+    /// among those, so the most uniform detail is lost first. Merging continues up the tree as
+    /// parents become eligible, and stops once the budget is met or the root is a leaf.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([0, 0, 0], 10).unwrap();
+    /// octree.insert([12, 10, 6], 20).unwrap();
+    /// octree.coarsen_to_budget(5, |values| values.iter().flatten().max().cloned());
+    ///
+    /// assert!(octree.node_count() <= 5);
+    /// assert_eq!(octree.at([1, 1, 1]), Some(10));
+    /// ```
+    ///
+    pub fn coarsen_to_budget<F: Fn(&[Option<T>]) -> Option<T>>(
+        &mut self,
+        max_nodes: usize,
+        reduce: F,
+    ) {
+        let mut count = self.node_count();
+        if count <= max_nodes {
+            return;
+        }
+
+        let mut candidates = BinaryHeap::new();
+        self.root
+            .for_each_mergeable(&mut vec![], &mut |path, cost, size| {
+                candidates.push(Reverse((cost, size, path.to_vec())))
+            });
+
+        while count > max_nodes {
+            let mut path = match candidates.pop() {
+                Some(Reverse((_, _, path))) => path,
+                None => break,
+            };
+            count -= self.root_mut().merge_at(&path, &reduce);
+
+            // The parent may now have only leaves below it
+            if path.pop().is_some() {
+                if let Some(parent) = self.root.descendant(&path) {
+                    if let Some(cost) = parent.merge_cost() {
+                        candidates.push(Reverse((cost, parent.dimension(), path)));
+                    }
+                }
+            }
+        }
+        self.occupied.invalidate();
+    }
+
+    /// Returns an estimate of the bytes used by the nodes of the `Octree<T>`
+    ///
+    /// This sums the size of every node plus the child slot storage of every node. It is only an
+    /// estimate, and ignores allocator and reference counting overhead. Compare against
+    /// `dimension^3 * size_of::<T>()` for the cost of the equivalent dense array.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// let empty_bytes = octree.memory_bytes();
+    /// octree.insert([0, 0, 0], 255).unwrap();
+    ///
+    /// assert!(octree.memory_bytes() > empty_bytes);
+    /// ```
+    ///
+    pub fn memory_bytes(&self) -> usize {
+        mem::size_of::<OctreeNode<T>>() + self.root.heap_bytes()
+    }
+
+    /// Copy the `Octree<T>` into a `FlatOctree<T>`, storing every node in one contiguous `Vec`
+    ///
+    /// The flat form is read-only, but answers `at` with less pointer chasing, suiting
+    /// traversal-heavy workloads on trees that rarely change.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([12, 10, 6], 255).unwrap();
+    /// let flat = octree.to_flat();
+    ///
+    /// assert_eq!(flat.nodes().len(), 33);
+    /// assert_eq!(flat.iter().collect::<Vec<_>>(), vec![([12, 10, 6], 255)]);
+    /// ```
+    ///
+    pub fn to_flat(&self) -> FlatOctree<T> {
+        FlatOctree::new(&self.root, self.extents, self.default)
+    }
+
+    /// Copy one of the eight top-level octants into a new `Octree<T>` of half the dimension,
+    /// with its co-ordinates rebased so the octant's origin becomes `[0, 0, 0]`
+    ///
+    /// Octants are numbered as the children of a node: `0` to `3` anticlockwise from the origin
+    /// on the lower Z half, viewed from above, then `4` to `7` likewise on the upper Z half. The
+    /// nodes of the octant are shared with this `Octree<T>` rather than copied. The default
+    /// value, simplification mode and metric are carried over, and extents smaller than the
+    /// dimension are clipped to the octant. Returns an `OutOfBoundsError` carrying the octant's
+    /// origin if the octant lies wholly beyond the extents of a `new_rect` tree.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `octant` is not below 8, or if the `Octree<T>` has a dimension of 1.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([12, 10, 6], 255).unwrap();
+    /// let octant = octree.extract_octant(2).unwrap();
+    ///
+    /// assert_eq!(octant.dimension(), 8);
+    /// assert_eq!(octant.at([4, 2, 6]), Some(255));
+    /// ```
+    ///
+    pub fn extract_octant(&self, octant: u8) -> Result<Octree<T>, OctreeError> {
+        assert!(octant < 8, "Octant {} is not below 8", octant);
+        let half = self.dimension / 2;
+        let origin = child_origin([0, 0, 0], usize::from(octant), half);
+        if !self.contains_loc(&self.loc_from_array(origin)) {
+            return Err(OctreeError::OutOfBoundsError(origin));
+        }
+
+        let mut extracted = Octree::new(half).expect("A single-cell Octree<T> has no octants");
+        extracted.default = self.default;
+        extracted.simplification = self.simplification;
+        extracted.metric = self.metric;
+        for (extent, (&start, &end)) in extracted
+            .extents
+            .iter_mut()
+            .zip(origin.iter().zip(&self.extents))
+        {
+            *extent = (end - start).min(half);
+        }
+
+        if self.root.leaf() {
+            if let Some(data) = self.root.get() {
+                extracted.root = Arc::new(OctreeNode::new_block(half, data));
+            }
+        } else if let Some(child) = self.root.shared_child(usize::from(octant)) {
+            extracted.root = child;
+        }
+        extracted.occupied.invalidate();
+        Ok(extracted)
+    }
+
+    /// Replace one of the eight top-level octants with the contents of an `Octree<T>` of half
+    /// the dimension, as the inverse of `extract_octant`
+    ///
+    /// The nodes of `sub` are shared rather than copied, and the root is simplified if the
+    /// octants have become uniform. Only the stored contents of `sub` are placed, so its default
+    /// value and settings are ignored. Returns a `DimensionError` if `sub` is not half the
+    /// dimension of this `Octree<T>`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `octant` is not below 8.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// let mut sub = Octree::<u8>::new(8).unwrap();
+    /// sub.insert([4, 2, 6], 255).unwrap();
+    /// octree.place_octant(2, &sub).unwrap();
+    ///
+    /// assert_eq!(octree.at([12, 10, 6]), Some(255));
+    /// ```
+    ///
+    pub fn place_octant(&mut self, octant: u8, sub: &Octree<T>) -> Result<(), OctreeError> {
+        assert!(octant < 8, "Octant {} is not below 8", octant);
+        let half = self.dimension / 2;
+        if sub.dimension != half {
+            return Err(OctreeError::DimensionError);
+        }
+
+        let child = if sub.root.leaf() && sub.root.get().is_none() {
+            None
+        } else {
+            Some(Arc::clone(&sub.root))
+        };
+        let within = self.equivalence();
+        let equivalent: Option<&dyn Fn(T, T) -> bool> = match self.simplification {
+            SimplificationMode::Off => None,
+            _ => Some(&within),
+        };
+        self.root_mut()
+            .replace_child(usize::from(octant), child, equivalent);
+        self.occupied.invalidate();
+
+        let origin = child_origin([0, 0, 0], usize::from(octant), half);
+        let mut last = origin;
+        for axis in 0..3 {
+            last[axis] = (origin[axis] + (half - 1)).min(self.extents[axis] - 1);
+        }
+        if (0..3).all(|axis| origin[axis] <= last[axis]) {
+            self.tracker.extend_dirty(origin);
+            self.tracker.extend_dirty(last);
+        }
+        Ok(())
+    }
+
+    /// Check the internal invariants of the `Octree<T>` in debug builds, panicking if any is
+    /// broken
+    ///
+    /// Every node must have eight octants of half its dimension, leaves must have no children,
+    /// only leaves may hold data or be simplified, and a known cached voxel count must match the
+    /// stored voxels. This walks the whole `Octree<T>`, and is meant for tests and debugging.
+    /// Release builds skip the checks.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.fill([0, 0, 0], [7, 7, 7], 255).unwrap();
+    /// octree.take([3, 3, 3]);
+    /// octree.assert_consistent();
+    /// ```
+    ///
+    pub fn assert_consistent(&self) {
+        if !cfg!(debug_assertions) {
+            return;
+        }
+
+        debug_assert_eq!(
+            self.root.dimension(),
+            self.dimension,
+            "Root has the wrong dimension"
+        );
+        self.root.assert_consistent(&mut vec![]);
+        if let Some(count) = self.occupied.get() {
+            let mut counted = 0;
+            self.for_each_voxel(|_, _| counted += 1);
+            debug_assert_eq!(count, counted, "Cached voxel count is stale");
+        }
+    }
+
+    /// Capture the current state of the `Octree<T>`, to be restored later with `restore`
+    ///
+    /// Nodes are shared between the `Octree<T>` and its snapshots, and are only copied when they
+    /// are modified. Each edit after taking a snapshot therefore copies only the nodes along the
+    /// edited path, rather than the whole `Octree<T>`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([0, 0, 0], 255).unwrap();
+    /// let snapshot = octree.snapshot();
+    ///
+    /// octree.insert([0, 0, 0], 128).unwrap();
+    /// octree.restore(snapshot);
+    /// assert_eq!(octree.at([0, 0, 0]), Some(255));
+    /// ```
+    ///
+    pub fn snapshot(&self) -> Snapshot<T> {
+        Snapshot {
+            dimension: self.dimension,
+            extents: self.extents,
+            max_depth: self.max_depth,
+            root: Arc::clone(&self.root),
+        }
+    }
+
+    /// Roll the `Octree<T>` back to the state captured by a `Snapshot<T>`
+    pub fn restore(&mut self, snapshot: Snapshot<T>) {
+        self.dimension = snapshot.dimension;
+        self.extents = snapshot.extents;
+        self.max_depth = snapshot.max_depth;
+        self.root = snapshot.root;
+        self.occupied.invalidate();
+        self.paths.clear();
+    }
+
+    /// Get metadata describing the leaf cell covering a given location, without exposing its data
+    ///
+    /// Returns `None` if the location is out of the `Octree<T>` bounds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([0, 0, 0], 255).unwrap();
+    /// let info = octree.cell_info([0, 0, 0]).unwrap();
+    ///
+    /// assert_eq!(info.size, 1);
+    /// assert!(info.occupied);
+    /// ```
+    ///
+    pub fn cell_info(&self, loc: [u16; 3]) -> Option<CellInfo> {
+        let mut node_loc = self.loc_from_array(loc);
+        if self.contains_loc(&node_loc) {
+            Some(self.root.cell_info(&mut node_loc, [0, 0, 0]))
+        } else {
+            None
+        }
+    }
+
+    /// Create a `Cursor<T>` positioned at a given location, for repeated local navigation
+    ///
+    /// Returns `None` if the location is out of the `Octree<T>` bounds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::{Axis, Octree};
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([0, 0, 0], 255).unwrap();
+    /// let mut cursor = octree.cursor([0, 0, 0]).unwrap();
+    ///
+    /// assert_eq!(cursor.value(), Some(255));
+    /// cursor.step(Axis::Z, 1);
+    /// assert_eq!(cursor.value(), None);
+    /// ```
+    ///
+    pub fn cursor(&self, loc: [u16; 3]) -> Option<Cursor<'_, T>> {
+        if !self.contains_loc(&self.loc_from_array(loc)) {
+            None
+        } else {
+            Some(Cursor::new(&self.root, self.extents, self.default, loc))
+        }
+    }
+
+    /// Get a shared reference to the `OctreeNode<T>` reached by following a path of octant
+    /// indices down from the root
+    ///
+    /// Octants are numbered as in `extract_octant`, and an empty path gives the root. Returns
+    /// `None` if the path runs off a leaf or into an empty octant, or holds an index not below
+    /// 8.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([12, 10, 6], 255).unwrap();
+    /// let node = octree.node_at_path(&[2, 5]).unwrap();
+    ///
+    /// assert_eq!(node.dimension(), 4);
+    /// assert!(octree.node_at_path(&[0]).is_none());
+    /// ```
+    ///
+    pub fn node_at_path(&self, path: &[u8]) -> Option<&OctreeNode<T>> {
+        path.iter().try_fold(&*self.root, |node, &octant| {
+            if node.leaf() || octant >= 8 {
+                None
+            } else {
+                node.child(usize::from(octant))
+            }
+        })
+    }
+
+    /// Set the scratch mark of the deepest node covering a given location
+    ///
+    /// Marks are free for external algorithms, such as visited-marking during a traversal. They
+    /// do not affect the data, simplification, equality or serialization of the `Octree<T>`, but
+    /// a mark is lost when its node is merged or split by a later edit. Returns `false` if the
+    /// location is out of bounds or lies in an unallocated empty region, which has no node to
+    /// mark.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([12, 10, 6], 128).unwrap();
+    ///
+    /// assert!(octree.mark([12, 10, 6]));
+    /// assert!(octree.is_marked([12, 10, 6]));
+    /// assert!(!octree.is_marked([0, 0, 0]));
+    /// ```
+    ///
+    pub fn mark(&mut self, loc: [u16; 3]) -> bool {
+        self.set_marked(loc, true)
+    }
+
+    /// Clear the scratch mark of the deepest node covering a given location
+    ///
+    /// Returns `false` if there is no node to unmark, as in `mark`.
+    pub fn unmark(&mut self, loc: [u16; 3]) -> bool {
+        self.set_marked(loc, false)
+    }
+
+    /// Test if the deepest node covering a given location is marked
+    pub fn is_marked(&self, loc: [u16; 3]) -> bool {
+        let mut node_loc = self.loc_from_array(loc);
+        if !self.contains_loc(&node_loc) {
+            return false;
+        }
+        if self.root.leaf() {
+            return self.root.marked();
+        }
+        self.root
+            .node_as_ref(&mut node_loc)
+            .is_some_and(OctreeNode::marked)
+    }
+
+    /// Clear the scratch mark of every node in the `Octree<T>`
+    pub fn clear_marks(&mut self) {
+        self.root_mut().clear_marks();
+    }
+
+    /// Transform the `Octree<T>` into an iterator, consuming the `Octree<T>`
+    ///
+    /// Values are yielded in depth-first order, visiting the octants of each node in the order
+    /// `(0, 0, 0)`, `(1, 0, 0)`, `(1, 1, 0)`, `(0, 1, 0)`, followed by the same four with `z`
+    /// set. A simplified node yields its value once.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([12, 10, 6], 128).unwrap();
+    /// octree.insert([0, 0, 0], 255).unwrap();
+    ///
+    /// let values: Vec<u8> = octree.iter().collect();
+    ///
+    /// assert_eq!(values, vec![255, 128]);
+    /// ```
+    ///
+    pub fn iter(&mut self) -> OctreeIterator<T> {
+        OctreeIterator::new_from_ref(self)
+    }
+
+    /// Iterate over every occupied leaf of the `Octree<T>` as a `Block<T>`, in the order they
+    /// are stored
+    ///
+    /// Simplified regions are yielded once, with `simplified` set and their full `size`, while
+    /// individual voxels are yielded with a `size` of 1. The leaves are gathered before the
+    /// iterator is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.fill([0, 0, 0], [7, 7, 7], 1).unwrap();
+    /// octree.insert([12, 10, 6], 2).unwrap();
+    /// let blocks: Vec<_> = octree.iter_blocks().collect();
+    ///
+    /// assert_eq!(blocks.len(), 2);
+    /// assert!(blocks[0].simplified && blocks[0].size == 8);
+    /// assert_eq!(blocks[1].origin, [12, 10, 6]);
+    /// ```
+    ///
+    pub fn iter_blocks(&self) -> impl Iterator<Item = Block<T>> {
+        let mut blocks = vec![];
+        self.root
+            .for_each_block([0, 0, 0], &mut |origin, size, value, simplified| {
+                blocks.push(Block {
+                    origin,
+                    size,
+                    value,
+                    simplified,
+                })
+            });
+        blocks.into_iter()
+    }
+
+    /// Iterate over every occupied voxel in ascending Morton (Z-order) key order, yielding the
+    /// key of each voxel with its value
+    ///
+    /// Simplified nodes are expanded into their individual voxels. Keys can be converted back
+    /// to locations with `math::morton_decode`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// # use octo::math::morton_decode;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([0, 0, 1], 128).unwrap();
+    /// octree.insert([1, 0, 0], 255).unwrap();
+    /// let voxels: Vec<([u16; 3], u8)> = octree
+    ///     .iter_morton()
+    ///     .map(|(key, data)| (morton_decode(key), data))
+    ///     .collect();
+    ///
+    /// assert_eq!(voxels, vec![([1, 0, 0], 255), ([0, 0, 1], 128)]);
+    /// ```
+    ///
+    pub fn iter_morton(&self) -> impl Iterator<Item = (u64, T)> {
+        let mut voxels = vec![];
+        self.for_each_voxel(|loc, data| voxels.push((morton_encode(loc), data)));
+        voxels.sort_by_key(|&(key, _)| key);
+        voxels.into_iter()
+    }
+
+    /// Apply `f` to every value of the `Octree<T>`, returning the result as a new `Octree<U>`
+    ///
+    /// Simplified nodes are mapped once rather than per voxel, and regions mapped to equal
+    /// values are simplified. Any default value is mapped too.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([12, 10, 6], 128).unwrap();
+    /// let mapped = octree.map(|value| u32::from(value) * 2);
+    ///
+    /// assert_eq!(mapped.at([12, 10, 6]), Some(256));
+    /// ```
+    ///
+    pub fn map<U, F>(&self, f: F) -> Octree<U>
+    where
+        U: Copy + PartialEq,
+        F: Fn(T) -> U,
+    {
+        let mut octree = self.empty_like();
+        octree.default = self.default.map(&f);
+        octree.root = Arc::new(self.root.map(&f));
+        octree.occupied = self.occupied.clone();
+        octree
+    }
+
+    /// Convert the `Octree<T>` into an `Octree<U>` with exactly the same node structure, applying
+    /// `f` to the data of every node
+    ///
+    /// Unlike `map`, `f` is also given the `None` data of internal and empty nodes, and nothing
+    /// is simplified afterwards, so simplified nodes stay simplified and unsimplified ones stay
+    /// split. This is faster than `map` when `f` is known to keep equal values equal. An `f`
+    /// returning a value for `None` occupies the empty leaves it is given, though not octants
+    /// with no node at all. Any default value is mapped too.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.fill([0, 0, 0], [7, 7, 7], 1).unwrap();
+    /// octree.insert([12, 10, 6], 2).unwrap();
+    /// let cast = octree.cast(|data| data.map(|value| f32::from(value) / 2.0));
+    ///
+    /// assert_eq!(cast.node_count(), octree.node_count());
+    /// assert_eq!(cast.at([12, 10, 6]), Some(1.0));
+    /// ```
+    ///
+    pub fn cast<U, F>(&self, f: F) -> Octree<U>
+    where
+        U: Copy + PartialEq,
+        F: Fn(Option<T>) -> Option<U>,
+    {
+        let mut octree = self.empty_like();
+        octree.default = self.default.and_then(|data| f(Some(data)));
+        octree.root = Arc::new(self.root.cast(&f));
+        octree.occupied.invalidate();
+        octree
+    }
+
+    /// Apply `f` to every value of the `Octree<T>` as in `map`, mapping the eight top-level
+    /// octants on up to `threads` scoped worker threads
+    ///
+    /// The values of each octant are moved to a worker, which is why `T` must be `Send`, and
+    /// the mapped values are moved back. Nodes are never shared between threads, and the mapped
+    /// nodes are rebuilt on the calling thread, so this pays off when `f` is expensive. Falls
+    /// back to `map` on the calling thread when `threads <= 1`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([12, 10, 6], 128).unwrap();
+    /// let mapped = octree.map_parallel(4, |value| u32::from(value) * 2);
+    ///
+    /// assert!(mapped == octree.map(|value| u32::from(value) * 2));
+    /// ```
+    ///
+    pub fn map_parallel<U, F>(&self, threads: usize, f: F) -> Octree<U>
+    where
+        T: Send,
+        U: Copy + PartialEq + Send,
+        F: Fn(T) -> U + Sync,
+    {
+        if threads <= 1 || self.root.leaf() {
+            return self.map(f);
+        }
+
+        let values: Vec<(usize, Vec<T>)> = (0..8)
+            .map(|i| {
+                let mut values = vec![];
+                if let Some(child) = self.root.child(i) {
+                    child.for_each_leaf([0, 0, 0], &mut |_, _, data| values.push(data));
+                }
+                (i, values)
+            })
+            .collect();
+
+        let mut mapped: Vec<Vec<U>> = vec![vec![]; 8];
+        thread::scope(|scope| {
+            let f = &f;
+            let per_worker = 8usize.div_ceil(threads.min(8));
+            let mut values = values.into_iter();
+            let mut workers = vec![];
+            loop {
+                let chunk: Vec<_> = values.by_ref().take(per_worker).collect();
+                if chunk.is_empty() {
+                    break;
+                }
+                workers.push(scope.spawn(move || {
+                    chunk
+                        .into_iter()
+                        .map(|(i, values)| (i, values.into_iter().map(f).collect::<Vec<_>>()))
+                        .collect::<Vec<_>>()
+                }));
+            }
+            for worker in workers {
+                for (i, values) in worker.join().unwrap() {
+                    mapped[i] = values;
+                }
+            }
+        });
+
+        let children = mapped
+            .into_iter()
+            .enumerate()
+            .map(|(i, values)| {
+                let values = RefCell::new(values.into_iter());
+                self.root
+                    .child(i)
+                    .map(|child| Arc::new(child.map(&|_| values.borrow_mut().next().unwrap())))
+            })
+            .collect();
+
+        let mut octree = self.empty_like();
+        octree.default = self.default.map(&f);
+        octree.root = Arc::new(OctreeNode::from_children(
+            self.dimension,
+            children,
+            Some(&|a: U, b: U| a == b),
+        ));
+        octree.occupied = self.occupied.clone();
+        octree
+    }
+
+    /// Blur the values of the `Octree<T>` with a gaussian kernel reaching `radius` voxels along
+    /// each axis, returning the result as a new `Octree<f32>`
+    ///
+    /// The kernel is applied as separable passes along x, y, then z, over the occupied bounds
+    /// grown by `radius`, treating empty voxels as zero. Voxels left at zero are empty in the
+    /// result. A `radius` of 0 converts the values without blurring them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([8, 8, 8], 100).unwrap();
+    /// let blurred = octree.blur(1);
+    ///
+    /// assert!(blurred.at([8, 8, 8]).unwrap() < 100.0);
+    /// assert_eq!(blurred.at([7, 8, 8]), blurred.at([9, 8, 8]));
+    /// assert_eq!(blurred.at([10, 8, 8]), None);
+    /// ```
+    ///
+    pub fn blur(&self, radius: u8) -> Octree<f32>
+    where
+        T: Into<f32>,
+    {
+        let mut octree = self.empty_like();
+        let (min, max) = match self.occupied_bounds() {
+            Some(bounds) => bounds,
+            None => return octree,
+        };
+
+        let reach = u16::from(radius);
+        let mut low = [0; 3];
+        let mut size = [0; 3];
+        for axis in 0..3 {
+            low[axis] = min[axis].saturating_sub(reach);
+            let high = max[axis].saturating_add(reach).min(self.extents[axis] - 1);
+            size[axis] = usize::from(high - low[axis]) + 1;
+        }
+        let high = [
+            low[0] + (size[0] - 1) as u16,
+            low[1] + (size[1] - 1) as u16,
+            low[2] + (size[2] - 1) as u16,
+        ];
+
+        let index = |loc: [u16; 3]| {
+            let offset = |axis: usize| usize::from(loc[axis] - low[axis]);
+            offset(0) + size[0] * (offset(1) + size[1] * offset(2))
+        };
+        let mut values = vec![0.0f32; size.iter().product()];
+        self.root
+            .for_each_occupied([0, 0, 0], low, high, &mut |loc, data| {
+                values[index(loc)] = data.into()
+            });
+
+        let sigma = (f32::from(radius) / 2.0).max(0.5);
+        let mut kernel: Vec<f32> = (-i32::from(radius)..=i32::from(radius))
+            .map(|offset| (-(offset * offset) as f32 / (2.0 * sigma * sigma)).exp())
+            .collect();
+        let total: f32 = kernel.iter().sum();
+        kernel.iter_mut().for_each(|weight| *weight /= total);
+
+        let radius = isize::from(radius);
+        for axis in 0..3 {
+            let stride = size[..axis].iter().product::<usize>() as isize;
+            let mut blurred = vec![0.0f32; values.len()];
+            for (i, value) in blurred.iter_mut().enumerate() {
+                let coord = (i as isize / stride) % size[axis] as isize;
+                for (k, weight) in kernel.iter().enumerate() {
+                    let offset = k as isize - radius;
+                    if coord + offset >= 0 && coord + offset < size[axis] as isize {
+                        *value += weight * values[(i as isize + offset * stride) as usize];
+                    }
+                }
+            }
+            values = blurred;
+        }
+
+        for z in low[2]..=high[2] {
+            for y in low[1]..=high[1] {
+                for x in low[0]..=high[0] {
+                    let value = values[index([x, y, z])];
+                    if value != 0.0 {
+                        let mut node_loc = octree.loc_from_array([x, y, z]);
+                        Arc::make_mut(&mut octree.root).insert(&mut node_loc, value, None);
+                    }
+                }
+            }
+        }
+        octree.resimplify();
+        octree.occupied.invalidate();
+        octree
+    }
+
+    /// Morphologically dilate the `Octree<T>`, returning the result as a new `Octree<T>`
+    ///
+    /// Every empty voxel sharing a face with an occupied voxel is set to `value`, while occupied
+    /// voxels keep their existing values.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([4, 4, 4], 255).unwrap();
+    /// let dilated = octree.dilate(128);
+    ///
+    /// assert_eq!(dilated.at([4, 4, 4]), Some(255));
+    /// assert_eq!(dilated.at([4, 5, 4]), Some(128));
+    /// ```
+    ///
+    pub fn dilate(&self, value: T) -> Octree<T> {
+        let mut dilated = self.clone();
+
+        self.for_each_voxel(|loc, _| {
+            for neighbor in self.face_neighbors(loc) {
+                if self.stored_at(neighbor).is_none() {
+                    dilated.insert(neighbor, value).unwrap();
+                }
+            }
+        });
+
+        dilated
+    }
+
+    /// Morphologically erode the `Octree<T>`, returning the result as a new `Octree<T>`
+    ///
+    /// An occupied voxel survives only if all six of its face neighbors are also occupied.
+    /// Neighbors outside the `Octree<T>` count as empty, so voxels on the border always erode away.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([4, 4, 4], 255).unwrap();
+    /// let eroded = octree.erode();
+    ///
+    /// assert_eq!(eroded.at([4, 4, 4]), None);
+    /// ```
+    ///
+    pub fn erode(&self) -> Octree<T> {
+        let mut eroded = self.empty_like();
+        eroded.default = self.default;
+
+        self.for_each_voxel(|loc, data| {
+            let neighbors = self.face_neighbors(loc);
+            if neighbors.len() == 6 && neighbors.iter().all(|n| self.stored_at(*n).is_some()) {
+                eroded.insert(loc, data).unwrap();
+            }
+        });
+
+        eroded
+    }
+
+    /// Move every occupied voxel to a new location given by `f`, returning the result as a new
+    /// `Octree<T>`
+    ///
+    /// Voxels are dropped where `f` returns `None` or a location outside the `Octree<T>`. When
+    /// several voxels land on the same location, the last one visited in storage order wins.
+    /// The result is simplified once every voxel has been moved.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([12, 10, 6], 255).unwrap();
+    /// let warped = octree.warp(|[x, y, z]| Some([x, y, z + 2]));
+    ///
+    /// assert_eq!(warped.at([12, 10, 8]), Some(255));
+    /// assert_eq!(warped.at([12, 10, 6]), None);
+    /// ```
+    ///
+    pub fn warp<F: Fn([u16; 3]) -> Option<[u16; 3]>>(&self, f: F) -> Octree<T> {
+        let mut warped = self.empty_like();
+        warped.default = self.default;
+        warped.simplification = self.simplification;
+        warped.metric = self.metric;
+
+        self.for_each_voxel(|loc, data| {
+            if let Some(target) = f(loc) {
+                // Targets outside the `Octree<T>` are dropped
+                let _ = warped.set_raw(target, data);
+            }
+        });
+        warped.resimplify();
+
+        warped
+    }
+
+    /// Let every occupied voxel fall along `down` towards the minimum co-ordinate, closing the
+    /// empty gaps below it, and return the number of voxels that moved
+    ///
+    /// Each column along `down` is settled independently, keeping the order of its voxels.
+    /// Default values are treated as empty space. The `Octree<T>` is simplified once every
+    /// voxel has been moved.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::{Axis, Octree};
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([12, 10, 6], 255).unwrap();
+    ///
+    /// assert_eq!(octree.settle(Axis::Y), 1);
+    /// assert_eq!(octree.at([12, 0, 6]), Some(255));
+    /// assert_eq!(octree.at([12, 10, 6]), None);
+    /// ```
+    ///
+    pub fn settle(&mut self, down: Axis) -> usize {
+        let axis = down as usize;
+        let mut columns: HashMap<[u16; 3], Vec<([u16; 3], T)>> = HashMap::new();
+        self.for_each_voxel(|loc, data| {
+            let mut column = loc;
+            column[axis] = 0;
+            columns.entry(column).or_default().push((loc, data));
+        });
+
+        let mut moved = 0;
+        for column in columns.values_mut() {
+            column.sort_by_key(|(loc, _)| loc[axis]);
+            // Voxels only ever move into space vacated below them, so settling from the bottom
+            // up never overwrites a voxel that has yet to move
+            for (floor, &(loc, data)) in column.iter().enumerate() {
+                if usize::from(loc[axis]) != floor {
+                    let mut target = loc;
+                    target[axis] = floor as u16;
+                    self.take(loc);
+                    self.insert(target, data).unwrap();
+                    moved += 1;
+                }
+            }
+        }
+
+        if moved > 0 {
+            self.resimplify();
+        }
+        moved
+    }
+
+    /// Label the connected components of occupied voxels, using 6-connectivity
+    ///
+    /// Returns a new `Octree<u32>` holding the label (from `1` to `n`) of the component each
+    /// occupied voxel belongs to, along with the number of components `n`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([0, 0, 0], 255).unwrap();
+    /// octree.insert([0, 0, 1], 255).unwrap();
+    /// octree.insert([8, 8, 8], 255).unwrap();
+    /// let (labels, count) = octree.connected_components();
+    ///
+    /// assert_eq!(count, 2);
+    /// assert_eq!(labels.at([0, 0, 0]), labels.at([0, 0, 1]));
+    /// ```
+    ///
+    pub fn connected_components(&self) -> (Octree<u32>, u32) {
+        let mut voxels = vec![];
+        let mut indices = HashMap::new();
+        self.for_each_voxel(|loc, _| {
+            indices.insert(loc, voxels.len());
+            voxels.push(loc);
+        });
+
+        let mut sets = DisjointSet::new(voxels.len());
+        for (i, loc) in voxels.iter().enumerate() {
+            for axis in 0..3 {
+                let mut neighbor = *loc;
+                neighbor[axis] += 1;
+                if let Some(&j) = indices.get(&neighbor) {
+                    sets.union(i, j);
+                }
+            }
+        }
+
+        let mut labels = self.empty_like();
+        let mut root_labels = HashMap::new();
+        for (i, loc) in voxels.iter().enumerate() {
+            let next_label = root_labels.len() as u32 + 1;
+            let label = *root_labels.entry(sets.find(i)).or_insert(next_label);
+            labels.insert(*loc, label).unwrap();
+        }
+
+        let count = root_labels.len() as u32;
+        (labels, count)
+    }
+
+    /// Sample the `Octree<T>` at a sub-voxel position, trilinearly interpolating between the
+    /// eight surrounding voxels
+    ///
+    /// Voxel values are taken to lie at integer co-ordinates, and the sample point is clamped to
+    /// the `Octree<T>` bounds. Empty voxels take the default value, or zero without one, and
+    /// `None` is returned if all eight are empty. The default value does not count as occupied.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([0, 0, 0], 0).unwrap();
+    /// octree.insert([1, 0, 0], 100).unwrap();
+    /// let sample = octree.sample_trilinear([0.5, 0.0, 0.0]).unwrap();
+    ///
+    /// assert!((sample - 50.0).abs() < 1e-4);
+    /// ```
+    ///
+    pub fn sample_trilinear(&self, p: [f32; 3]) -> Option<f32>
+    where
+        T: Into<f64>,
+    {
+        let mut base = [0u16; 3];
+        let mut frac = [0f64; 3];
+        for axis in 0..3 {
+            let clamped = p[axis].max(0.0).min(f32::from(self.extents[axis] - 1));
+            base[axis] = clamped.floor() as u16;
+            frac[axis] = f64::from(clamped - clamped.floor());
+        }
+
+        let mut any_occupied = false;
+        let mut sample = 0.0;
+        for corner in 0..8 {
+            let offset = [corner & 1, (corner >> 1) & 1, (corner >> 2) & 1];
+            let mut loc = base;
+            let mut weight = 1.0;
+            for axis in 0..3 {
+                if offset[axis] == 1 {
+                    loc[axis] = (loc[axis] + 1).min(self.extents[axis] - 1);
+                    weight *= frac[axis];
+                } else {
+                    weight *= 1.0 - frac[axis];
+                }
+            }
+
+            let stored = self.stored_at(loc);
+            any_occupied |= stored.is_some();
+            if let Some(data) = stored.or(self.default) {
+                sample += weight * data.into();
+            }
+        }
+
+        if any_occupied {
+            Some(sample as f32)
+        } else {
+            None
+        }
+    }
+
+    /// Estimate the gradient of the values of the `Octree<T>` at a given location
+    ///
+    /// Each component is the central difference of the two face neighbours along that axis.
+    /// Where one neighbour is empty or out of bounds, the forward or backward difference with
+    /// the other is used instead, and the component is zero if both are. Unoccupied locations
+    /// take the default value, as with `at`. Returns `None` if the location itself is empty or
+    /// out of bounds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([4, 4, 4], 10).unwrap();
+    /// octree.insert([5, 4, 4], 20).unwrap();
+    /// octree.insert([6, 4, 4], 30).unwrap();
+    ///
+    /// assert_eq!(octree.gradient_at([5, 4, 4]), Some([10.0, 0.0, 0.0]));
+    /// assert_eq!(octree.gradient_at([4, 4, 4]), Some([10.0, 0.0, 0.0]));
+    /// ```
+    ///
+    pub fn gradient_at(&self, loc: [u16; 3]) -> Option<[f32; 3]>
+    where
+        T: Into<f32>,
+    {
+        let sample = |loc: [u16; 3]| self.try_at(loc).ok()?.map(Into::<f32>::into);
+        let center = sample(loc)?;
+        let mut gradient = [0.0; 3];
+        for (axis, component) in gradient.iter_mut().enumerate() {
+            let mut below = loc;
+            let mut above = loc;
+            below[axis] = below[axis].wrapping_sub(1);
+            above[axis] = above[axis].wrapping_add(1);
+
+            *component = match (sample(below), sample(above)) {
+                (Some(low), Some(high)) => (high - low) / 2.0,
+                (None, Some(high)) => high - center,
+                (Some(low), None) => center - low,
+                (None, None) => 0.0,
+            };
+        }
+        Some(gradient)
+    }
+
+    /// Cache a level of detail value on every internal node of the `Octree<T>`, computed
+    /// bottom-up by `aggregate`
+    ///
+    /// `aggregate` is given the values of the eight children of a node in octant order, where
+    /// each value is the data of a leaf child or the cached value of an internal child, and
+    /// `None` for an empty child. The cache is not updated by later edits, so this should be
+    /// called again after modifying the `Octree<T>`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([0, 0, 0], 10).unwrap();
+    /// octree.insert([1, 0, 0], 20).unwrap();
+    /// octree.compute_lod(|values| values.iter().flatten().max().cloned());
+    ///
+    /// assert_eq!(octree.node_lod([0, 0, 0], 0), Some(20));
+    /// ```
+    ///
+    pub fn compute_lod<F: Fn(&[Option<T>]) -> Option<T>>(&mut self, aggregate: F) {
+        self.root_mut().compute_lod(&aggregate);
+    }
+
+    /// Get the level of detail value cached by `compute_lod` for the node containing a given
+    /// location, `level` levels below the root
+    ///
+    /// Level `0` is the root. If a leaf is reached before `level`, its data is returned
+    /// instead. Out of bounds locations and empty regions return `None`.
+    pub fn node_lod(&self, loc: [u16; 3], level: u8) -> Option<T> {
+        let mut node_loc = self.loc_from_array(loc);
+        if self.contains_loc(&node_loc) {
+            self.root.lod_at(&mut node_loc, level)
+        } else {
+            None
+        }
+    }
+
+    /// Iterate over the nodes of the `Octree<T>` down to a given depth, treating each subtree
+    /// below it as a single entry, for loading the `Octree<T>` progressively
+    ///
+    /// Depth `0` is the eight octants of the root, and each further level halves the size of
+    /// the entries. Every entry is given as its origin and edge length, with the level of detail
+    /// value cached by `compute_lod` for an internal node, or the data of a leaf. Leaves and
+    /// empty octants reached above `max_depth` are yielded whole, and entries outside the
+    /// extents are skipped. The entries are gathered before the iterator is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([0, 0, 0], 10).unwrap();
+    /// octree.insert([1, 0, 0], 20).unwrap();
+    /// octree.compute_lod(|values| values.iter().flatten().max().cloned());
+    /// let entries: Vec<_> = octree.iter_to_depth(0).collect();
+    ///
+    /// assert_eq!(entries.len(), 8);
+    /// assert_eq!(entries[0], (([0, 0, 0], 8), Some(20)));
+    /// ```
+    ///
+    pub fn iter_to_depth(
+        &self,
+        max_depth: u8,
+    ) -> impl Iterator<Item = (([u16; 3], u16), Option<T>)> {
+        let mut entries = vec![];
+        let extents = self.extents;
+        let mut visit = |origin: [u16; 3], size, data| {
+            if (0..3).all(|axis| origin[axis] < extents[axis]) {
+                entries.push(((origin, size), data));
+            }
+        };
+
+        if self.root.leaf() {
+            visit([0, 0, 0], self.dimension, self.root.get());
+        } else {
+            self.root
+                .for_each_to_depth([0, 0, 0], max_depth.saturating_add(1), &mut visit);
+        }
+        entries.into_iter()
+    }
+
+    /// Find a shortest path between two locations using A* search with 6-connectivity
+    ///
+    /// `passable` is given the value at each location, following `at`, and decides whether the
+    /// path may pass through it. Returns the path from `start` to `goal` inclusive, or `None`
+    /// if either end is out of bounds or impassable, or no path exists.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([1, 0, 0], 255).unwrap();
+    /// let path = octree.find_path([0, 0, 0], [2, 0, 0], |data| data.is_none()).unwrap();
+    ///
+    /// assert_eq!(path.len(), 5);
+    /// ```
+    ///
+    pub fn find_path(
+        &self,
+        start: [u16; 3],
+        goal: [u16; 3],
+        passable: impl Fn(Option<T>) -> bool,
+    ) -> Option<Vec<[u16; 3]>> {
+        let in_bounds = |loc: [u16; 3]| self.contains_loc(&self.loc_from_array(loc));
+        if !in_bounds(start) || !in_bounds(goal) {
+            return None;
+        }
+        if !passable(self.at(start)) || !passable(self.at(goal)) {
+            return None;
+        }
+
+        let heuristic = |loc: [u16; 3]| -> u32 {
+            (0..3)
+                .map(|axis| (i32::from(loc[axis]) - i32::from(goal[axis])).unsigned_abs())
+                .sum()
+        };
+
+        let mut open = BinaryHeap::new();
+        let mut costs = HashMap::new();
+        let mut came_from = HashMap::new();
+        costs.insert(start, 0u32);
+        open.push(Reverse((heuristic(start), 0u32, start)));
+
+        while let Some(Reverse((_, cost, loc))) = open.pop() {
+            if loc == goal {
+                let mut path = vec![goal];
+                let mut current = goal;
+                while let Some(&previous) = came_from.get(&current) {
+                    path.push(previous);
+                    current = previous;
+                }
+                path.reverse();
+                return Some(path);
+            }
+            if cost > costs[&loc] {
+                continue;
+            }
+
+            for neighbor in self.face_neighbors(loc) {
+                let next_cost = cost + 1;
+                let better = costs.get(&neighbor).is_none_or(|&known| next_cost < known);
+                if better && passable(self.at(neighbor)) {
+                    costs.insert(neighbor, next_cost);
+                    came_from.insert(neighbor, loc);
+                    open.push(Reverse((
+                        next_cost + heuristic(neighbor),
+                        next_cost,
+                        neighbor,
+                    )));
+                }
+            }
+        }
+        None
+    }
+
+    /// Cast a ray through the `Octree<T>`, returning the location and value of the first
+    /// occupied voxel it passes through
+    ///
+    /// Voxel `[x, y, z]` occupies the unit cube from `[x, y, z]` to `[x + 1, y + 1, z + 1]`. The
+    /// ray is clipped to the bounds of the `Octree<T>` before stepping through voxels, and the
+    /// default value is not considered occupied.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([8, 2, 3], 255).unwrap();
+    /// let hit = octree.raycast([-4.0, 2.5, 3.5], [1.0, 0.0, 0.0]);
+    ///
+    /// assert_eq!(hit, Some(([8, 2, 3], 255)));
+    /// ```
+    ///
+    pub fn raycast(&self, origin: [f32; 3], dir: [f32; 3]) -> Option<([u16; 3], T)> {
+        cast_ray(self.dimension, origin, dir, |loc| self.stored_at(loc))
+    }
+
+    /// Fill every empty region fully enclosed by occupied voxels with `value`
+    ///
+    /// Empty space is flood-filled (using 6-connectivity) from the outer boundary of the
+    /// `Octree<T>`, and any empty voxel not reached is considered interior and set to `value`.
+    /// Only the box bounding the occupied voxels, padded by one voxel, is flooded, so the cost
+    /// follows the extent of the content rather than the size of the `Octree<T>`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(4).unwrap();
+    /// for x in 0..3 {
+    ///     for y in 0..3 {
+    ///         for z in 0..3 {
+    ///             if [x, y, z] != [1, 1, 1] {
+    ///                 octree.insert([x, y, z], 255).unwrap();
+    ///             }
+    ///         }
+    ///     }
+    /// }
+    /// octree.fill_interior(128);
+    ///
+    /// assert_eq!(octree.at([1, 1, 1]), Some(128));
+    /// assert_eq!(octree.at([3, 3, 3]), None);
+    /// ```
+    ///
+    pub fn fill_interior(&mut self, value: T) {
+        let (min, max) = match self.occupied_bounds() {
+            Some(bounds) => bounds,
+            None => return,
+        };
+
+        // Everything beyond the occupied bounds is outside, so only the bounds padded by one
+        // voxel need flooding, seeded from the edge of the padded box
+        let mut lo = [0u16; 3];
+        let mut hi = [0u16; 3];
+        let mut size = [0usize; 3];
+        for axis in 0..3 {
+            lo[axis] = min[axis].saturating_sub(1);
+            hi[axis] = max[axis].saturating_add(1).min(self.extents[axis] - 1);
+            size[axis] = usize::from(hi[axis] - lo[axis]) + 1;
+        }
+        let index = |loc: [u16; 3]| {
+            usize::from(loc[0] - lo[0])
+                + usize::from(loc[1] - lo[1]) * size[0]
+                + usize::from(loc[2] - lo[2]) * size[0] * size[1]
+        };
+
+        let mut occupied = vec![false; size[0] * size[1] * size[2]];
+        self.root
+            .for_each_occupied([0, 0, 0], lo, hi, &mut |loc, _| occupied[index(loc)] = true);
+
+        let mut outside = vec![false; occupied.len()];
+        let mut queue = VecDeque::new();
+        for z in lo[2]..=hi[2] {
+            for y in lo[1]..=hi[1] {
+                for x in lo[0]..=hi[0] {
+                    let loc = [x, y, z];
+                    let on_edge = (0..3).any(|axis| loc[axis] == lo[axis] || loc[axis] == hi[axis]);
+                    if on_edge && !occupied[index(loc)] {
+                        outside[index(loc)] = true;
+                        queue.push_back(loc);
+                    }
+                }
+            }
+        }
+
+        while let Some(loc) = queue.pop_front() {
+            for neighbor in self.face_neighbors(loc) {
+                let within =
+                    (0..3).all(|axis| neighbor[axis] >= lo[axis] && neighbor[axis] <= hi[axis]);
+                if within && !outside[index(neighbor)] && !occupied[index(neighbor)] {
+                    outside[index(neighbor)] = true;
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+
+        for z in lo[2]..=hi[2] {
+            for y in lo[1]..=hi[1] {
+                for x in lo[0]..=hi[0] {
+                    let loc = [x, y, z];
+                    if !outside[index(loc)] && !occupied[index(loc)] {
+                        self.insert(loc, value).unwrap();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Collect every occupied voxel of the `Octree<T>` into a `HashMap`, keyed by location
+    ///
+    /// Simplified nodes are expanded into an entry for each voxel they cover.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([12, 10, 6], 128).unwrap();
+    /// let map = octree.to_hashmap();
+    ///
+    /// assert_eq!(map.get(&[12, 10, 6]), Some(&128));
+    /// ```
+    ///
+    pub fn to_hashmap(&self) -> HashMap<[u16; 3], T> {
+        let mut map = HashMap::new();
+        self.for_each_voxel(|loc, data| {
+            map.insert(loc, data);
+        });
+        map
+    }
+
+    /// Get the occupancy of a single Z-slice as a bitmask, packed into `u64` words
+    ///
+    /// The cell at `(x, y)` is stored in bit `y * width + x`, counting from the least
+    /// significant bit of the first word, where `width` is the extent of the `Octree<T>` along
+    /// the X axis. A set bit marks an occupied cell.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([3, 0, 5], 255).unwrap();
+    /// let mask = octree.slice_mask(5).unwrap();
+    ///
+    /// assert_eq!(mask.len(), 4);
+    /// assert_eq!(mask[0], 1 << 3);
+    /// ```
+    ///
+    pub fn slice_mask(&self, z: u16) -> Result<Vec<u64>, OctreeError> {
+        if z >= self.extents[2] {
+            return Err(OctreeError::OutOfBoundsError([0, 0, z]));
+        }
+
+        let width = usize::from(self.extents[0]);
+        let height = usize::from(self.extents[1]);
+        let mut mask = vec![0u64; (width * height).div_ceil(64)];
+        self.root.for_each_leaf([0, 0, 0], &mut |origin, size, _| {
+            if z < origin[2] || z >= origin[2] + size {
+                return;
+            }
+            for y in origin[1]..(origin[1] + size).min(self.extents[1]) {
+                for x in origin[0]..(origin[0] + size).min(self.extents[0]) {
+                    let bit = usize::from(y) * width + usize::from(x);
+                    mask[bit / 64] |= 1 << (bit % 64);
+                }
+            }
+        });
+        Ok(mask)
+    }
+
+    /// Get the values of a single Z-slice as a 2D grid, indexed as `grid[y][x]`
+    ///
+    /// The grid spans the extents of the `Octree<T>` along the X and Y axes, and holds `None`
+    /// for empty cells, whatever the default value. Returns an `OutOfBoundsError` if `z` is out
+    /// of bounds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([3, 1, 5], 255).unwrap();
+    /// let grid = octree.slice(5).unwrap();
+    ///
+    /// assert_eq!(grid.len(), 16);
+    /// assert_eq!(grid[1][3], Some(255));
+    /// assert_eq!(grid[3][1], None);
+    /// ```
+    ///
+    pub fn slice(&self, z: u16) -> Result<Vec<Vec<Option<T>>>, OctreeError> {
+        if z >= self.extents[2] {
+            return Err(OctreeError::OutOfBoundsError([0, 0, z]));
+        }
+
+        let width = usize::from(self.extents[0]);
+        let mut grid = vec![vec![None; width]; usize::from(self.extents[1])];
+        let max = [self.extents[0] - 1, self.extents[1] - 1, z];
+        self.root
+            .for_each_occupied([0, 0, 0], [0, 0, z], max, &mut |loc, data| {
+                grid[usize::from(loc[1])][usize::from(loc[0])] = Some(data)
+            });
+        Ok(grid)
+    }
+
+    /// Get the values of every Z-slice as a stack of row-major 2D buffers, with `empty` in
+    /// unoccupied cells
+    ///
+    /// Slice `z` of the stack holds the cell at `(x, y)` at index `y * width + x`, where `width`
+    /// is the extent of the `Octree<T>` along the X axis, so a cubic `Octree<T>` produces
+    /// `dimension` slices of `dimension * dimension` cells. Each occupied leaf is written
+    /// straight into every slice it spans, and unoccupied cells hold `empty` whatever the
+    /// default value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([3, 1, 5], 255).unwrap();
+    /// let stack = octree.to_image_stack(0);
+    ///
+    /// assert_eq!(stack.len(), 16);
+    /// assert_eq!(stack[5][16 + 3], 255);
+    /// assert_eq!(stack[4][16 + 3], 0);
+    /// ```
+    ///
+    pub fn to_image_stack(&self, empty: T) -> Vec<Vec<T>> {
+        let width = usize::from(self.extents[0]);
+        let height = usize::from(self.extents[1]);
+        let mut stack = vec![vec![empty; width * height]; usize::from(self.extents[2])];
+        self.root
+            .for_each_leaf([0, 0, 0], &mut |origin, size, data| {
+                let last = |axis: usize| (origin[axis] + size).min(self.extents[axis]);
+                let (x_start, x_end) = (usize::from(origin[0]), usize::from(last(0)));
+                for z in origin[2]..last(2) {
+                    let slice = &mut stack[usize::from(z)];
+                    for y in origin[1]..last(1) {
+                        let row = usize::from(y) * width;
+                        for cell in &mut slice[row + x_start..row + x_end] {
+                            *cell = data;
+                        }
+                    }
+                }
+            });
+        stack
+    }
+
+    /// Get every occupied voxel with an empty or out of bounds face neighbor
+    ///
+    /// This is equivalent to `shell(1)`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([4, 4, 4], 255).unwrap();
+    ///
+    /// assert_eq!(octree.surface_voxels(), vec![([4, 4, 4], 255)]);
+    /// ```
+    ///
+    pub fn surface_voxels(&self) -> Vec<([u16; 3], T)> {
+        self.shell(1)
+    }
+
+    /// Get the surface voxel whose center is nearest to a point, along with an approximate
+    /// outward normal
+    ///
+    /// Surface voxels are those returned by `surface_voxels`, and `p` is given in voxel
+    /// coordinates, so the center of the voxel at `[x, y, z]` is `[x + 0.5, y + 0.5, z + 0.5]`.
+    /// The normal is the normalized sum of the directions of the voxel's empty or out of bounds
+    /// faces, preferring those facing `p`, and is zero if they cancel out. Nodes further away
+    /// than the nearest voxel found so far are skipped. Returns `None` if the `Octree<T>` is
+    /// empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.fill([8, 0, 0], [11, 15, 15], 255).unwrap();
+    ///
+    /// let (loc, normal) = octree.closest_surface([3.0, 5.5, 5.5]).unwrap();
+    /// assert_eq!(loc, [8, 5, 5]);
+    /// assert_eq!(normal, [-1.0, 0.0, 0.0]);
+    /// ```
+    ///
+    pub fn closest_surface(&self, p: [f32; 3]) -> Option<([u16; 3], [f32; 3])> {
+        let mut closest = None;
+        self.closest_surface_in(&self.root, [0, 0, 0], p, &mut closest);
+        closest.map(|(_, loc)| (loc, self.surface_normal(loc, p)))
+    }
+
+    /// Get every occupied voxel within `thickness` steps of an empty or out of bounds voxel,
+    /// using 6-connectivity
+    ///
+    /// Voxels are returned in the order they are stored in the `Octree<T>`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// for x in 0..3 {
+    ///     octree.insert([x, 4, 4], 255).unwrap();
+    /// }
+    ///
+    /// assert_eq!(octree.shell(1).len(), 3);
+    /// assert!(octree.shell(0).is_empty());
+    /// ```
+    ///
+    pub fn shell(&self, thickness: u8) -> Vec<([u16; 3], T)> {
+        let mut voxels = vec![];
+        self.for_each_voxel(|loc, data| voxels.push((loc, data)));
+
+        let mut distances = HashMap::new();
+        let mut queue = VecDeque::new();
+        if thickness > 0 {
+            for (loc, _) in &voxels {
+                let neighbors = self.face_neighbors(*loc);
+                if neighbors.len() < 6 || neighbors.iter().any(|n| self.stored_at(*n).is_none()) {
+                    distances.insert(*loc, 1);
+                    queue.push_back(*loc);
+                }
+            }
+        }
+
+        while let Some(loc) = queue.pop_front() {
+            let distance = distances[&loc];
+            if distance == thickness {
+                continue;
+            }
+
+            for neighbor in self.face_neighbors(loc) {
+                if !distances.contains_key(&neighbor) && self.stored_at(neighbor).is_some() {
+                    distances.insert(neighbor, distance + 1);
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+
+        voxels.retain(|(loc, _)| distances.contains_key(loc));
+        voxels
+    }
+
+    /// Iterate over every unoccupied location within the bounds of the occupied voxels
+    ///
+    /// Occupied simplified nodes are skipped as a whole, rather than visiting each voxel.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([0, 0, 0], 255).unwrap();
+    /// octree.insert([2, 0, 0], 255).unwrap();
+    /// let empty: Vec<[u16; 3]> = octree.iter_empty_in_bounds().collect();
+    ///
+    /// assert_eq!(empty, vec![[1, 0, 0]]);
+    /// ```
+    ///
+    pub fn iter_empty_in_bounds(&self) -> impl Iterator<Item = [u16; 3]> {
+        let mut empty = vec![];
+        if let Some((min, max)) = self.occupied_bounds() {
+            self.root
+                .for_each_empty([0, 0, 0], min, max, &mut |loc| empty.push(loc));
+        }
+        empty.into_iter()
+    }
+
+    /// Get the value stored at a given in-bounds location, ignoring any default value
+    fn stored_at(&self, loc: [u16; 3]) -> Option<T> {
+        let mut node_loc = self.loc_from_array(loc);
+        self.root.at(&mut node_loc)
+    }
+
+    /// Set or clear the scratch mark of the deepest node covering a given location
+    fn set_marked(&mut self, loc: [u16; 3], marked: bool) -> bool {
+        let mut node_loc = self.loc_from_array(loc);
+        if !self.contains_loc(&node_loc) {
+            return false;
+        }
+        match self.root_mut().node_as_mut(&mut node_loc) {
+            Some(node) => {
+                node.set_marked(marked);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Construct an empty `Octree<U>` with the same dimensions as this `Octree<T>`
+    fn empty_like<U: Copy + PartialEq>(&self) -> Octree<U> {
+        Octree {
+            dimension: self.dimension,
+            extents: self.extents,
+            max_depth: self.max_depth,
+            root: Arc::new(OctreeNode::construct_root(self.dimension)),
+            default: None,
+            simplification: SimplificationMode::Eager,
+            bounds_policy: self.bounds_policy,
+            metric: None,
+            meta: self.meta.clone(),
+            tracker: ChangeTracker::default(),
+            occupied: OccupiedCount::new(0),
+            paths: PathCache::default(),
+        }
+    }
+
+    /// Get the first occupied voxel, visiting octants depth first, whose value is `better` than
+    /// that of every voxel before it
+    fn arg_best<F: Fn(T, T) -> bool>(&self, better: F) -> Option<([u16; 3], T)> {
+        let mut best: Option<([u16; 3], T)> = None;
+        self.root.for_each_leaf([0, 0, 0], &mut |origin, _, data| {
+            if best.is_none_or(|(_, best)| better(data, best)) {
+                best = Some((origin, data));
+            }
+        });
+        best
+    }
+
+    /// Combine this `Octree<T>` with another of the same extents voxel by voxel, by traversing
+    /// both in lockstep
+    ///
+    /// `f` is given the value of each side at a voxel, including any default value. Voxels empty
+    /// on both sides are left empty, and read as `f` of the two default values if either
+    /// `Octree<T>` has one.
+    fn zip_with<F>(&self, other: &Octree<T>, f: F) -> Result<Octree<T>, OctreeError>
+    where
+        F: Fn(Option<T>, Option<T>) -> Option<T>,
+    {
+        if self.extents != other.extents {
+            return Err(OctreeError::DimensionError);
+        }
+
+        let (default_a, default_b) = (self.default, other.default);
+        let within = self.equivalence();
+        let root = OctreeNode::combine(
+            Some(&self.root),
+            Some(&other.root),
+            self.dimension,
+            &|a: Option<T>, b: Option<T>| {
+                if a.is_none() && b.is_none() {
+                    None
+                } else {
+                    f(a.or(default_a), b.or(default_b))
+                }
+            },
+            &within,
+        );
+
+        let mut octree = self.empty_like();
+        if let Some(root) = root {
+            octree.root = root;
+            octree.occupied.invalidate();
+        }
+        if default_a.is_some() || default_b.is_some() {
+            octree.default = f(default_a, default_b);
+        }
+        Ok(octree)
+    }
+
+    /// Visit every occupied voxel, expanding simplified nodes into their individual voxels
+    fn for_each_voxel<F: FnMut([u16; 3], T)>(&self, mut f: F) {
+        self.root
+            .for_each_leaf([0, 0, 0], &mut |origin, size, data| {
+                for z in origin[2]..origin[2] + size {
+                    for y in origin[1]..origin[1] + size {
+                        for x in origin[0]..origin[0] + size {
+                            f([x, y, z], data);
+                        }
+                    }
+                }
+            });
+    }
+
+    /// Build the node at a given origin and edge length for `from_fn_with_uniform`, returning
+    /// `None` if it is empty
+    fn build_from_fn<F, U>(
+        origin: [u16; 3],
+        size: u16,
+        f: &mut F,
+        uniform: &mut U,
+        equivalent: &dyn Fn(T, T) -> bool,
+    ) -> Option<Arc<OctreeNode<T>>>
+    where
+        F: FnMut([u16; 3]) -> Option<T>,
+        U: FnMut([u16; 3], u16) -> bool,
+    {
+        if size == 1 || uniform(origin, size) {
+            return f(origin).map(|data| Arc::new(OctreeNode::new_block(size, data)));
+        }
+
+        let half = size / 2;
+        let children: Vec<Option<Arc<OctreeNode<T>>>> = (0..8)
+            .map(|i| {
+                Octree::build_from_fn(child_origin(origin, i, half), half, f, uniform, equivalent)
+            })
+            .collect();
+        if children.iter().all(Option::is_none) {
+            return None;
+        }
+        Some(Arc::new(OctreeNode::from_children(
+            size,
+            children,
+            Some(equivalent),
+        )))
+    }
+
+    /// Complete the deepest node under construction by `from_morton_sorted`, storing it in its
+    /// parent
+    fn finish_morton_level(
+        path: &mut MortonPath<T>,
+        dimension: u16,
+        equivalent: &dyn Fn(T, T) -> bool,
+    ) {
+        let (prefix, children) = path.pop().unwrap();
+        let node = OctreeNode::from_children(dimension >> path.len(), children, Some(equivalent));
+        path.last_mut().unwrap().1[morton_child(prefix)] = Some(Arc::new(node));
+    }
+
+    /// Search a node for a surface voxel nearer to `p` than `closest`, which holds the squared
+    /// distance and location of the nearest one found so far
+    fn closest_surface_in(
+        &self,
+        node: &OctreeNode<T>,
+        origin: [u16; 3],
+        p: [f32; 3],
+        closest: &mut Option<(f32, [u16; 3])>,
+    ) {
+        let nearer = |distance: f32, closest: &Option<(f32, [u16; 3])>| {
+            closest.is_none_or(|(best, _)| distance < best)
+        };
+        if !nearer(box_distance_squared(origin, node.dimension(), p), closest) {
+            return;
+        }
+
+        if node.leaf() {
+            if node.get().is_none() {
+                return;
+            }
+
+            // Only voxels on the faces of a block can border an empty voxel
+            let last =
+                |axis: usize| (origin[axis] + (node.dimension() - 1)).min(self.extents[axis] - 1);
+            let on_face =
+                |loc: [u16; 3], axis: usize| loc[axis] == origin[axis] || loc[axis] == last(axis);
+            for z in origin[2]..=last(2) {
+                for y in origin[1]..=last(1) {
+                    let step = if on_face([0, y, z], 1) || on_face([0, y, z], 2) {
+                        1
+                    } else {
+                        (last(0) - origin[0]).max(1)
+                    };
+                    for x in (origin[0]..=last(0)).step_by(usize::from(step)) {
+                        let loc = [x, y, z];
+                        let distance = box_distance_squared(loc, 1, p);
+                        if nearer(distance, closest) && self.is_surface(loc) {
+                            *closest = Some((distance, loc));
+                        }
+                    }
+                }
+            }
+            return;
+        }
+
+        let half = node.dimension() / 2;
+        let mut children: Vec<_> = node
+            .children_ref()
+            .enumerate()
+            .filter_map(|(i, child)| child.map(|child| (child_origin(origin, i, half), child)))
+            .filter(|(child_origin, _)| (0..3).all(|axis| child_origin[axis] < self.extents[axis]))
+            .map(|(child_origin, child)| {
+                (
+                    box_distance_squared(child_origin, half, p),
+                    child_origin,
+                    child,
+                )
+            })
+            .collect();
+        children.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        for (_, child_origin, child) in children {
+            self.closest_surface_in(child, child_origin, p, closest);
+        }
+    }
+
+    /// Gather the occupied voxels of a node whose centers lie within `radius` of `center`
+    fn sphere_voxels_in(
+        &self,
+        node: &OctreeNode<T>,
+        origin: [u16; 3],
+        center: [f32; 3],
+        radius: f32,
+        voxels: &mut Vec<([u16; 3], T)>,
+    ) {
+        if box_distance_squared(origin, node.dimension(), center) > radius * radius {
+            return;
+        }
+
+        if !node.leaf() {
+            let half = node.dimension() / 2;
+            for (i, child) in node.children_ref().enumerate() {
+                if let Some(child) = child {
+                    let child_origin = child_origin(origin, i, half);
+                    self.sphere_voxels_in(child, child_origin, center, radius, voxels);
+                }
+            }
+            return;
+        }
+
+        if let Some(data) = node.get() {
+            // Only expand the part of the block within the bounding box of the sphere
+            let first = |axis: usize| {
+                let reach = (center[axis] - radius - 0.5).floor().max(0.0) as u16;
+                origin[axis].max(reach)
+            };
+            let last = |axis: usize| {
+                let reach = (center[axis] + radius - 0.5)
+                    .ceil()
+                    .clamp(0.0, f32::from(u16::MAX)) as u16;
+                (origin[axis] + (node.dimension() - 1))
+                    .min(self.extents[axis] - 1)
+                    .min(reach)
+            };
+            for z in first(2)..=last(2) {
+                for y in first(1)..=last(1) {
+                    for x in first(0)..=last(0) {
+                        if box_distance_squared([x, y, z], 1, center) <= radius * radius {
+                            voxels.push(([x, y, z], data));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Test if an occupied voxel has an empty or out of bounds face neighbor
+    fn is_surface(&self, loc: [u16; 3]) -> bool {
+        let neighbors = self.face_neighbors(loc);
+        neighbors.len() < 6 || neighbors.iter().any(|n| self.stored_at(*n).is_none())
+    }
+
+    /// Get the normalized sum of the directions of the empty or out of bounds faces of a voxel,
+    /// only counting the faces facing `p` if any of them are empty
+    fn surface_normal(&self, loc: [u16; 3], p: [f32; 3]) -> [f32; 3] {
+        let mut facing = [0.0; 3];
+        let mut all = [0.0; 3];
+        for axis in 0..3 {
+            let toward = p[axis] - (f32::from(loc[axis]) + 0.5);
+            for &sign in &[-1.0f32, 1.0] {
+                let empty = if sign < 0.0 {
+                    loc[axis] == 0 || {
+                        let mut neighbor = loc;
+                        neighbor[axis] -= 1;
+                        self.stored_at(neighbor).is_none()
+                    }
+                } else {
+                    loc[axis] + 1 >= self.extents[axis] || {
+                        let mut neighbor = loc;
+                        neighbor[axis] += 1;
+                        self.stored_at(neighbor).is_none()
+                    }
+                };
+
+                if empty {
+                    all[axis] += sign;
+                    if sign * toward > 0.0 {
+                        facing[axis] += sign;
+                    }
+                }
+            }
+        }
+
+        let normal = if facing == [0.0; 3] { all } else { facing };
+        let length = normal.iter().map(|n| n * n).sum::<f32>().sqrt();
+        if length > 0.0 {
+            [normal[0] / length, normal[1] / length, normal[2] / length]
+        } else {
+            normal
+        }
+    }
+
+    /// Get the in-bounds locations sharing a face with the given location
+    fn face_neighbors(&self, loc: [u16; 3]) -> Vec<[u16; 3]> {
+        let mut neighbors = Vec::with_capacity(6);
+        for axis in 0..3 {
+            if loc[axis] > 0 {
+                let mut neighbor = loc;
+                neighbor[axis] -= 1;
+                neighbors.push(neighbor);
+            }
+            if loc[axis] + 1 < self.extents[axis] {
+                let mut neighbor = loc;
+                neighbor[axis] += 1;
+                neighbors.push(neighbor);
+            }
+        }
+        neighbors
+    }
+}
+
+/// Nodes under construction by `Octree::from_morton_sorted`, from the root down, each with the
+/// Morton key prefix of its location and the children gathered so far
+type MortonPath<T> = Vec<(u64, Vec<Option<Arc<OctreeNode<T>>>>)>;
+
+/// Location yielded by `Octree::join`, with the value of each `Octree<T>` there
+type JoinedCell<T> = ([u16; 3], Option<T>, Option<T>);
+
+/// Get the squared distance from a point to the nearest voxel center within a cube
+fn box_distance_squared(origin: [u16; 3], size: u16, p: [f32; 3]) -> f32 {
+    (0..3)
+        .map(|axis| {
+            let low = f32::from(origin[axis]) + 0.5;
+            let high = f32::from(origin[axis]) + f32::from(size) - 0.5;
+            let distance = (low - p[axis]).max(p[axis] - high).max(0.0);
+            distance * distance
+        })
+        .sum()
+}
+
+/// Get the index of the child octant selected by the lowest three bits of a Morton key
+fn morton_child(key: u64) -> usize {
+    let octant = [
+        (key & 1) as u16,
+        ((key >> 1) & 1) as u16,
+        ((key >> 2) & 1) as u16,
+    ];
+    child_index([0, 0, 0], 1, octant)
+}
+
+/// Lazy iterator over the occupied voxels of an `Octree<T>` within a box, returned by
+/// `Octree::iter_region`
+struct RegionIterator<'a, T: 'a> {
+    min: [u16; 3],
+    max: [u16; 3],
+    stack: Vec<(&'a OctreeNode<T>, [u16; 3])>,
+    block: Option<RegionBlock<T>>,
+}
+
+/// Part of an occupied leaf within the box of a `RegionIterator<T>`, with the next location to
+/// be yielded from it
+struct RegionBlock<T> {
+    min: [u16; 3],
+    max: [u16; 3],
+    next: [u16; 3],
+    data: T,
+}
+
+impl<'a, T> Iterator for RegionIterator<'a, T>
+where
+    T: Copy + PartialEq,
+{
+    type Item = ([u16; 3], T);
+
+    fn next(&mut self) -> Option<([u16; 3], T)> {
+        loop {
+            if let Some(block) = self.block.as_mut() {
+                let loc = block.next;
+                let data = block.data;
+                if loc[0] < block.max[0] {
+                    block.next[0] += 1;
+                } else if loc[1] < block.max[1] {
+                    block.next = [block.min[0], loc[1] + 1, loc[2]];
+                } else if loc[2] < block.max[2] {
+                    block.next = [block.min[0], block.min[1], loc[2] + 1];
+                } else {
+                    self.block = None;
+                }
+                return Some((loc, data));
+            }
+
+            let (node, origin) = self.stack.pop()?;
+            if node.leaf() {
+                if let Some(data) = node.get() {
+                    let size = node.dimension();
+                    let min = [
+                        origin[0].max(self.min[0]),
+                        origin[1].max(self.min[1]),
+                        origin[2].max(self.min[2]),
+                    ];
+                    let max = [
+                        (origin[0] + (size - 1)).min(self.max[0]),
+                        (origin[1] + (size - 1)).min(self.max[1]),
+                        (origin[2] + (size - 1)).min(self.max[2]),
+                    ];
+                    self.block = Some(RegionBlock {
+                        min,
+                        max,
+                        next: min,
+                        data,
+                    });
+                }
+                continue;
+            }
+
+            let half = node.dimension() / 2;
+            for i in (0..8).rev() {
+                let child_origin = child_origin(origin, i, half);
+                let overlaps = (0..3).all(|axis| {
+                    self.max[axis] >= child_origin[axis]
+                        && self.min[axis] < child_origin[axis] + half
+                });
+                if let (true, Some(child)) = (overlaps, node.child(i)) {
+                    self.stack.push((child, child_origin));
+                }
+            }
+        }
+    }
+}
+
+/// Axis of an `Octree<T>`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Axis {
+    X = 0,
+    Y,
+    Z,
+}
+
+/// Get a digest of the logical contents of a node, equal for any two nodes holding the same
+/// values regardless of how they are simplified
+fn node_digest<T: Copy + PartialEq + Hash>(node: &OctreeNode<T>) -> u64 {
+    if node.leaf() {
+        return block_digest(node.dimension(), node.get());
+    }
+
+    let half = node.dimension() / 2;
+    let mut hasher = FnvHasher::new();
+    for i in 0..8 {
+        let digest = match node.child(i) {
+            Some(child) => node_digest(child),
+            None => block_digest::<T>(half, None),
+        };
+        hasher.write_u64(digest);
+    }
+    hasher.finish()
+}
+
+/// Get the digest of a uniform cube, as if it were split into single voxels
+fn block_digest<T: Hash>(size: u16, data: Option<T>) -> u64 {
+    let mut hasher = FnvHasher::new();
+    data.hash(&mut hasher);
+    let mut digest = hasher.finish();
+
+    let mut block = 1;
+    while block < size {
+        let mut hasher = FnvHasher::new();
+        for _ in 0..8 {
+            hasher.write_u64(digest);
+        }
+        digest = hasher.finish();
+        block *= 2;
+    }
+    digest
+}
+
+/// 64-bit FNV-1a hasher, used where hashes must be stable across runs and Rust versions
+struct FnvHasher(u64);
+
+impl FnvHasher {
+    fn new() -> FnvHasher {
+        FnvHasher(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for FnvHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+/// Union-find structure used for labelling connected components
+struct DisjointSet {
+    parents: Vec<usize>,
+}
+
+impl DisjointSet {
+    fn new(size: usize) -> DisjointSet {
+        DisjointSet {
+            parents: (0..size).collect(),
+        }
+    }
+
+    /// Find the representative of the set containing `i`, compressing the path on the way
+    fn find(&mut self, i: usize) -> usize {
+        let mut root = i;
+        while self.parents[root] != root {
+            root = self.parents[root];
+        }
+
+        let mut curr = i;
+        while self.parents[curr] != root {
+            let next = self.parents[curr];
+            self.parents[curr] = root;
+            curr = next;
+        }
+
+        root
+    }
+
+    /// Merge the sets containing `a` and `b`
+    fn union(&mut self, a: usize, b: usize) {
+        let root_a = self.find(a);
+        let root_b = self.find(b);
+        if root_a != root_b {
+            self.parents[root_b] = root_a;
+        }
+    }
+}
+
+/// Saved state of an `Octree<T>`, sharing unmodified nodes with the `Octree<T>` it was taken from
+#[derive(Clone)]
+pub struct Snapshot<T> {
+    dimension: u16,
+    extents: [u16; 3],
+    max_depth: u8,
+    root: Arc<OctreeNode<T>>,
+}
+
+/// Metadata describing a single leaf cell of an `Octree<T>`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CellInfo {
+    /// Minimum corner of the cell
+    pub origin: [u16; 3],
+    /// Edge length of the cell
+    pub size: u16,
+    /// Whether the cell is the result of simplifying a uniform region
+    pub simplified: bool,
+    /// Whether the cell holds data
+    pub occupied: bool,
+}
+
+/// A single occupied leaf of an `Octree<T>`, as yielded by `Octree::iter_blocks`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Block<T> {
+    /// Minimum corner of the leaf
+    pub origin: [u16; 3],
+    /// Edge length of the leaf
+    pub size: u16,
+    /// Value held by every voxel of the leaf
+    pub value: T,
+    /// Whether the leaf is the result of simplifying a uniform region
+    pub simplified: bool,
+}
+
+/// Struct providing iterator functionality for `Octree<T>`
+///
+/// Values are yielded depth-first in ascending octant order, as documented on `Octree::iter`.
+/// The traversal advances lazily with each call to `next`, holding shared handles to the
+/// nodes still to be visited rather than copying them.
+pub struct OctreeIterator<T> {
+    node_stack: Vec<Arc<OctreeNode<T>>>,
+    remaining: Cell<Option<usize>>,
+}
+
+impl<T> IntoIterator for Octree<T>
+where
+    T: Copy + PartialEq,
+{
+    type Item = T;
+    type IntoIter = OctreeIterator<T>;
+
+    /// Transform the `Octree<T>` into an iterator, consuming the `Octree<T>`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([0, 0, 0], 255).unwrap();
+    /// let mut iter = octree.into_iter();
+    ///
+    /// assert_eq!(iter.nth(0), Some(255));
+    /// ```
+    ///
+    fn into_iter(self) -> OctreeIterator<T> {
+        OctreeIterator::new(self)
+    }
+}
 
 impl<T> OctreeIterator<T>
 where
     T: Copy + PartialEq,
 {
-    /// Create a new `OctreeIterator<T>` from an `Octree<T>`, consuming it in the process
-    fn new(octree: Octree<T>) -> OctreeIterator<T> {
-        let mut iter = OctreeIterator {
-            node_stack: vec![],
-            value_stack: vec![],
-        };
-        iter.node_stack.push(octree.root.clone());
-        iter.dfs();
-        iter
+    /// Create a new `OctreeIterator<T>` from an `Octree<T>`, consuming it in the process
+    fn new(octree: Octree<T>) -> OctreeIterator<T> {
+        OctreeIterator {
+            node_stack: vec![octree.root],
+            remaining: Cell::new(None),
+        }
+    }
+
+    /// Create a new `OctreeIterator<T>` from an `Octree<T>`, without consuming it
+    fn new_from_ref(octree: &Octree<T>) -> OctreeIterator<T> {
+        OctreeIterator {
+            node_stack: vec![octree.root.clone()],
+            remaining: Cell::new(None),
+        }
+    }
+
+    /// Count the values below every node still to be visited
+    fn count_remaining(&self) -> usize {
+        let mut count = 0;
+        for node in &self.node_stack {
+            node.for_each_leaf([0, 0, 0], &mut |_, _, _| count += 1);
+        }
+        count
+    }
+
+    /// Get the number of nodes waiting to be visited
+    #[cfg(test)]
+    pub(crate) fn pending(&self) -> usize {
+        self.node_stack.len()
+    }
+}
+
+impl<T> Iterator for OctreeIterator<T>
+where
+    T: Copy + PartialEq,
+{
+    type Item = T;
+
+    /// Essential `Iterator` implementation, advancing a depth-first search
+    ///
+    /// Children are pushed in reverse so they are popped in ascending octant order.
+    fn next(&mut self) -> Option<T> {
+        while let Some(node) = self.node_stack.pop() {
+            self.node_stack.extend(node.shared_children().rev());
+            if let Some(data) = node.get() {
+                if let Some(remaining) = self.remaining.get() {
+                    self.remaining.set(Some(remaining - 1));
+                }
+                return Some(data);
+            }
+        }
+        None
     }
 
-    /// Create a new `OctreeIterator<T>` from an `Octree<T>`, without consuming it
-    fn new_from_ref(octree: &Octree<T>) -> OctreeIterator<T> {
-        let mut iter = OctreeIterator {
-            node_stack: vec![],
-            value_stack: vec![],
+    /// Get the exact number of values remaining
+    ///
+    /// The count is computed on the first call by walking the unvisited nodes, and kept up to
+    /// date by `next` afterwards.
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = match self.remaining.get() {
+            Some(remaining) => remaining,
+            None => {
+                let remaining = self.count_remaining();
+                self.remaining.set(Some(remaining));
+                remaining
+            }
         };
-        iter.node_stack.push(octree.root.clone());
-        iter.dfs();
-        iter
+        (remaining, Some(remaining))
     }
+}
 
-    /// Iterator implementation using depth-first search
-    fn dfs(&mut self) {
-        while !self.node_stack.is_empty() {
-            if let Some(curr_node) = self.node_stack.pop() {
-                if let Some(data) = curr_node.get() {
-                    self.value_stack.push(data);
-                };
-                for child in curr_node.children() {
-                    if let Some(child_node) = child {
-                        self.node_stack.push(child_node);
-                    };
+impl<T> ExactSizeIterator for OctreeIterator<T> where T: Copy + PartialEq {}
+
+impl<T> VolumeQuery<T> for Octree<T>
+where
+    T: Copy + PartialEq,
+{
+    fn dimension(&self) -> u16 {
+        self.dimension
+    }
+
+    fn at(&self, loc: [u16; 3]) -> Option<T> {
+        Octree::at(self, loc)
+    }
+
+    fn query_region(&self, min: [u16; 3], max: [u16; 3]) -> Vec<([u16; 3], T)> {
+        Octree::query_region(self, min, max)
+    }
+}
+
+/// Hashes the logical contents of the `Octree<T>`, so two `Octree<T>`s holding the same values
+/// hash equally even if they are simplified differently
+impl<T> Hash for Octree<T>
+where
+    T: Copy + PartialEq + Hash,
+{
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.dimension.hash(state);
+        self.extents.hash(state);
+        self.default.hash(state);
+        state.write_u64(node_digest(&self.root));
+    }
+}
+
+impl<T> Octree<T>
+where
+    T: Copy + PartialEq + VoxelCodec,
+{
+    /// Write the voxels where this `Octree<T>` differs from `base` as a binary delta
+    ///
+    /// Each stored value that is new or changed is written with its location, and each location
+    /// stored in `base` but empty here is written as a tombstone. Default values are ignored.
+    /// Apply the delta to a copy of `base` with `apply_delta`.
+    ///
+    /// Returns a `DimensionError` if the extents of the two `Octree<T>`s differ, or an `IoError`
+    /// if writing fails.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// let base = Octree::<u8>::new(16).unwrap();
+    /// let mut edited = base.clone();
+    /// edited.insert([12, 10, 6], 128).unwrap();
+    ///
+    /// let mut delta = vec![];
+    /// edited.encode_delta(&base, &mut delta).unwrap();
+    ///
+    /// let mut synced = base.clone();
+    /// synced.apply_delta(&delta[..]).unwrap();
+    /// assert_eq!(synced.at([12, 10, 6]), Some(128));
+    /// ```
+    ///
+    pub fn encode_delta<W: Write>(
+        &self,
+        base: &Octree<T>,
+        mut writer: W,
+    ) -> Result<(), OctreeError> {
+        if self.extents != base.extents {
+            return Err(OctreeError::DimensionError);
+        }
+
+        let mut changes = vec![];
+        self.for_each_voxel(|loc, data| {
+            if base.stored_at(loc) != Some(data) {
+                changes.push((loc, Some(data)));
+            }
+        });
+        base.for_each_voxel(|loc, _| {
+            if self.stored_at(loc).is_none() {
+                changes.push((loc, None));
+            }
+        });
+
+        write_loc(&mut writer, self.extents)?;
+        (changes.len() as u64).write_to(&mut writer)?;
+        for (loc, data) in changes {
+            write_loc(&mut writer, loc)?;
+            match data {
+                Some(data) => {
+                    DELTA_VALUE.write_to(&mut writer)?;
+                    data.write_to(&mut writer)?;
                 }
+                None => DELTA_TOMBSTONE.write_to(&mut writer)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply a binary delta written by `encode_delta`
+    ///
+    /// The whole delta is read before any voxel is changed, so the `Octree<T>` is left unchanged
+    /// if it cannot be applied. Returns a `DimensionError` if the delta was encoded for different
+    /// extents, an `OutOfBoundsError` if it holds a location outside them, or an `IoError` if it
+    /// cannot be read.
+    pub fn apply_delta<R: Read>(&mut self, mut reader: R) -> Result<(), OctreeError> {
+        if read_loc(&mut reader)? != self.extents {
+            return Err(OctreeError::DimensionError);
+        }
+
+        let count = u64::read_from(&mut reader)?;
+        let mut changes = vec![];
+        for _ in 0..count {
+            let loc = read_loc(&mut reader)?;
+            if !self.contains_loc(&self.loc_from_array(loc)) {
+                return Err(OctreeError::OutOfBoundsError(loc));
+            }
+            let data = match u8::read_from(&mut reader)? {
+                DELTA_TOMBSTONE => None,
+                DELTA_VALUE => Some(T::read_from(&mut reader)?),
+                _ => return Err(OctreeError::IoError(io::ErrorKind::InvalidData)),
             };
+            changes.push((loc, data));
+        }
+
+        for (loc, data) in changes {
+            match data {
+                Some(data) => self.insert(loc, data)?,
+                None => {
+                    self.take(loc);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the `Octree<T>` in a compact binary form, followed by a CRC-32 checksum
+    ///
+    /// The extents, default value and node count are written as a header, then every node in
+    /// depth-first order. Read it back with `load_from`. Returns an `IoError` if writing fails.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([12, 10, 6], 255).unwrap();
+    ///
+    /// let mut bytes = vec![];
+    /// octree.save_to(&mut bytes).unwrap();
+    /// let loaded = Octree::<u8>::load_from(&bytes[..]).unwrap();
+    ///
+    /// assert_eq!(loaded.at([12, 10, 6]), Some(255));
+    /// ```
+    ///
+    pub fn save_to<W: Write>(&self, mut writer: W) -> Result<(), OctreeError> {
+        let mut payload = vec![];
+        write_loc(&mut payload, self.extents)?;
+        match self.default {
+            None => 0u8.write_to(&mut payload)?,
+            Some(default) => {
+                1u8.write_to(&mut payload)?;
+                default.write_to(&mut payload)?;
+            }
+        }
+        self.root.binary_len().write_to(&mut payload)?;
+        self.root.write_binary(&mut payload)?;
+
+        writer.write_all(&payload)?;
+        crc32(&payload).write_to(&mut writer)?;
+        Ok(())
+    }
+
+    /// Read an `Octree<T>` written by `save_to`
+    ///
+    /// The whole input is read and checked against its checksum before any node is built.
+    /// Returns `CorruptData` if the checksum does not match, or if the data ends before every
+    /// node in the header has been read, so a truncated or damaged file is never loaded. Returns
+    /// an `IoError` if reading fails.
+    pub fn load_from<R: Read>(mut reader: R) -> Result<Octree<T>, OctreeError> {
+        let mut bytes = vec![];
+        reader.read_to_end(&mut bytes)?;
+        if bytes.len() < 4 {
+            return Err(OctreeError::CorruptData);
+        }
+
+        let (mut payload, mut checksum) = bytes.split_at(bytes.len() - 4);
+        if u32::read_from(&mut checksum)? != crc32(payload) {
+            return Err(OctreeError::CorruptData);
+        }
+
+        let corrupt = |_| OctreeError::CorruptData;
+        let extents = read_loc(&mut payload).map_err(corrupt)?;
+        let mut octree = Octree::new_rect(extents)?;
+        octree.default = match u8::read_from(&mut payload).map_err(corrupt)? {
+            0 => None,
+            1 => Some(T::read_from(&mut payload).map_err(corrupt)?),
+            _ => return Err(OctreeError::CorruptData),
+        };
+
+        let expected = u64::read_from(&mut payload).map_err(corrupt)?;
+        let mut count = 0;
+        let root =
+            read_binary(&mut payload, octree.dimension, None, &mut count).map_err(corrupt)?;
+        match root {
+            Some(root) if count == expected && payload.is_empty() => {
+                octree.root = root;
+                octree.occupied.invalidate();
+                Ok(octree)
+            }
+            _ => Err(OctreeError::CorruptData),
+        }
+    }
+
+    /// Write the `Octree<T>` in the binary form of `save_to`, relative to a base `Octree<T>`
+    ///
+    /// Unlike `encode_delta`, this compares the two trees node by node, so every subtree equal
+    /// to the one at the same position of `base` is written as a single reference, and only the
+    /// differing subtrees are written in full. Trees sharing most of their structure with `base`
+    /// serialize to a fraction of their full size. Read it back with `deserialize_against` and
+    /// the same `base`. No checksum is written.
+    ///
+    /// Returns a `DimensionError` if the extents of the two `Octree<T>`s differ, or an `IoError`
+    /// if writing fails.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut base = Octree::<u8>::new(16).unwrap();
+    /// base.fill([0, 0, 0], [15, 7, 15], 1).unwrap();
+    /// let mut edited = base.clone();
+    /// edited.insert([12, 10, 6], 2).unwrap();
+    ///
+    /// let mut bytes = vec![];
+    /// edited.serialize_against(&base, &mut bytes).unwrap();
+    /// let loaded = Octree::deserialize_against(&base, &bytes[..]).unwrap();
+    ///
+    /// assert_eq!(loaded, edited);
+    /// ```
+    ///
+    pub fn serialize_against<W: Write>(
+        &self,
+        base: &Octree<T>,
+        mut writer: W,
+    ) -> Result<(), OctreeError> {
+        if self.extents != base.extents {
+            return Err(OctreeError::DimensionError);
+        }
+
+        write_loc(&mut writer, self.extents)?;
+        match self.default {
+            None => 0u8.write_to(&mut writer)?,
+            Some(default) => {
+                1u8.write_to(&mut writer)?;
+                default.write_to(&mut writer)?;
+            }
+        }
+        self.root.write_against(Some(&base.root), &mut writer)?;
+        Ok(())
+    }
+
+    /// Read an `Octree<T>` written by `serialize_against` with the same `base`
+    ///
+    /// Unchanged subtrees are shared with `base` rather than copied. The result takes its
+    /// simplification mode and metric from `base`. Returns a `DimensionError` if the data was
+    /// written for different extents, or an `IoError` if it cannot be read or is malformed.
+    pub fn deserialize_against<R: Read>(
+        base: &Octree<T>,
+        mut reader: R,
+    ) -> Result<Octree<T>, OctreeError> {
+        if read_loc(&mut reader)? != base.extents {
+            return Err(OctreeError::DimensionError);
+        }
+
+        let mut octree = Octree::new_rect(base.extents)?;
+        octree.simplification = base.simplification;
+        octree.metric = base.metric;
+        octree.default = match u8::read_from(&mut reader)? {
+            0 => None,
+            1 => Some(T::read_from(&mut reader)?),
+            _ => return Err(OctreeError::IoError(io::ErrorKind::InvalidData)),
+        };
+
+        match read_binary(&mut reader, octree.dimension, Some(&base.root), &mut 0)? {
+            Some(root) => {
+                octree.root = root;
+                octree.occupied.invalidate();
+                Ok(octree)
+            }
+            None => Err(OctreeError::IoError(io::ErrorKind::InvalidData)),
         }
     }
 }
 
-impl<T> Iterator for OctreeIterator<T>
+/// Tag of a tombstone entry in a delta written by `Octree::encode_delta`
+const DELTA_TOMBSTONE: u8 = 0;
+
+/// Tag of a value entry in a delta written by `Octree::encode_delta`
+const DELTA_VALUE: u8 = 1;
+
+impl<T> Octree<T>
 where
-    T: Copy,
+    T: Copy + PartialEq + fmt::Display,
 {
-    type Item = T;
+    /// Write every occupied voxel as a vertex of an ASCII PLY point cloud
+    ///
+    /// Each vertex is placed at the centre of its voxel, transformed into physical space by the
+    /// `origin` and `voxel_size` of the `OctreeMeta`, and carries the voxel value as a `value`
+    /// property.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([1, 0, 0], 255).unwrap();
+    ///
+    /// let mut ply = vec![];
+    /// octree.export_ply(&mut ply).unwrap();
+    ///
+    /// assert!(String::from_utf8(ply).unwrap().ends_with("1.5 0.5 0.5 255\n"));
+    /// ```
+    ///
+    pub fn export_ply<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let mut voxels = vec![];
+        self.for_each_voxel(|loc, data| voxels.push((loc, data)));
 
-    /// Essential `Iterator` implementation
-    fn next(&mut self) -> Option<T> {
-        self.value_stack.pop()
+        writeln!(writer, "ply")?;
+        writeln!(writer, "format ascii 1.0")?;
+        if !self.meta.name.is_empty() {
+            writeln!(writer, "comment {}", self.meta.name)?;
+        }
+        writeln!(writer, "element vertex {}", voxels.len())?;
+        writeln!(writer, "property float x")?;
+        writeln!(writer, "property float y")?;
+        writeln!(writer, "property float z")?;
+        writeln!(writer, "property float value")?;
+        writeln!(writer, "end_header")?;
+        for (loc, data) in voxels {
+            let [x, y, z] = self.meta.voxel_center(loc);
+            writeln!(writer, "{} {} {} {}", x, y, z, data)?;
+        }
+        Ok(())
+    }
+
+    /// Write every occupied voxel as a row of CSV, with an `x,y,z,value` header
+    ///
+    /// Each row holds the centre of its voxel, transformed into physical space by the `origin`
+    /// and `voxel_size` of the `OctreeMeta`, followed by the voxel value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([1, 0, 0], 255).unwrap();
+    /// octree.meta_mut().origin = [10.0, 0.0, 0.0];
+    /// octree.meta_mut().voxel_size = 2.0;
+    ///
+    /// let mut csv = vec![];
+    /// octree.export_csv(&mut csv).unwrap();
+    ///
+    /// assert_eq!(String::from_utf8(csv).unwrap(), "x,y,z,value\n13,1,1,255\n");
+    /// ```
+    ///
+    pub fn export_csv<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(writer, "x,y,z,value")?;
+        let mut result = Ok(());
+        self.for_each_voxel(|loc, data| {
+            if result.is_ok() {
+                let [x, y, z] = self.meta.voxel_center(loc);
+                result = writeln!(writer, "{},{},{},{}", x, y, z, data);
+            }
+        });
+        result
+    }
+}
+
+impl Octree<bool> {
+    /// Combine two boolean masks of the same extents with logical AND
+    ///
+    /// A voxel is set if it holds `true`, and clear if it holds `false` or is empty. The result
+    /// holds the combined value at every voxel where either mask holds a value.
+    ///
+    /// Returns a `DimensionError` if the masks have different extents.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// let mut a = Octree::<bool>::new(16).unwrap();
+    /// let mut b = Octree::<bool>::new(16).unwrap();
+    /// a.insert([0, 0, 0], true).unwrap();
+    /// b.insert([0, 0, 0], true).unwrap();
+    /// b.insert([1, 0, 0], true).unwrap();
+    ///
+    /// let mask = a.and(&b).unwrap();
+    ///
+    /// assert_eq!(mask.at([0, 0, 0]), Some(true));
+    /// assert_eq!(mask.at([1, 0, 0]), Some(false));
+    /// ```
+    ///
+    pub fn and(&self, other: &Octree<bool>) -> Result<Octree<bool>, OctreeError> {
+        self.zip_with(other, |a, b| Some(is_set(a) && is_set(b)))
+    }
+
+    /// Combine two boolean masks of the same extents with logical OR
+    ///
+    /// Values are combined as in `and`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// let mut a = Octree::<bool>::new(16).unwrap();
+    /// let mut b = Octree::<bool>::new(16).unwrap();
+    /// a.insert([0, 0, 0], true).unwrap();
+    /// b.insert([1, 0, 0], true).unwrap();
+    ///
+    /// let mask = a.or(&b).unwrap();
+    ///
+    /// assert_eq!(mask.at([0, 0, 0]), Some(true));
+    /// assert_eq!(mask.at([1, 0, 0]), Some(true));
+    /// ```
+    ///
+    pub fn or(&self, other: &Octree<bool>) -> Result<Octree<bool>, OctreeError> {
+        self.zip_with(other, |a, b| Some(is_set(a) || is_set(b)))
+    }
+
+    /// Combine two boolean masks of the same extents with logical XOR
+    ///
+    /// Values are combined as in `and`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// let mut a = Octree::<bool>::new(16).unwrap();
+    /// let mut b = Octree::<bool>::new(16).unwrap();
+    /// a.insert([0, 0, 0], true).unwrap();
+    /// b.insert([0, 0, 0], true).unwrap();
+    /// b.insert([1, 0, 0], true).unwrap();
+    ///
+    /// let mask = a.xor(&b).unwrap();
+    ///
+    /// assert_eq!(mask.at([0, 0, 0]), Some(false));
+    /// assert_eq!(mask.at([1, 0, 0]), Some(true));
+    /// ```
+    ///
+    pub fn xor(&self, other: &Octree<bool>) -> Result<Octree<bool>, OctreeError> {
+        self.zip_with(other, |a, b| Some(is_set(a) != is_set(b)))
+    }
+}
+
+impl Octree<u8> {
+    /// Spread the occupied values across the full `0..=255` range by histogram equalization
+    ///
+    /// Each value is remapped by the cumulative distribution of values over the occupied voxels,
+    /// so the lowest value present becomes 0, the highest becomes 255, and crowded ranges of
+    /// values are stretched apart. Empty voxels and the default value are left unchanged, and the
+    /// `Octree<u8>` is simplified again afterwards. A volume holding a single value is unchanged.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([0, 0, 0], 100).unwrap();
+    /// octree.insert([12, 10, 6], 110).unwrap();
+    /// octree.equalize();
+    ///
+    /// assert_eq!(octree.at([0, 0, 0]), Some(0));
+    /// assert_eq!(octree.at([12, 10, 6]), Some(255));
+    /// ```
+    ///
+    pub fn equalize(&mut self) {
+        let mut histogram = [0u64; 256];
+        self.root.for_each_leaf([0, 0, 0], &mut |_, size, data| {
+            histogram[usize::from(data)] += u64::from(size).pow(3);
+        });
+
+        let mut cumulative = [0u64; 256];
+        let mut total = 0;
+        for (value, count) in histogram.iter().enumerate() {
+            total += count;
+            cumulative[value] = total;
+        }
+        let lowest = histogram
+            .iter()
+            .copied()
+            .find(|&count| count > 0)
+            .unwrap_or(0);
+        if total == lowest {
+            return;
+        }
+
+        let levels: Vec<u8> = cumulative
+            .iter()
+            .map(|&count| {
+                let spread = count.saturating_sub(lowest) as f64 / (total - lowest) as f64;
+                (spread * 255.0).round() as u8
+            })
+            .collect();
+        let root = self.root.map(&|data: u8| levels[usize::from(data)]);
+        *self.root_mut() = root;
+        self.resimplify();
+    }
+}
+
+/// Test whether a voxel of a boolean mask is set
+fn is_set(value: Option<bool>) -> bool {
+    value == Some(true)
+}
+
+#[cfg(feature = "json")]
+impl<T> Octree<T>
+where
+    T: Copy + PartialEq + Serialize,
+{
+    /// Export the occupied voxels as JSON, holding the `dimension` of the `Octree<T>` and a flat
+    /// `voxels` array of `{"x", "y", "z", "value"}` objects
+    ///
+    /// Unlike the serialized node structure, this is easy for other tools to read. Simplified
+    /// nodes are expanded into their individual voxels. Only available with the `json` feature.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `Serialize` implementation of `T` fails.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.insert([12, 10, 6], 128).unwrap();
+    ///
+    /// assert_eq!(
+    ///     octree.to_json_voxels(),
+    ///     r#"{"dimension":16,"voxels":[{"x":12,"y":10,"z":6,"value":128}]}"#
+    /// );
+    /// ```
+    ///
+    pub fn to_json_voxels(&self) -> String {
+        let mut voxels = vec![];
+        self.for_each_voxel(|loc, value| {
+            voxels.push(JsonVoxel {
+                x: loc[0],
+                y: loc[1],
+                z: loc[2],
+                value,
+            })
+        });
+
+        let export = JsonVoxels {
+            dimension: self.dimension,
+            voxels,
+        };
+        serde_json::to_string(&export).expect("voxel values failed to serialize")
+    }
+}
+
+/// Layout of the JSON written by `Octree::to_json_voxels`
+#[cfg(feature = "json")]
+#[derive(Serialize)]
+struct JsonVoxels<T> {
+    dimension: u16,
+    voxels: Vec<JsonVoxel<T>>,
+}
+
+/// A single voxel written by `Octree::to_json_voxels`
+#[cfg(feature = "json")]
+#[derive(Serialize)]
+struct JsonVoxel<T> {
+    x: u16,
+    y: u16,
+    z: u16,
+    value: T,
+}
+
+impl<T> PartialEq for Octree<T>
+where
+    T: PartialEq,
+{
+    fn eq(&self, other: &Octree<T>) -> bool {
+        self.dimension == other.dimension
+            && self.extents == other.extents
+            && self.root == other.root
+            && self.default == other.default
+            && self.simplification == other.simplification
+            && self.bounds_policy == other.bounds_policy
     }
 }
 
 /// Debug printing
 impl<T> fmt::Debug for Octree<T>
 where
-    T: Copy + PartialEq + fmt::Debug,
+    T: fmt::Debug,
 {
-    fn fmt(&self, _f: &mut fmt::Formatter) -> fmt::Result {
-        println!("{:?}", self.root);
-        Ok(())
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.root)
+    }
+}
+
+impl<T> Octree<T>
+where
+    T: Clone,
+{
+    /// Consume the `Octree<T>`, yielding each stored value by move in storage order
+    ///
+    /// Values are moved out of the nodes rather than copied, so large values are never
+    /// duplicated. A simplified node yields its value once, however many voxels it covers, and
+    /// default values are not yielded. Nodes still shared with a clone or snapshot are cloned
+    /// first. The values are gathered before the iterator is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use octo::octree::Octree;
+    /// #
+    /// # let mut octree = Octree::<u8>::new(16).unwrap();
+    /// octree.fill([0, 0, 0], [7, 7, 7], 1).unwrap();
+    /// octree.insert([12, 10, 6], 2).unwrap();
+    ///
+    /// assert_eq!(octree.into_values().collect::<Vec<_>>(), vec![1, 2]);
+    /// ```
+    ///
+    pub fn into_values(self) -> impl Iterator<Item = T> {
+        let mut values = vec![];
+        let root = Arc::try_unwrap(self.root).unwrap_or_else(|shared| (*shared).clone());
+        root.into_values(&mut values);
+        values.into_iter()
     }
 }
@@ -0,0 +1,104 @@
+use math::ray_box_intersection;
+
+/// Read-only voxel queries shared by sparse and dense volumes, such as an `Octree<T>` and a
+/// `DenseView<T>`
+///
+/// Algorithms written against `VolumeQuery<T>` work with either source, such as `raycast`.
+pub trait VolumeQuery<T> {
+    /// Returns the x/y/z dimension of the volume
+    fn dimension(&self) -> u16;
+
+    /// Get the value at a given location, or `None` if it is empty or out of bounds
+    fn at(&self, loc: [u16; 3]) -> Option<T>;
+
+    /// Get every occupied voxel within the inclusive box `min`..=`max`, with its value
+    fn query_region(&self, min: [u16; 3], max: [u16; 3]) -> Vec<([u16; 3], T)>;
+}
+
+/// Cast a ray through any `VolumeQuery<T>`, returning the location and value of the first
+/// occupied voxel it passes through
+///
+/// Voxel `[x, y, z]` occupies the unit cube from `[x, y, z]` to `[x + 1, y + 1, z + 1]`. Unlike
+/// `Octree::raycast`, a location is occupied whenever `VolumeQuery::at` returns a value, so
+/// the default value of an `Octree<T>` counts as occupied.
+///
+/// # Examples
+///
+/// ```
+/// # use octo::{raycast, view_dense};
+/// #
+/// let data = vec![0u8; 8];
+/// let view = view_dense(2, &data).unwrap();
+///
+/// assert_eq!(raycast(&view, [-1.0, 0.5, 0.5], [1.0, 0.0, 0.0]), Some(([0, 0, 0], 0)));
+/// ```
+///
+pub fn raycast<T, V: VolumeQuery<T>>(
+    volume: &V,
+    origin: [f32; 3],
+    dir: [f32; 3],
+) -> Option<([u16; 3], T)> {
+    cast_ray(volume.dimension(), origin, dir, |loc| volume.at(loc))
+}
+
+/// Step a ray voxel by voxel through a cube of the given dimension, returning the first
+/// location for which `at` returns a value
+pub fn cast_ray<T, F: Fn([u16; 3]) -> Option<T>>(
+    dimension: u16,
+    origin: [f32; 3],
+    dir: [f32; 3],
+    at: F,
+) -> Option<([u16; 3], T)> {
+    let size = f32::from(dimension);
+    let (near, far) = ray_box_intersection(origin, dir, [0.0; 3], [size; 3])?;
+    let start = near.max(0.0);
+
+    let mut voxel = [0u16; 3];
+    let mut step = [0i8; 3];
+    let mut t_max = [f32::INFINITY; 3];
+    let mut t_delta = [f32::INFINITY; 3];
+    for axis in 0..3 {
+        let p = origin[axis] + dir[axis] * start;
+        voxel[axis] = (p.floor().max(0.0) as u16).min(dimension - 1);
+
+        let cell = f32::from(voxel[axis]);
+        if dir[axis] > 0.0 {
+            step[axis] = 1;
+            t_max[axis] = (cell + 1.0 - origin[axis]) / dir[axis];
+            t_delta[axis] = 1.0 / dir[axis];
+        } else if dir[axis] < 0.0 {
+            step[axis] = -1;
+            t_max[axis] = (cell - origin[axis]) / dir[axis];
+            t_delta[axis] = -1.0 / dir[axis];
+        }
+    }
+
+    loop {
+        if let Some(data) = at(voxel) {
+            return Some((voxel, data));
+        }
+
+        let mut axis = 0;
+        for next in 1..3 {
+            if t_max[next] < t_max[axis] {
+                axis = next;
+            }
+        }
+        if step[axis] == 0 || t_max[axis] > far {
+            return None;
+        }
+
+        if step[axis] > 0 {
+            if voxel[axis] + 1 >= dimension {
+                return None;
+            }
+            voxel[axis] += 1;
+        } else {
+            if voxel[axis] == 0 {
+                return None;
+            }
+            voxel[axis] -= 1;
+        }
+        t_max[axis] += t_delta[axis];
+    }
+}
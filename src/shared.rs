@@ -0,0 +1,78 @@
+use octree::Octree;
+use std::sync::{Arc, RwLock};
+
+/// Handle to an `Octree<T>` shared between threads, allowing many concurrent readers and one
+/// writer at a time
+///
+/// Clones of a `SharedOctree<T>` refer to the same `Octree<T>`. Each helper holds the lock only
+/// for the duration of the call, so handles can be moved into threads freely.
+///
+/// # Examples
+///
+/// ```
+/// # use octo::{Octree, SharedOctree};
+/// # use std::thread;
+/// #
+/// let shared = SharedOctree::new(Octree::<u8>::new(16).unwrap());
+/// let writer = shared.clone();
+/// thread::spawn(move || writer.write(|octree| octree.insert([12, 10, 6], 255)))
+///     .join()
+///     .unwrap()
+///     .unwrap();
+///
+/// assert_eq!(shared.read_at([12, 10, 6]), Some(255));
+/// ```
+///
+pub struct SharedOctree<T>(Arc<RwLock<Octree<T>>>);
+
+impl<T> SharedOctree<T>
+where
+    T: Copy + PartialEq + Send + Sync,
+{
+    /// Constructs a new `SharedOctree<T>` taking ownership of an `Octree<T>`
+    pub fn new(octree: Octree<T>) -> SharedOctree<T> {
+        SharedOctree(Arc::new(RwLock::new(octree)))
+    }
+
+    /// Get the value stored at a given location, as `Octree::at`
+    pub fn read_at(&self, loc: [u16; 3]) -> Option<T> {
+        self.read(|octree| octree.at(loc))
+    }
+
+    /// Get every occupied voxel within the inclusive box `min`..=`max`, as
+    /// `Octree::query_region`
+    pub fn read_region(&self, min: [u16; 3], max: [u16; 3]) -> Vec<([u16; 3], T)> {
+        self.read(|octree| octree.query_region(min, max))
+    }
+
+    /// Run `f` with shared access to the `Octree<T>`, returning its result
+    ///
+    /// Other readers may run at the same time, but writers wait until `f` returns.
+    pub fn read<R, F: FnOnce(&Octree<T>) -> R>(&self, f: F) -> R {
+        let octree = self.0.read().unwrap_or_else(|e| e.into_inner());
+        f(&octree)
+    }
+
+    /// Run `f` with exclusive access to the `Octree<T>`, returning its result
+    ///
+    /// Readers and other writers wait until `f` returns.
+    pub fn write<R, F: FnOnce(&mut Octree<T>) -> R>(&self, f: F) -> R {
+        let mut octree = self.0.write().unwrap_or_else(|e| e.into_inner());
+        f(&mut octree)
+    }
+}
+
+impl<T> Clone for SharedOctree<T> {
+    fn clone(&self) -> SharedOctree<T> {
+        SharedOctree(Arc::clone(&self.0))
+    }
+}
+
+impl<T> From<Octree<T>> for SharedOctree<T>
+where
+    T: Copy + PartialEq + Send + Sync,
+{
+    fn from(octree: Octree<T>) -> SharedOctree<T> {
+        SharedOctree::new(octree)
+    }
+}